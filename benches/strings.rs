@@ -0,0 +1,53 @@
+//! Builds the same long string through repeated `+` and through `join`.
+//!
+//! Run with `cargo bench --bench strings`.
+
+use std::time::{Duration, Instant};
+
+use interpreter::{eval::Eval, lexer::Lexer, parser::Parser};
+
+const PIECES: usize = 1000;
+
+const CONCAT: &str = r#"
+let build = fn(n, acc) {
+    if (n == 0) { return acc; }
+    build(n - 1, acc + "0123456789012345678901234567890123456789012345678901234567890123456789")
+};
+len(build(PIECES, ""))
+"#;
+
+const JOIN: &str = r#"
+let build = fn(n, acc) {
+    if (n == 0) { return acc; }
+    build(n - 1, push(acc, "0123456789012345678901234567890123456789012345678901234567890123456789"))
+};
+len(join(build(PIECES, []), ""))
+"#;
+
+fn run(source: &str) -> Duration {
+    let source = source.replace("PIECES", &PIECES.to_string());
+    let program = Parser::new(Lexer::new(&source)).parse_program().unwrap();
+
+    let start = Instant::now();
+    let result = Eval::new().eval(program).unwrap();
+    let elapsed = start.elapsed();
+
+    assert_eq!(result.to_string(), (PIECES * 70).to_string());
+    elapsed
+}
+
+fn main() {
+    // The evaluator recurses once per Monkey call, so give it room.
+    std::thread::Builder::new()
+        .stack_size(512 * 1024 * 1024)
+        .spawn(|| {
+            for (name, source) in [("concat", CONCAT), ("join", JOIN)] {
+                let runs = (0..10).map(|_| run(source)).collect::<Vec<_>>();
+                let average = runs.iter().sum::<Duration>() / runs.len() as u32;
+                println!("{:<8} {:>10.3?} per run", name, average);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use interpreter::lexer::{Lexer, Token};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let mut lexer = Lexer::new(input);
+    while let Ok(token) = lexer.next_token() {
+        if token == Token::Eof {
+            break;
+        }
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use interpreter::{lexer::Lexer, parser::Parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let _ = Parser::new(Lexer::new(input)).parse_program();
+});
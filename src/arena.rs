@@ -0,0 +1,421 @@
+//! The AST laid out flat: every statement and expression of a program is an entry of one
+//! vector, and nodes refer to their children by `NodeId` instead of owning them in `Box`es.
+//!
+//! Ids are the ones `node_id::number` gives the same program, so side tables built by other
+//! passes index the arena directly. The evaluator still walks the boxed tree from
+//! `Parser::parse_program`; `Parser::parse_arena` gives this form to passes that want it, and
+//! `Arena::to_block` turns it back into the boxed tree.
+
+use std::ops::Index;
+
+use anyhow::Result;
+
+use crate::{
+    ast::{
+        BlockStatement, Expression, Identifier, IfExpression, Imports, Infix, Literal, Pattern,
+        Prefix, Statement,
+    },
+    lexer::Lexer,
+    node_id::NodeId,
+    parser::Parser,
+};
+
+/// The statements of a block, in order.
+pub type Block = Vec<NodeId>;
+
+/// A `Statement` whose expressions are ids.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Stmt {
+    Let(Pattern, NodeId),
+    Return(NodeId),
+    Yield(NodeId),
+    Import(String, Imports),
+    Expression(NodeId),
+    Empty,
+}
+
+/// An `Expression` whose subexpressions and blocks are ids.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Identifier(Identifier),
+    Literal(Literal),
+    Prefix(Prefix, NodeId),
+    Infix(Infix, NodeId, NodeId),
+    If {
+        condition: NodeId,
+        consequence: Block,
+        alternative: Block,
+    },
+    Function {
+        params: Vec<Identifier>,
+        body: Block,
+        generator: bool,
+    },
+    Call {
+        function: NodeId,
+        args: Vec<NodeId>,
+        named: Vec<(Identifier, NodeId)>,
+    },
+    Array(Vec<NodeId>),
+    Tuple(Vec<NodeId>),
+    Hash(Vec<(NodeId, NodeId)>),
+    Index {
+        left: NodeId,
+        index: NodeId,
+    },
+    Assign {
+        target: Identifier,
+        value: NodeId,
+    },
+    Range {
+        start: NodeId,
+        end: NodeId,
+        inclusive: bool,
+    },
+    Spread(NodeId),
+    Await(NodeId),
+    Member {
+        object: NodeId,
+        attribute: Identifier,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Entry {
+    Statement(Stmt),
+    Expression(Expr),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Arena {
+    entries: Vec<Entry>,
+}
+
+impl Arena {
+    /// Lays out `block`, numbering its nodes like `node_id::number`.
+    pub fn from_block(block: &BlockStatement) -> (Arena, Block) {
+        let mut arena = Arena::default();
+        let block = arena.lower_block(block);
+        (arena, block)
+    }
+
+    /// Parses `source`, failing on its first syntax error.
+    pub fn parse(source: &str) -> Result<(Arena, Block)> {
+        Parser::new(Lexer::new(source)).parse_arena()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The statement `id` refers to. Panics if it is an expression.
+    pub fn statement(&self, id: NodeId) -> &Stmt {
+        match &self[id] {
+            Entry::Statement(statement) => statement,
+            Entry::Expression(_) => panic!("node {} is an expression", id.0),
+        }
+    }
+
+    /// The expression `id` refers to. Panics if it is a statement.
+    pub fn expression(&self, id: NodeId) -> &Expr {
+        match &self[id] {
+            Entry::Expression(expression) => expression,
+            Entry::Statement(_) => panic!("node {} is a statement", id.0),
+        }
+    }
+
+    /// The boxed tree of `block`, as the parser would have built it.
+    pub fn to_block(&self, block: &Block) -> BlockStatement {
+        block.iter().map(|&id| self.to_statement(id)).collect()
+    }
+
+    pub fn to_statement(&self, id: NodeId) -> Statement {
+        match self.statement(id) {
+            Stmt::Let(pattern, value) => {
+                Statement::Let(pattern.clone(), self.to_expression(*value))
+            }
+            Stmt::Return(value) => Statement::Return(self.to_expression(*value)),
+            Stmt::Yield(value) => Statement::Yield(self.to_expression(*value)),
+            Stmt::Import(name, imports) => Statement::Import(name.clone(), imports.clone()),
+            Stmt::Expression(value) => Statement::Expression(self.to_expression(*value)),
+            Stmt::Empty => Statement::Empty,
+        }
+    }
+
+    pub fn to_expression(&self, id: NodeId) -> Expression {
+        let boxed = |id: &NodeId| Box::new(self.to_expression(*id));
+        let list = |ids: &[NodeId]| ids.iter().map(|&id| self.to_expression(id)).collect();
+
+        match self.expression(id) {
+            Expr::Identifier(name) => Expression::Identifier(name.clone()),
+            Expr::Literal(literal) => Expression::Literal(literal.clone()),
+            Expr::Prefix(operator, right) => Expression::Prefix(operator.clone(), boxed(right)),
+            Expr::Infix(operator, left, right) => {
+                Expression::Infix(*operator, boxed(left), boxed(right))
+            }
+            Expr::If {
+                condition,
+                consequence,
+                alternative,
+            } => Expression::If(IfExpression {
+                condition: boxed(condition),
+                consequence: self.to_block(consequence),
+                alternative: self.to_block(alternative),
+            }),
+            Expr::Function {
+                params,
+                body,
+                generator,
+            } => Expression::Function {
+                params: params.clone(),
+                body: self.to_block(body),
+                generator: *generator,
+            },
+            Expr::Call {
+                function,
+                args,
+                named,
+            } => Expression::Call {
+                function: boxed(function),
+                args: list(args),
+                named: named
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.to_expression(*value)))
+                    .collect(),
+            },
+            Expr::Array(elements) => Expression::Array(list(elements)),
+            Expr::Tuple(elements) => Expression::Tuple(list(elements)),
+            Expr::Hash(pairs) => Expression::Hash(
+                pairs
+                    .iter()
+                    .map(|(key, value)| (self.to_expression(*key), self.to_expression(*value)))
+                    .collect(),
+            ),
+            Expr::Index { left, index } => Expression::Index {
+                left: boxed(left),
+                index: boxed(index),
+            },
+            Expr::Assign { target, value } => Expression::Assign {
+                target: target.clone(),
+                value: boxed(value),
+            },
+            Expr::Range {
+                start,
+                end,
+                inclusive,
+            } => Expression::Range {
+                start: boxed(start),
+                end: boxed(end),
+                inclusive: *inclusive,
+            },
+            Expr::Spread(inner) => Expression::Spread(boxed(inner)),
+            Expr::Await(inner) => Expression::Await(boxed(inner)),
+            Expr::Member { object, attribute } => Expression::Member {
+                object: boxed(object),
+                attribute: attribute.clone(),
+            },
+        }
+    }
+
+    /// Takes the next id for a node whose children still have to be laid out, so parents come
+    /// before their children as `node_id::number` has them.
+    fn reserve(&mut self) -> NodeId {
+        self.entries.push(Entry::Statement(Stmt::Empty));
+        NodeId((self.entries.len() - 1) as u32)
+    }
+
+    fn lower_block(&mut self, block: &BlockStatement) -> Block {
+        block
+            .iter()
+            .map(|statement| self.lower_statement(statement))
+            .collect()
+    }
+
+    fn lower_statement(&mut self, statement: &Statement) -> NodeId {
+        let id = self.reserve();
+        let statement = match statement {
+            Statement::Let(pattern, value) => Stmt::Let(pattern.clone(), self.lower(value)),
+            Statement::Return(value) => Stmt::Return(self.lower(value)),
+            Statement::Yield(value) => Stmt::Yield(self.lower(value)),
+            Statement::Import(name, imports) => Stmt::Import(name.clone(), imports.clone()),
+            Statement::Expression(value) => Stmt::Expression(self.lower(value)),
+            Statement::Empty => Stmt::Empty,
+        };
+        self.entries[id.0 as usize] = Entry::Statement(statement);
+        id
+    }
+
+    fn lower(&mut self, expression: &Expression) -> NodeId {
+        let id = self.reserve();
+        let expression = match expression {
+            Expression::Identifier(name) => Expr::Identifier(name.clone()),
+            Expression::Literal(literal) => Expr::Literal(literal.clone()),
+            Expression::Prefix(operator, right) => {
+                Expr::Prefix(operator.clone(), self.lower(right))
+            }
+            Expression::Infix(operator, left, right) => {
+                let left = self.lower(left);
+                Expr::Infix(*operator, left, self.lower(right))
+            }
+            Expression::If(IfExpression {
+                condition,
+                consequence,
+                alternative,
+            }) => Expr::If {
+                condition: self.lower(condition),
+                consequence: self.lower_block(consequence),
+                alternative: self.lower_block(alternative),
+            },
+            Expression::Function {
+                params,
+                body,
+                generator,
+            } => Expr::Function {
+                params: params.clone(),
+                body: self.lower_block(body),
+                generator: *generator,
+            },
+            Expression::Call {
+                function,
+                args,
+                named,
+            } => Expr::Call {
+                function: self.lower(function),
+                args: args.iter().map(|arg| self.lower(arg)).collect(),
+                named: named
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.lower(value)))
+                    .collect(),
+            },
+            Expression::Array(elements) => {
+                Expr::Array(elements.iter().map(|element| self.lower(element)).collect())
+            }
+            Expression::Tuple(elements) => {
+                Expr::Tuple(elements.iter().map(|element| self.lower(element)).collect())
+            }
+            Expression::Hash(pairs) => Expr::Hash(
+                pairs
+                    .iter()
+                    .map(|(key, value)| (self.lower(key), self.lower(value)))
+                    .collect(),
+            ),
+            Expression::Index { left, index } => Expr::Index {
+                left: self.lower(left),
+                index: self.lower(index),
+            },
+            Expression::Assign { target, value } => Expr::Assign {
+                target: target.clone(),
+                value: self.lower(value),
+            },
+            Expression::Range {
+                start,
+                end,
+                inclusive,
+            } => Expr::Range {
+                start: self.lower(start),
+                end: self.lower(end),
+                inclusive: *inclusive,
+            },
+            Expression::Spread(inner) => Expr::Spread(self.lower(inner)),
+            Expression::Await(inner) => Expr::Await(self.lower(inner)),
+            Expression::Member { object, attribute } => Expr::Member {
+                object: self.lower(object),
+                attribute: attribute.clone(),
+            },
+        };
+        self.entries[id.0 as usize] = Entry::Expression(expression);
+        id
+    }
+}
+
+impl Index<NodeId> for Arena {
+    type Output = Entry;
+
+    fn index(&self, id: NodeId) -> &Entry {
+        &self.entries[id.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use crate::{
+        ast::{Identifier, Infix, Literal, Pattern, Statement},
+        lexer::Lexer,
+        node_id::{number, Node, NodeId},
+        parser::Parser,
+    };
+
+    use super::{Arena, Entry, Expr, Stmt};
+
+    #[test]
+    fn lays_out_nodes_by_id() -> Result<()> {
+        let (arena, block) = Arena::parse("let x = 1 + 2; f(x)")?;
+
+        assert_eq!(block, [NodeId(0), NodeId(4)]);
+        assert_eq!(arena.len(), 8);
+        assert_eq!(
+            arena.statement(NodeId(0)),
+            &Stmt::Let(Pattern::Identifier(Identifier("x".into())), NodeId(1))
+        );
+        assert_eq!(
+            arena.expression(NodeId(1)),
+            &Expr::Infix(Infix::Plus, NodeId(2), NodeId(3))
+        );
+        assert_eq!(
+            arena[NodeId(3)],
+            Entry::Expression(Expr::Literal(Literal::Int(2)))
+        );
+        assert_eq!(
+            arena.expression(NodeId(5)),
+            &Expr::Call {
+                function: NodeId(6),
+                args: vec![NodeId(7)],
+                named: vec![],
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_with_the_same_ids() -> Result<()> {
+        let source = r#"
+            import "math" as m
+            let add = fn(a, b) { let [c, (d, e)] = [a, (b, 1)]; return a + -b; };
+            let gen = fn() { yield 1 };
+            if (add(1, b: 2) == 3) { {"a": [1..=3, ...m.xs][0]} } else { x = await p; }
+        "#;
+        let program = Parser::new(Lexer::new(source))
+            .parse_program()?
+            .into_iter()
+            .collect::<Result<Vec<Statement>>>()?;
+        let (arena, block) = Arena::from_block(&program);
+
+        assert_eq!(arena.to_block(&block), program);
+
+        let mut count = 0;
+        number(&program, |id, node| {
+            count += 1;
+            match node {
+                Node::Statement(statement) => assert_eq!(&arena.to_statement(id), statement),
+                Node::Expression(expression) => {
+                    assert_eq!(&arena.to_expression(id), expression)
+                }
+            }
+        });
+        assert_eq!(count, arena.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_syntax_errors() {
+        assert!(Arena::parse("let = 1").is_err());
+    }
+}
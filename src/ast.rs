@@ -1,16 +1,23 @@
-use std::borrow::Borrow;
+use std::fmt::{Display, Write};
 
 use anyhow::Result;
 
+use crate::symbol::Symbol;
+
 #[derive(PartialEq, PartialOrd)]
 pub enum Precedence {
     Lowest,
+    Assign,
+    Membership,
     Equals,
     LessGreater,
+    Pipe,
+    Range,
     Sum,
     Product,
     Prefix,
     Call,
+    Index,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -20,41 +27,58 @@ pub enum Prefix {
     Not,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl Display for Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Prefix::Plus => write!(f, "+"),
+            Prefix::Minus => write!(f, "-"),
+            Prefix::Not => write!(f, "!"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Infix {
     Plus,
     Minus,
     Divide,
+    /// `//`, division rounding towards negative infinity.
+    FloorDivide,
     Product,
     Equal,
     NotEqual,
+    /// `===`, like `==` but comparing values of different types is an error.
+    StrictEqual,
+    StrictNotEqual,
     GreaterThan,
     LessThan,
+    /// `in`, whether the right operand contains the left one.
+    In,
+    NotIn,
 }
 
-impl std::fmt::Display for Infix {
+impl Display for Infix {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Infix::Plus => write!(f, "+"),
             Infix::Minus => write!(f, "-"),
             Infix::Divide => write!(f, "/"),
+            Infix::FloorDivide => write!(f, "//"),
             Infix::Product => write!(f, "*"),
             Infix::Equal => write!(f, "=="),
             Infix::NotEqual => write!(f, "!="),
+            Infix::StrictEqual => write!(f, "==="),
+            Infix::StrictNotEqual => write!(f, "!=="),
             Infix::GreaterThan => write!(f, ">"),
             Infix::LessThan => write!(f, "<"),
+            Infix::In => write!(f, "in"),
+            Infix::NotIn => write!(f, "!in"),
         }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Identifier(pub String);
-
-impl Borrow<str> for Identifier {
-    fn borrow(&self) -> &str {
-        self.0.as_str()
-    }
-}
+pub struct Identifier(pub Symbol);
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
@@ -66,18 +90,62 @@ pub enum Expression {
     Function {
         params: Vec<Identifier>,
         body: BlockStatement,
+        /// Whether the body contains a `yield`, making calls return a generator.
+        generator: bool,
     },
     Call {
         function: Box<Expression>,
         args: Vec<Expression>,
+        /// `name: value` arguments, which always follow the positional ones.
+        named: Vec<(Identifier, Expression)>,
     },
+    Array(Vec<Expression>),
+    Tuple(Vec<Expression>),
+    Hash(Vec<(Expression, Expression)>),
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    Assign {
+        target: Identifier,
+        value: Box<Expression>,
+    },
+    /// `start..end`, or `start..=end` to include the end.
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool,
+    },
+    /// `...array`, only allowed as a call argument or array element.
+    Spread(Box<Expression>),
+    /// `await promise`, the value an async builtin produces once it is ready.
+    Await(Box<Expression>),
+    /// `module.name`, a binding of an imported module.
+    Member {
+        object: Box<Expression>,
+        attribute: Identifier,
+    },
+}
+
+/// What an import statement binds in the current scope.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Imports {
+    /// `import "name"`: everything the module defines.
+    All,
+    /// `import "name" as m`: the module itself, as `m`.
+    Module(Identifier),
+    /// `from "name" import a, b`: just those bindings.
+    Names(Vec<Identifier>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
     Int(i64),
+    Float(f64),
     String(String),
+    Bytes(Vec<u8>),
     Bool(bool),
+    Null,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -89,11 +157,262 @@ pub struct IfExpression {
 
 pub type BlockStatement = Vec<Statement>;
 
+/// Left-hand side of a `let` statement.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// `let x = ...`
+    Identifier(Identifier),
+    /// `let [a, [b, c]] = ...`, binds array elements by position.
+    Array(Vec<Pattern>),
+    /// `let (q, r) = ...`, binds tuple elements by position.
+    Tuple(Vec<Pattern>),
+    /// `let {x, y} = ...`, binds the values of the string keys `"x"` and `"y"`.
+    Hash(Vec<Identifier>),
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
-    Let(Identifier, Expression),
+    Let(Pattern, Expression),
     Return(Expression),
+    Yield(Expression),
+    /// `import "name"` and its variants, see `Imports`.
+    Import(String, Imports),
     Expression(Expression),
+    /// Left by a `;` after the last statement of a block, so the block evaluates to null
+    /// instead of to that statement's value.
+    Empty,
 }
 
 pub type Program = Vec<Result<Statement>>;
+
+/// Source of the statements in a block, one per line, with nested blocks indented. Operators
+/// are fully parenthesized, as in `((a + b) * c)`, so the output parses back to the same AST.
+pub fn block_source(block: &BlockStatement) -> String {
+    let mut output = String::new();
+    write_statements(&mut output, block, 0).expect("writing to a String cannot fail");
+    output
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_statement(f, self, 0)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_expression(f, self, 0)
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Int(num) => write!(f, "{}", num),
+            Literal::Float(num) => write!(f, "{:?}", num),
+            Literal::String(s) => write!(f, "\"{}\"", s),
+            // Literals have no escapes, their bytes are the source text between the quotes.
+            Literal::Bytes(bytes) => write!(f, "b\"{}\"", String::from_utf8_lossy(bytes)),
+            Literal::Bool(bool) => write!(f, "{}", bool),
+            Literal::Null => write!(f, "null"),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Identifier(name) => write!(f, "{}", name.0),
+            Pattern::Array(patterns) => write!(f, "[{}]", join(patterns)),
+            Pattern::Tuple(patterns) => write!(f, "({})", join(patterns)),
+            Pattern::Hash(names) => write!(f, "{{{}}}", join(names.iter().map(|name| &name.0))),
+        }
+    }
+}
+
+fn join<T: Display>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_statements(f: &mut dyn Write, block: &BlockStatement, indent: usize) -> std::fmt::Result {
+    for (i, statement) in block.iter().enumerate() {
+        if i > 0 {
+            write!(f, ";")?;
+        }
+        if *statement == Statement::Empty {
+            continue;
+        }
+        if i > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{:indent$}", "", indent = indent * 4)?;
+        write_statement(f, statement, indent)?;
+    }
+    Ok(())
+}
+
+fn write_block(f: &mut dyn Write, block: &BlockStatement, indent: usize) -> std::fmt::Result {
+    if block.is_empty() {
+        return write!(f, "{{}}");
+    }
+    writeln!(f, "{{")?;
+    write_statements(f, block, indent + 1)?;
+    write!(f, "\n{:indent$}}}", "", indent = indent * 4)
+}
+
+fn write_statement(f: &mut dyn Write, statement: &Statement, indent: usize) -> std::fmt::Result {
+    match statement {
+        Statement::Let(pattern, value) => {
+            write!(f, "let {} = ", pattern)?;
+            write_expression(f, value, indent)
+        }
+        Statement::Return(value) => {
+            write!(f, "return ")?;
+            write_expression(f, value, indent)
+        }
+        Statement::Yield(value) => {
+            write!(f, "yield ")?;
+            write_expression(f, value, indent)
+        }
+        Statement::Import(name, Imports::All) => write!(f, "import \"{}\"", name),
+        Statement::Import(name, Imports::Module(alias)) => {
+            write!(f, "import \"{}\" as {}", name, alias.0)
+        }
+        Statement::Import(name, Imports::Names(names)) => {
+            let names = names.iter().map(|id| id.0.as_str()).collect::<Vec<_>>();
+            write!(f, "from \"{}\" import {}", name, names.join(", "))
+        }
+        Statement::Expression(value) => write_expression(f, value, indent),
+        Statement::Empty => Ok(()),
+    }
+}
+
+fn write_list(f: &mut dyn Write, expressions: &[Expression], indent: usize) -> std::fmt::Result {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write_expression(f, expression, indent)?;
+    }
+    Ok(())
+}
+
+fn write_expression(f: &mut dyn Write, expression: &Expression, indent: usize) -> std::fmt::Result {
+    match expression {
+        Expression::Identifier(name) => write!(f, "{}", name.0),
+        Expression::Literal(literal) => write!(f, "{}", literal),
+        Expression::Prefix(prefix, right) => {
+            write!(f, "({}", prefix)?;
+            write_expression(f, right, indent)?;
+            write!(f, ")")
+        }
+        Expression::Infix(infix, left, right) => {
+            write!(f, "(")?;
+            write_expression(f, left, indent)?;
+            write!(f, " {} ", infix)?;
+            write_expression(f, right, indent)?;
+            write!(f, ")")
+        }
+        Expression::If(IfExpression {
+            condition,
+            consequence,
+            alternative,
+        }) => {
+            write!(f, "if (")?;
+            write_expression(f, condition, indent)?;
+            write!(f, ") ")?;
+            write_block(f, consequence, indent)?;
+            if !alternative.is_empty() {
+                write!(f, " else ")?;
+                write_block(f, alternative, indent)?;
+            }
+            Ok(())
+        }
+        Expression::Function { params, body, .. } => {
+            write!(f, "fn({}) ", join(params.iter().map(|param| &param.0)))?;
+            write_block(f, body, indent)
+        }
+        Expression::Call {
+            function,
+            args,
+            named,
+        } => {
+            write_expression(f, function, indent)?;
+            write!(f, "(")?;
+            write_list(f, args, indent)?;
+            for (i, (name, value)) in named.iter().enumerate() {
+                if i > 0 || !args.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: ", name.0)?;
+                write_expression(f, value, indent)?;
+            }
+            write!(f, ")")
+        }
+        Expression::Array(elements) => {
+            write!(f, "[")?;
+            write_list(f, elements, indent)?;
+            write!(f, "]")
+        }
+        Expression::Tuple(elements) => {
+            write!(f, "(")?;
+            write_list(f, elements, indent)?;
+            if elements.len() == 1 {
+                write!(f, ",")?;
+            }
+            write!(f, ")")
+        }
+        Expression::Hash(pairs) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_expression(f, key, indent)?;
+                write!(f, ": ")?;
+                write_expression(f, value, indent)?;
+            }
+            write!(f, "}}")
+        }
+        Expression::Member { object, attribute } => {
+            write!(f, "(")?;
+            write_expression(f, object, indent)?;
+            write!(f, ".{})", attribute.0)
+        }
+        Expression::Index { left, index } => {
+            write!(f, "(")?;
+            write_expression(f, left, indent)?;
+            write!(f, "[")?;
+            write_expression(f, index, indent)?;
+            write!(f, "])")
+        }
+        Expression::Assign { target, value } => {
+            write!(f, "{} = ", target.0)?;
+            write_expression(f, value, indent)
+        }
+        Expression::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            write!(f, "(")?;
+            write_expression(f, start, indent)?;
+            write!(f, "{}", if *inclusive { "..=" } else { ".." })?;
+            write_expression(f, end, indent)?;
+            write!(f, ")")
+        }
+        Expression::Spread(array) => {
+            write!(f, "...")?;
+            write_expression(f, array, indent)
+        }
+        Expression::Await(promise) => {
+            write!(f, "(await ")?;
+            write_expression(f, promise, indent)?;
+            write!(f, ")")
+        }
+    }
+}
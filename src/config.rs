@@ -0,0 +1,403 @@
+//! `monkey.toml`, project settings applied before command line flags.
+//!
+//! Only the part of TOML settings need is understood: `[section]` headers, and `key = value`
+//! lines where the value is a string, a boolean, an integer or a one-line array of strings.
+//! `#` starts a comment.
+//!
+//! ```toml
+//! strict = true
+//! division = "floor"
+//! module_paths = ["lib"]
+//!
+//! [limits]
+//! max_array_len = 100000
+//!
+//! [capabilities]
+//! fs = ["data"]
+//! env = true
+//!
+//! [repl]
+//! prompt = "monkey> "
+//! rc = false
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    eval::{Division, Options},
+    repl::ReplOptions,
+};
+
+/// Name of the file `Config::find` looks for.
+pub const FILE_NAME: &str = "monkey.toml";
+
+/// Settings read from a `monkey.toml`. Unset ones are `None` (or empty), leaving the defaults
+/// or the command line in charge.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub strict: Option<bool>,
+    pub strict_index: Option<bool>,
+    pub division: Option<Division>,
+    pub max_call_depth: Option<usize>,
+    /// Searched by `import` after the script's directory, relative to the file's directory.
+    pub module_paths: Vec<PathBuf>,
+    /// The `[limits]` section.
+    pub max_array_len: Option<usize>,
+    pub max_hash_len: Option<usize>,
+    pub max_string_len: Option<usize>,
+    pub max_env_entries: Option<usize>,
+    /// The `[capabilities]` section. Directories builtins may read, relative to the file's
+    /// directory.
+    pub fs: Vec<PathBuf>,
+    pub net: Option<bool>,
+    pub exec: Option<bool>,
+    pub env: Option<bool>,
+    pub time: Option<bool>,
+    /// The `[repl]` section.
+    pub prompt: Option<String>,
+    /// Whether to evaluate `~/.monkeyrc` on startup.
+    pub rc: Option<bool>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Array(Vec<Value>),
+}
+
+impl Config {
+    /// The `monkey.toml` in `dir` or the closest of its parents that has one, along with its
+    /// path.
+    pub fn find(dir: &Path) -> Result<Option<(PathBuf, Config)>> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for dir in dir.ancestors() {
+            let path = dir.join(FILE_NAME);
+            if path.is_file() {
+                let config = Self::load(&path)?;
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> Result<Config> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Self::parse(&text, dir).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    /// Parses the contents of a `monkey.toml`, resolving relative paths against `dir`.
+    pub fn parse(text: &str, dir: &Path) -> Result<Config> {
+        let mut config = Config::default();
+        let mut section = String::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result = match line.strip_prefix('[') {
+                Some(header) => match header.strip_suffix(']') {
+                    Some(name) => {
+                        section = name.trim().to_string();
+                        match section.as_str() {
+                            "limits" | "capabilities" | "repl" => Ok(()),
+                            _ => Err(anyhow::anyhow!("unknown section [{}]", section)),
+                        }
+                    }
+                    None => Err(anyhow::anyhow!("expected ] after the section name")),
+                },
+                None => config.set(&section, line, dir),
+            };
+            result.with_context(|| format!("line {}", number + 1))?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, section: &str, line: &str, dir: &Path) -> Result<()> {
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("expected key = value");
+        };
+        let key = key.trim();
+        let (value, rest) = parse_value(value.trim())?;
+        if !rest.trim().is_empty() {
+            bail!("unexpected {} after the value", rest.trim());
+        }
+
+        match (section, key) {
+            ("", "strict") => self.strict = Some(boolean(key, value)?),
+            ("", "strict_index") => self.strict_index = Some(boolean(key, value)?),
+            ("", "division") => {
+                self.division = Some(match string(key, value)?.as_str() {
+                    "truncate" => Division::Truncate,
+                    "floor" => Division::Floor,
+                    "float" => Division::Float,
+                    other => bail!("invalid division {}", other),
+                })
+            }
+            ("", "max_call_depth") => self.max_call_depth = Some(count(key, value)?),
+            ("", "module_paths") => self.module_paths = paths(key, value, dir)?,
+            ("limits", "max_array_len") => self.max_array_len = Some(count(key, value)?),
+            ("limits", "max_hash_len") => self.max_hash_len = Some(count(key, value)?),
+            ("limits", "max_string_len") => self.max_string_len = Some(count(key, value)?),
+            ("limits", "max_env_entries") => self.max_env_entries = Some(count(key, value)?),
+            ("capabilities", "fs") => self.fs = paths(key, value, dir)?,
+            ("capabilities", "net") => self.net = Some(boolean(key, value)?),
+            ("capabilities", "exec") => self.exec = Some(boolean(key, value)?),
+            ("capabilities", "env") => self.env = Some(boolean(key, value)?),
+            ("capabilities", "time") => self.time = Some(boolean(key, value)?),
+            ("repl", "prompt") => self.prompt = Some(string(key, value)?),
+            ("repl", "rc") => self.rc = Some(boolean(key, value)?),
+            ("", key) => bail!("unknown setting {}", key),
+            (section, key) => bail!("unknown setting {} in [{}]", key, section),
+        }
+        Ok(())
+    }
+
+    /// Overrides `options` and `repl_options` with the settings that are set. Module paths are
+    /// left to the caller, as where they go in the search order depends on the script.
+    pub fn apply(&self, options: &mut Options, repl_options: &mut ReplOptions) {
+        set(&mut options.strict, self.strict);
+        set(&mut options.strict_index, self.strict_index);
+        set(&mut options.division, self.division);
+        set(&mut options.max_call_depth, self.max_call_depth);
+        set(&mut options.limits.max_array_len, self.max_array_len);
+        set(&mut options.limits.max_hash_len, self.max_hash_len);
+        set(&mut options.limits.max_string_len, self.max_string_len);
+        set(&mut options.limits.max_env_entries, self.max_env_entries);
+
+        let capabilities = &mut options.capabilities;
+        capabilities.fs.extend(self.fs.iter().cloned());
+        set(&mut capabilities.net, self.net);
+        set(&mut capabilities.exec, self.exec);
+        set(&mut capabilities.env, self.env);
+        set(&mut capabilities.time, self.time);
+
+        if let Some(prompt) = &self.prompt {
+            repl_options.prompt = prompt.clone();
+        }
+        if self.rc == Some(false) {
+            repl_options.rc_file = None;
+        }
+    }
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+/// `line` without a trailing `#` comment, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, ch) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// The value at the start of `text`, and what follows it.
+fn parse_value(text: &str) -> Result<(Value, &str)> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut string = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, ch)) = chars.next() {
+            match ch {
+                '"' => return Ok((Value::String(string), &rest[i + 1..])),
+                '\\' => string.push(match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, ch @ ('"' | '\\'))) => ch,
+                    Some((_, ch)) => bail!("unknown escape \\{}", ch),
+                    None => break,
+                }),
+                ch => string.push(ch),
+            }
+        }
+        bail!("unterminated string");
+    }
+
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = vec![];
+        loop {
+            rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), rest));
+            }
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after,
+                None if rest.starts_with(']') => {}
+                None => bail!("expected , or ] in array"),
+            }
+        }
+    }
+
+    let end = text
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' || ch == '+'))
+        .unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "" => bail!("expected a value"),
+        word => match word.replace('_', "").parse() {
+            Ok(int) => Value::Int(int),
+            Err(_) => bail!("invalid value {}", word),
+        },
+    };
+    Ok((value, rest))
+}
+
+fn boolean(key: &str, value: Value) -> Result<bool> {
+    match value {
+        Value::Bool(bool) => Ok(bool),
+        _ => bail!("{} must be true or false", key),
+    }
+}
+
+fn string(key: &str, value: Value) -> Result<String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => bail!("{} must be a string", key),
+    }
+}
+
+fn count(key: &str, value: Value) -> Result<usize> {
+    match value {
+        Value::Int(int) if int >= 0 => Ok(int as usize),
+        _ => bail!("{} must be a number of at least 0", key),
+    }
+}
+
+fn paths(key: &str, value: Value, dir: &Path) -> Result<Vec<PathBuf>> {
+    let Value::Array(values) = value else {
+        bail!("{} must be an array of paths", key);
+    };
+    values
+        .into_iter()
+        .map(|value| match value {
+            Value::String(path) => Ok(dir.join(path)),
+            _ => bail!("{} must be an array of paths", key),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        eval::{Division, Options},
+        repl::ReplOptions,
+    };
+
+    use super::Config;
+
+    #[test]
+    fn parses_settings() {
+        let config = Config::parse(
+            r##"
+            # Settings for the whole project.
+            strict = true
+            division = "floor"  # like Python
+            module_paths = ["lib", "/opt/monkey"]
+
+            [limits]
+            max_array_len = 100_000
+
+            [capabilities]
+            fs = ["data"]
+            exec = false
+            env = true
+
+            [repl]
+            prompt = "#> \"monkey\" "
+            rc = false
+            "##,
+            Path::new("/project"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                strict: Some(true),
+                division: Some(Division::Floor),
+                module_paths: vec![PathBuf::from("/project/lib"), PathBuf::from("/opt/monkey")],
+                max_array_len: Some(100_000),
+                fs: vec![PathBuf::from("/project/data")],
+                exec: Some(false),
+                env: Some(true),
+                prompt: Some(String::from("#> \"monkey\" ")),
+                rc: Some(false),
+                ..Default::default()
+            }
+        );
+
+        let mut options = Options::default();
+        let mut repl_options = ReplOptions::default();
+        config.apply(&mut options, &mut repl_options);
+        assert!(options.strict);
+        assert!(!options.strict_index);
+        assert_eq!(options.division, Division::Floor);
+        assert_eq!(options.limits.max_array_len, 100_000);
+        assert_eq!(options.capabilities.fs, [PathBuf::from("/project/data")]);
+        assert!(options.capabilities.env && options.capabilities.time);
+        assert_eq!(repl_options.prompt, "#> \"monkey\" ");
+        assert_eq!(repl_options.rc_file, None);
+    }
+
+    #[test]
+    fn rejects_mistakes() {
+        for (text, error) in [
+            ("strikt = true", "line 1: unknown setting strikt"),
+            (
+                "[repl]\nstrict = true",
+                "line 2: unknown setting strict in [repl]",
+            ),
+            ("[colours]", "line 1: unknown section [colours]"),
+            ("strict = 1", "line 1: strict must be true or false"),
+            ("division = \"round\"", "line 1: invalid division round"),
+            (
+                "max_call_depth = -1",
+                "line 1: max_call_depth must be a number of at least 0",
+            ),
+            (
+                "module_paths = [\"lib\" \"x\"]",
+                "line 1: expected , or ] in array",
+            ),
+            ("[repl]\nprompt = \"> ", "line 2: unterminated string"),
+            ("strict", "line 1: expected key = value"),
+            (
+                "strict = true false",
+                "line 1: unexpected false after the value",
+            ),
+        ] {
+            let message = format!("{:#}", Config::parse(text, Path::new(".")).unwrap_err());
+            assert_eq!(message, error, "{}", text);
+        }
+    }
+}
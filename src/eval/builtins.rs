@@ -0,0 +1,1430 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    io::Write,
+    path::Path,
+    process::Command,
+    rc::Rc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    ast::{block_source, Identifier},
+    symbol::Symbol,
+};
+
+use super::{
+    capabilities::{self, Capabilities},
+    object::{Callable, Elements, Object, Pairs, Promise, Thunk},
+    random::Random,
+    stats::HeapStats,
+    Limits,
+};
+
+/// What builtins need from the engine running them. Builtins only go through this, so the same
+/// implementations serve the tree-walking `Eval` and any other backend implementing it.
+pub trait Runtime {
+    /// Calls a Monkey function or builtin, e.g. the callback given to `map`.
+    fn call(&mut self, function: Object, args: Vec<Object>) -> Result<Object>;
+
+    /// Parses and runs Monkey source in the scope the builtin was called from, for `eval`.
+    /// Like `call`, it may run while an outer evaluation is suspended in the builtin.
+    fn eval_source(&mut self, _source: &str) -> Result<Object> {
+        bail!("This runtime cannot evaluate source code!")
+    }
+
+    /// Where `puts` writes.
+    fn output(&mut self) -> &mut dyn Write;
+
+    /// Reads the next line, including its line break, into `line`, like `BufRead::read_line`.
+    fn read_line(&mut self, line: &mut String) -> std::io::Result<usize>;
+
+    /// Source of the numbers `random_int` draws.
+    fn random(&self) -> &Random;
+
+    /// Caps on the arrays and strings builtins build. Unlimited unless the runtime says otherwise.
+    fn limits(&self) -> Limits {
+        Limits::default()
+    }
+
+    /// What builtins may do outside the interpreter. Nothing but waiting, unless the runtime
+    /// says otherwise.
+    fn capabilities(&self) -> &Capabilities {
+        &capabilities::DEFAULT
+    }
+
+    /// Counts of the values alive and reachable, for `stats`.
+    fn heap_stats(&self) -> Result<HeapStats> {
+        bail!("This runtime cannot report heap statistics!")
+    }
+}
+
+pub type BuiltinFn = fn(&mut dyn Runtime, Vec<Object>) -> Result<Object>;
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+    pub signature: Signature,
+}
+
+impl Builtin {
+    /// Runs the builtin once the arguments match its signature, so it doesn't check them again.
+    pub fn call(&self, runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+        self.signature.check(self.name, &args)?;
+        (self.func)(runtime, args)
+    }
+
+    /// How to call the builtin, e.g. `push(array: array, value)`, shown as its docstring.
+    pub fn usage(&self) -> String {
+        format!("{}({})", self.name, self.signature)
+    }
+}
+
+/// The arguments a builtin takes.
+pub struct Signature {
+    pub params: &'static [Param],
+    /// Whether the last parameter takes any number of arguments, none included.
+    pub variadic: bool,
+}
+
+pub struct Param {
+    pub name: &'static str,
+    /// Types, as named by `Object::get_type`, the argument may have. Empty for any type.
+    pub types: &'static [&'static str],
+}
+
+const ANY: &[&str] = &[];
+const CALLABLE: &[&str] = &["function", "builtin"];
+
+const fn param(name: &'static str, types: &'static [&'static str]) -> Param {
+    Param { name, types }
+}
+
+impl Signature {
+    const fn new(params: &'static [Param]) -> Self {
+        Self {
+            params,
+            variadic: false,
+        }
+    }
+
+    const fn variadic(self) -> Self {
+        Self {
+            variadic: true,
+            ..self
+        }
+    }
+
+    /// How many arguments must be given.
+    pub fn required(&self) -> usize {
+        self.params.len() - usize::from(self.variadic)
+    }
+
+    fn check(&self, builtin: &str, args: &[Object]) -> Result<()> {
+        let (given, required) = (args.len(), self.required());
+        if self.variadic && given < required {
+            bail!(
+                "Wrong number of arguments. Expected: at least {}. Given: {}",
+                required,
+                given
+            );
+        }
+        if !self.variadic && given != required {
+            bail!(
+                "Wrong number of arguments. Expected: {}. Given: {}",
+                required,
+                given
+            );
+        }
+
+        let last = self.params.last();
+        let params = self
+            .params
+            .iter()
+            .chain(last.filter(|_| self.variadic).into_iter().cycle());
+        for (arg, param) in args.iter().zip(params) {
+            if !param.types.is_empty() && !param.types.contains(&arg.get_type()) {
+                bail!("Builtin {} is not defined for {}!", builtin, arg.get_type());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, param) in self.params.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            if self.variadic && index == self.params.len() - 1 {
+                write!(f, "...")?;
+            }
+            write!(f, "{}", param.name)?;
+            if !param.types.is_empty() {
+                write!(f, ": {}", param.types.join(" | "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Function the application embedding the interpreter adds, see `Eval::register_builtin`.
+pub type HostFn = dyn Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object>;
+
+pub struct HostBuiltin {
+    pub name: Symbol,
+    pub arity: usize,
+    pub func: Box<HostFn>,
+}
+
+impl HostBuiltin {
+    pub fn call(&self, runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+        if args.len() != self.arity {
+            bail!(
+                "Wrong number of arguments. Expected: {}. Given: {}",
+                self.arity,
+                args.len()
+            );
+        }
+        (self.func)(runtime, args)
+    }
+}
+
+impl PartialEq for HostBuiltin {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for HostBuiltin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HostBuiltin({})", self.name)
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+static BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "len",
+        func: len,
+        signature: Signature::new(&[param(
+            "value",
+            &["string", "bytes", "array", "tuple", "hash", "set", "range"],
+        )]),
+    },
+    Builtin {
+        name: "chars",
+        func: chars,
+        signature: Signature::new(&[param("string", &["string"])]),
+    },
+    Builtin {
+        name: "bytes",
+        func: bytes,
+        signature: Signature::new(&[param("value", &["string", "bytes"])]),
+    },
+    Builtin {
+        name: "encode",
+        func: encode,
+        signature: Signature::new(&[param("string", &["string"])]),
+    },
+    Builtin {
+        name: "decode",
+        func: decode,
+        signature: Signature::new(&[param("bytes", &["bytes"])]),
+    },
+    Builtin {
+        name: "first",
+        func: first,
+        signature: Signature::new(&[param("array", &["array"])]),
+    },
+    Builtin {
+        name: "last",
+        func: last,
+        signature: Signature::new(&[param("array", &["array"])]),
+    },
+    Builtin {
+        name: "rest",
+        func: rest,
+        signature: Signature::new(&[param("array", &["array"])]),
+    },
+    Builtin {
+        name: "push",
+        func: push,
+        signature: Signature::new(&[param("array", &["array"]), param("value", ANY)]),
+    },
+    Builtin {
+        name: "push_front",
+        func: push_front,
+        signature: Signature::new(&[param("array", &["array"]), param("value", ANY)]),
+    },
+    Builtin {
+        name: "pop",
+        func: pop,
+        signature: Signature::new(&[param("array", &["array"])]),
+    },
+    Builtin {
+        name: "pop_front",
+        func: pop_front,
+        signature: Signature::new(&[param("array", &["array"])]),
+    },
+    Builtin {
+        name: "map",
+        func: map,
+        signature: Signature::new(&[param("iterable", ANY), param("function", CALLABLE)]),
+    },
+    Builtin {
+        name: "to_array",
+        func: to_array,
+        signature: Signature::new(&[param("range", &["range"])]),
+    },
+    Builtin {
+        name: "clone",
+        func: clone,
+        signature: Signature::new(&[param("value", ANY)]),
+    },
+    Builtin {
+        name: "freeze",
+        func: freeze,
+        signature: Signature::new(&[param("value", ANY)]),
+    },
+    Builtin {
+        name: "is_frozen",
+        func: is_frozen,
+        signature: Signature::new(&[param("value", ANY)]),
+    },
+    Builtin {
+        name: "join",
+        func: join,
+        signature: Signature::new(&[param("array", &["array"]), param("separator", &["string"])]),
+    },
+    Builtin {
+        name: "repeat",
+        func: repeat,
+        signature: Signature::new(&[param("string", &["string"]), param("count", &["int"])]),
+    },
+    Builtin {
+        name: "concat",
+        func: concat,
+        signature: Signature::new(&[param("left", &["array"]), param("right", &["array"])]),
+    },
+    Builtin {
+        name: "merge",
+        func: merge,
+        signature: Signature::new(&[param("left", &["hash"]), param("right", &["hash"])]),
+    },
+    Builtin {
+        name: "set",
+        func: set,
+        signature: Signature::new(&[param("iterable", ANY)]),
+    },
+    Builtin {
+        name: "insert",
+        func: insert,
+        signature: Signature::new(&[param("set", &["set"]), param("value", ANY)]),
+    },
+    Builtin {
+        name: "contains",
+        func: contains,
+        signature: Signature::new(&[param("set", &["set"]), param("value", ANY)]),
+    },
+    Builtin {
+        name: "union",
+        func: union,
+        signature: Signature::new(&[param("left", &["set"]), param("right", &["set"])]),
+    },
+    Builtin {
+        name: "intersection",
+        func: intersection,
+        signature: Signature::new(&[param("left", &["set"]), param("right", &["set"])]),
+    },
+    Builtin {
+        name: "difference",
+        func: difference,
+        signature: Signature::new(&[param("left", &["set"]), param("right", &["set"])]),
+    },
+    Builtin {
+        name: "arity",
+        func: arity,
+        signature: Signature::new(&[param("function", &["function"])]),
+    },
+    Builtin {
+        name: "params",
+        func: params,
+        signature: Signature::new(&[param("function", &["function"])]),
+    },
+    Builtin {
+        name: "body_source",
+        func: body_source,
+        signature: Signature::new(&[param("function", &["function"])]),
+    },
+    Builtin {
+        name: "doc",
+        func: doc,
+        signature: Signature::new(&[param("function", CALLABLE)]),
+    },
+    Builtin {
+        name: "eval",
+        func: eval,
+        signature: Signature::new(&[param("source", &["string"])]),
+    },
+    Builtin {
+        name: "sleep_async",
+        func: sleep_async,
+        signature: Signature::new(&[param("ms", &["int"])]),
+    },
+    Builtin {
+        name: "async_read_file",
+        func: async_read_file,
+        signature: Signature::new(&[param("path", &["string"])]),
+    },
+    Builtin {
+        name: "parse_int",
+        func: parse_int,
+        signature: Signature::new(&[param("string", &["string"]), param("base", &["int"])]),
+    },
+    Builtin {
+        name: "parse_float",
+        func: parse_float,
+        signature: Signature::new(&[param("string", &["string"])]),
+    },
+    Builtin {
+        name: "to_fixed",
+        func: to_fixed,
+        signature: Signature::new(&[
+            param("number", &["int", "float"]),
+            param("digits", &["int"]),
+        ]),
+    },
+    Builtin {
+        name: "seed",
+        func: seed,
+        signature: Signature::new(&[param("n", &["int"])]),
+    },
+    Builtin {
+        name: "random_int",
+        func: random_int,
+        signature: Signature::new(&[param("lo", &["int"]), param("hi", &["int"])]),
+    },
+    Builtin {
+        name: "puts",
+        func: puts,
+        signature: Signature::new(&[param("values", ANY)]).variadic(),
+    },
+    Builtin {
+        name: "input",
+        func: input,
+        signature: Signature::new(&[param("prompt", ANY)]),
+    },
+    Builtin {
+        name: "getenv",
+        func: getenv,
+        signature: Signature::new(&[param("name", &["string"])]),
+    },
+    Builtin {
+        name: "exec",
+        func: exec,
+        signature: Signature::new(&[param("cmd", &["string"]), param("args", &["array"])]),
+    },
+    Builtin {
+        name: "format",
+        func: format,
+        signature: Signature::new(&[param("template", &["string"]), param("args", ANY)]).variadic(),
+    },
+    Builtin {
+        name: "printf",
+        func: printf,
+        signature: Signature::new(&[param("template", &["string"]), param("args", ANY)]).variadic(),
+    },
+    Builtin {
+        name: "inspect",
+        func: inspect,
+        signature: Signature::new(&[param("value", ANY)]),
+    },
+    Builtin {
+        name: "stats",
+        func: stats,
+        signature: Signature::new(&[]),
+    },
+    Builtin {
+        name: "next",
+        func: next,
+        signature: Signature::new(&[param("generator", &["generator"])]),
+    },
+    Builtin {
+        name: "delay",
+        func: delay,
+        signature: Signature::new(&[param("function", CALLABLE)]),
+    },
+    Builtin {
+        name: "force",
+        func: force,
+        signature: Signature::new(&[param("thunk", &["thunk"])]),
+    },
+    Builtin {
+        name: "compose",
+        func: compose,
+        signature: Signature::new(&[param("outer", CALLABLE), param("inner", CALLABLE)]),
+    },
+    Builtin {
+        name: "partial",
+        func: partial,
+        signature: Signature::new(&[param("function", CALLABLE), param("args", ANY)]).variadic(),
+    },
+    Builtin {
+        name: "memoize",
+        func: memoize,
+        signature: Signature::new(&[param("function", CALLABLE)]),
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|builtin| builtin.name)
+}
+
+/// The arguments of a builtin taking `N` of them, already counted by `Builtin::call`.
+fn arguments<const N: usize>(args: Vec<Object>) -> [Object; N] {
+    args.try_into()
+        .unwrap_or_else(|_| unreachable!("the signature takes {} arguments", N))
+}
+
+/// Strings are measured in chars, like `chars` splits them, not in UTF-8 bytes.
+fn len(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [value] = arguments(args);
+
+    Ok(Object::Int(match value {
+        Object::String(s) => s.chars().count() as i64,
+        Object::Bytes(bytes) => bytes.len() as i64,
+        Object::Array(elements) | Object::Tuple(elements) => elements.len() as i64,
+        Object::Hash(hash) => hash.len() as i64,
+        Object::Set(elements) => elements.len() as i64,
+        Object::Range(start, end) => end.saturating_sub(start).max(0),
+        _ => unreachable!(),
+    }))
+}
+
+/// Splits a string into single-char strings, one per Unicode scalar value.
+fn chars(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(s)] = arguments(args) else {
+        unreachable!()
+    };
+
+    runtime.limits().check_array(s.chars().count())?;
+    Ok(Object::Array(
+        s.chars()
+            .map(|ch| Object::String(ch.to_string().into()))
+            .collect(),
+    ))
+}
+
+/// The UTF-8 encoding of a string, or the contents of binary data, as ints from 0 to 255.
+fn bytes(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [value] = arguments(args);
+
+    let bytes = match &value {
+        Object::String(s) => s.as_bytes(),
+        Object::Bytes(bytes) => bytes,
+        _ => unreachable!(),
+    };
+    runtime.limits().check_array(bytes.len())?;
+    Ok(Object::Array(
+        bytes.iter().map(|&byte| Object::Int(byte.into())).collect(),
+    ))
+}
+
+/// The UTF-8 encoding of a string as binary data.
+fn encode(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(s)] = arguments(args) else {
+        unreachable!()
+    };
+
+    Ok(Object::Bytes(s.as_bytes().into()))
+}
+
+/// Binary data holding UTF-8 text as a string.
+fn decode(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Bytes(bytes)] = arguments(args) else {
+        unreachable!()
+    };
+
+    match std::str::from_utf8(&bytes) {
+        Ok(s) => Ok(Object::String(s.into())),
+        Err(error) => bail!("Builtin decode needs UTF-8 text: {}!", error),
+    }
+}
+
+fn first(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements)] = arguments(args) else {
+        unreachable!()
+    };
+
+    Ok(elements.first().cloned().unwrap_or(Object::Null))
+}
+
+fn last(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements)] = arguments(args) else {
+        unreachable!()
+    };
+
+    Ok(elements.last().cloned().unwrap_or(Object::Null))
+}
+
+fn rest(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements)] = arguments(args) else {
+        unreachable!()
+    };
+
+    check_unfrozen(&elements)?;
+    Ok(match elements.is_empty() {
+        true => Object::Null,
+        false => Object::Array(elements.rest()),
+    })
+}
+
+fn push(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements), value] = arguments(args) else {
+        unreachable!()
+    };
+
+    check_unfrozen(&elements)?;
+    runtime.limits().check_array(elements.len() + 1)?;
+    Ok(Object::Array(elements.push(value)))
+}
+
+fn push_front(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements), value] = arguments(args) else {
+        unreachable!()
+    };
+
+    check_unfrozen(&elements)?;
+    runtime.limits().check_array(elements.len() + 1)?;
+    Ok(Object::Array(elements.push_front(value)))
+}
+
+/// Splits off the last element, as a `(last, rest)` tuple. Null for an empty array.
+fn pop(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements)] = arguments(args) else {
+        unreachable!()
+    };
+
+    check_unfrozen(&elements)?;
+    Ok(match elements.last() {
+        Some(last) => Object::Tuple(vec![last.clone(), Object::Array(elements.pop())].into()),
+        None => Object::Null,
+    })
+}
+
+/// Splits off the first element, as a `(first, rest)` tuple. Null for an empty array.
+fn pop_front(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements)] = arguments(args) else {
+        unreachable!()
+    };
+
+    check_unfrozen(&elements)?;
+    Ok(match elements.first() {
+        Some(first) => Object::Tuple(vec![first.clone(), Object::Array(elements.rest())].into()),
+        None => Object::Null,
+    })
+}
+
+/// Every builtin and operator returning an updated copy of an array or hash, e.g. `push`,
+/// `rest` and `concat`, refuses frozen operands, see `Object::freeze`.
+fn check_unfrozen(elements: &Elements) -> Result<()> {
+    if elements.is_frozen() {
+        bail!("Cannot change a frozen array!");
+    }
+    Ok(())
+}
+
+fn check_unfrozen_hash(pairs: &Pairs) -> Result<()> {
+    if pairs.is_frozen() {
+        bail!("Cannot change a frozen hash!");
+    }
+    Ok(())
+}
+
+fn map(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [array, function] = arguments(args);
+
+    let Some(elements) = array.iter() else {
+        bail!("Builtin map is not defined for {}!", array.get_type());
+    };
+    runtime.limits().check_array(elements.size_hint().0)?;
+
+    Ok(Object::Array(
+        elements
+            .map(|x| runtime.call(function.clone(), vec![x]))
+            .collect::<Result<_>>()?,
+    ))
+}
+
+fn to_array(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Range(start, end)] = arguments(args) else {
+        unreachable!()
+    };
+
+    let len = usize::try_from(end.saturating_sub(start)).unwrap_or(0);
+    runtime.limits().check_array(len)?;
+    Ok(Object::Array((start..end).map(Object::Int).collect()))
+}
+
+/// Copies a value into buffers of its own. Values never change in place, so the copy is only
+/// observable in memory: e.g. `rest` keeps the whole array alive, its clone doesn't. Unlike
+/// the original, the copy of a frozen value isn't frozen.
+fn clone(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [value] = arguments(args);
+
+    Ok(deep_copy(&value))
+}
+
+fn deep_copy(value: &Object) -> Object {
+    match value {
+        Object::String(s) => Object::String(Rc::from(&**s)),
+        Object::Bytes(bytes) => Object::Bytes(Rc::from(&**bytes)),
+        Object::Array(elements) => Object::Array(elements.iter().map(deep_copy).collect()),
+        Object::Tuple(elements) => Object::Tuple(elements.iter().map(deep_copy).collect()),
+        Object::Hash(pairs) => Object::Hash(
+            pairs
+                .iter()
+                .map(|(key, value)| (deep_copy(key), deep_copy(value)))
+                .collect(),
+        ),
+        Object::Set(elements) => Object::Set(elements.iter().map(deep_copy).collect()),
+        // Functions, generators and the like are shared by identity, copying them would make
+        // `==` tell the copy apart.
+        _ => value.clone(),
+    }
+}
+
+fn freeze(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [value] = arguments(args);
+
+    Ok(value.freeze())
+}
+
+fn is_frozen(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [value] = arguments(args);
+
+    Ok(Object::Bool(value.is_frozen()))
+}
+
+/// Concatenates the elements in a single allocation, unlike repeated `+`.
+/// Elements that aren't strings are joined by their display form.
+fn join(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(elements), Object::String(separator)] = arguments(args) else {
+        unreachable!()
+    };
+
+    let parts = elements.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    let len = parts.iter().map(String::len).sum::<usize>()
+        + separator.len() * parts.len().saturating_sub(1);
+    runtime.limits().check_string(len)?;
+    Ok(Object::String(parts.join(&separator).into()))
+}
+
+fn repeat(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(string), Object::Int(count)] = arguments(args) else {
+        unreachable!()
+    };
+
+    repeat_string(&string, count, &runtime.limits())
+}
+
+/// `string` `count` times over, for `repeat` and `string * int`.
+pub fn repeat_string(string: &str, count: i64, limits: &Limits) -> Result<Object> {
+    let Ok(count) = usize::try_from(count) else {
+        bail!("Cannot repeat a string {} times!", count);
+    };
+    let Some(len) = string.len().checked_mul(count) else {
+        bail!("Repeating a string {} times makes it too long!", count);
+    };
+    limits.check_string(len)?;
+
+    // Without a limit only memory bounds the length, and `repeat` aborts the process when it
+    // can't allocate, so try first.
+    if String::new().try_reserve_exact(len).is_err() {
+        bail!("Repeating a string {} times makes it too long!", count);
+    }
+
+    Ok(Object::String(string.repeat(count).into()))
+}
+
+fn concat(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Array(left), Object::Array(right)] = arguments(args) else {
+        unreachable!()
+    };
+
+    concat_arrays(&left, &right, &runtime.limits())
+}
+
+/// A new array with the elements of `left` and then those of `right`, for `concat` and
+/// `array + array`. The elements are shared with the operands rather than copied.
+pub fn concat_arrays(left: &Elements, right: &Elements, limits: &Limits) -> Result<Object> {
+    check_unfrozen(left)?;
+    check_unfrozen(right)?;
+    limits.check_array(left.len() + right.len())?;
+    Ok(Object::Array(
+        left.iter().chain(right.iter()).cloned().collect(),
+    ))
+}
+
+fn merge(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Hash(left), Object::Hash(right)] = arguments(args) else {
+        unreachable!()
+    };
+
+    merge_hashes(&left, &right, &runtime.limits())
+}
+
+/// A new hash with the pairs of both, for `merge` and `hash + hash`. Keys in both get the value
+/// from `right`. Like `concat_arrays`, keys and values are shared rather than copied.
+pub fn merge_hashes(left: &Pairs, right: &Pairs, limits: &Limits) -> Result<Object> {
+    check_unfrozen_hash(left)?;
+    check_unfrozen_hash(right)?;
+
+    let mut merged = left.clone();
+    merged.extend(
+        right
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone())),
+    );
+    limits.check_hash(merged.len())?;
+    Ok(Object::Hash(merged))
+}
+
+/// Resumes a generator up to its next `yield`. Returns null once it has finished.
+fn next(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Generator(generator)] = arguments(args) else {
+        unreachable!()
+    };
+
+    Ok(generator.resume()?.unwrap_or(Object::Null))
+}
+
+fn delay(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [function] = arguments(args);
+
+    Ok(Object::Thunk(Rc::new(Thunk::new(function))))
+}
+
+fn force(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Thunk(thunk)] = arguments(args) else {
+        unreachable!()
+    };
+
+    thunk.force(runtime)
+}
+
+fn compose(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [outer, inner] = arguments(args);
+
+    Ok(Object::Callable(Rc::new(Callable::Composed(outer, inner))))
+}
+
+fn partial(_: &mut dyn Runtime, mut args: Vec<Object>) -> Result<Object> {
+    let function = args.remove(0);
+
+    Ok(Object::Callable(Rc::new(Callable::Partial(function, args))))
+}
+
+/// Only calls whose arguments are all hashable (see `Object::hashable`) are cached.
+fn memoize(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [function] = arguments(args);
+
+    Ok(Object::Callable(Rc::new(Callable::Memoized(
+        function,
+        Default::default(),
+    ))))
+}
+
+/// Only hashable values (see `Object::hashable`) can be set elements, like cache keys in
+/// `memoize`, since they are compared by contents alone.
+fn set_element(builtin: &str, value: Object) -> Result<Object> {
+    if value.hashable().is_err() {
+        bail!(
+            "Builtin {} cannot add {} to a set!",
+            builtin,
+            value.get_type()
+        );
+    }
+
+    Ok(value)
+}
+
+/// Builds a set from the elements of an array, range or any other iterable value.
+fn set(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [elements] = arguments(args);
+
+    let Some(iter) = elements.iter() else {
+        bail!("Builtin set is not defined for {}!", elements.get_type());
+    };
+    runtime.limits().check_array(iter.size_hint().0)?;
+    Ok(Object::Set(
+        iter.map(|x| set_element("set", x)).collect::<Result<_>>()?,
+    ))
+}
+
+fn insert(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Set(mut elements), value] = arguments(args) else {
+        unreachable!()
+    };
+
+    runtime.limits().check_array(elements.len() + 1)?;
+    elements.insert(set_element("insert", value)?);
+    Ok(Object::Set(elements))
+}
+
+fn contains(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Set(elements), value] = arguments(args) else {
+        unreachable!()
+    };
+
+    Ok(Object::Bool(elements.contains(&value)))
+}
+
+fn sets(args: Vec<Object>) -> (HashSet<Object>, HashSet<Object>) {
+    let [Object::Set(left), Object::Set(right)] = arguments(args) else {
+        unreachable!()
+    };
+
+    (left, right)
+}
+
+fn union(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let (left, right) = sets(args);
+    Ok(Object::Set(left.union(&right).cloned().collect()))
+}
+
+fn intersection(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let (left, right) = sets(args);
+    Ok(Object::Set(left.intersection(&right).cloned().collect()))
+}
+
+fn difference(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let (left, right) = sets(args);
+    Ok(Object::Set(left.difference(&right).cloned().collect()))
+}
+
+/// Parameters a callable still takes: a partial application has bound the first few and a
+/// composition takes those of the function it calls first.
+fn parameters<'a>(builtin: &str, function: &'a Object) -> Result<&'a [Identifier]> {
+    match function {
+        Object::Function(function) => Ok(&function.params),
+        Object::Callable(callable) => match callable.as_ref() {
+            Callable::Composed(_, inner) => parameters(builtin, inner),
+            Callable::Partial(function, bound) => Ok(parameters(builtin, function)?
+                .get(bound.len()..)
+                .unwrap_or_default()),
+            Callable::Memoized(function, _) => parameters(builtin, function),
+            Callable::Host(_) => bail!("Builtin {} is not defined for builtin!", builtin),
+        },
+        _ => bail!(
+            "Builtin {} is not defined for {}!",
+            builtin,
+            function.get_type()
+        ),
+    }
+}
+
+fn arity(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [function] = arguments(args);
+
+    Ok(Object::Int(parameters("arity", &function)?.len() as i64))
+}
+
+fn params(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [function] = arguments(args);
+
+    Ok(Object::Array(
+        parameters("params", &function)?
+            .iter()
+            .map(|param| Object::String(param.0.as_str().into()))
+            .collect(),
+    ))
+}
+
+/// The statements of a function body, see `block_source`.
+fn body_source(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [function] = arguments(args);
+
+    // Partial applications and the like are functions too, but have no body of their own.
+    Ok(match function {
+        Object::Function(function) => Object::String(block_source(&function.body).into()),
+        _ => bail!(
+            "Builtin body_source is not defined for {}!",
+            function.get_type()
+        ),
+    })
+}
+
+/// The docstring of a function, or null when it has none. See `Object::doc`.
+fn doc(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [function] = arguments(args);
+
+    Ok(function
+        .doc()
+        .map_or(Object::Null, |doc| Object::String(doc.into())))
+}
+
+/// Runs a string of Monkey code where `eval` was called, so it sees and can define the local
+/// variables there. Gives the value of its last statement.
+fn eval(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(source)] = arguments(args) else {
+        unreachable!()
+    };
+    runtime.eval_source(&source)
+}
+
+/// A promise settling with null after `ms` milliseconds.
+fn sleep_async(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    runtime.capabilities().check_time("sleep_async")?;
+    let [Object::Int(ms)] = arguments(args) else {
+        unreachable!()
+    };
+    let Ok(ms) = u64::try_from(ms) else {
+        bail!("Cannot sleep for {} ms!", ms);
+    };
+    Ok(Object::Promise(Rc::new(Promise::sleep(
+        Duration::from_millis(ms),
+    ))))
+}
+
+/// The int `string` spells in `base`, from 2 to 36, or null if it isn't one or doesn't fit.
+/// Surrounding whitespace and a leading sign are allowed.
+fn parse_int(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(string), Object::Int(base)] = arguments(args) else {
+        unreachable!()
+    };
+    if !(2..=36).contains(&base) {
+        bail!("Invalid base {}, expected 2 to 36!", base);
+    }
+
+    Ok(i64::from_str_radix(string.trim(), base as u32).map_or(Object::Null, Object::Int))
+}
+
+/// The float `string` spells, e.g. `-1.5`, `2e3` or `inf`, or null if it isn't one. Surrounding
+/// whitespace is allowed. The decimal separator is always `.`.
+fn parse_float(_: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(string)] = arguments(args) else {
+        unreachable!()
+    };
+    Ok(string.trim().parse().map_or(Object::Null, Object::Float))
+}
+
+/// `number` as a string with exactly `digits` digits after the `.`, rounding ties to even.
+/// More decimals than any float has, and few enough for `format!`, which can't pad to millions.
+const MAX_FIXED_DIGITS: usize = 100;
+
+fn to_fixed(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let (number, digits) = match arguments(args) {
+        [Object::Float(number), Object::Int(digits)] => (number, digits),
+        [Object::Int(number), Object::Int(digits)] => (number as f64, digits),
+        _ => unreachable!(),
+    };
+    let digits = match usize::try_from(digits) {
+        Ok(digits) if digits <= MAX_FIXED_DIGITS => digits,
+        _ => bail!(
+            "Builtin to_fixed needs digits from 0 to {}!",
+            MAX_FIXED_DIGITS
+        ),
+    };
+
+    runtime.limits().check_string(digits)?;
+    Ok(Object::String(format!("{:.*}", digits, number).into()))
+}
+
+/// Restarts the numbers `random_int` draws from `n`, so runs can be reproduced.
+fn seed(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Int(n)] = arguments(args) else {
+        unreachable!()
+    };
+    runtime.random().seed(n as u64);
+    Ok(Object::Null)
+}
+
+/// A pseudo-random int between `lo` and `hi`, both included.
+fn random_int(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::Int(lo), Object::Int(hi)] = arguments(args) else {
+        unreachable!()
+    };
+    if lo > hi {
+        bail!("Cannot draw a random int between {} and {}!", lo, hi);
+    }
+    Ok(Object::Int(runtime.random().int_between(lo, hi)))
+}
+
+/// A promise settling with the contents of a file, read by another thread.
+fn async_read_file(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [Object::String(path)] = arguments(args) else {
+        unreachable!()
+    };
+    runtime
+        .capabilities()
+        .check_path("async_read_file", Path::new(&*path))?;
+
+    let path = path.to_string();
+    let limits = runtime.limits();
+    Ok(Object::Promise(Rc::new(Promise::spawn(move || {
+        let error = |error: &dyn Display| format!("Cannot read {}: {}!", path, error);
+        let len = fs::metadata(&path).map_err(|e| error(&e))?.len();
+        limits
+            .check_string(usize::try_from(len).unwrap_or(usize::MAX))
+            .map_err(|e| error(&e))?;
+        fs::read_to_string(&path).map_err(|e| error(&e))
+    }))))
+}
+
+fn puts(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    for arg in args {
+        writeln!(runtime.output(), "{}", arg)?;
+    }
+
+    Ok(Object::Null)
+}
+
+/// Writes `prompt` and reads a line, without its line break. Null once the input has ended.
+fn input(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [prompt] = arguments(args);
+
+    write!(runtime.output(), "{}", prompt)?;
+    runtime.output().flush()?;
+
+    let mut line = String::new();
+    if runtime.read_line(&mut line)? == 0 {
+        return Ok(Object::Null);
+    }
+    runtime.limits().check_string(line.len())?;
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    Ok(Object::String(line.into()))
+}
+
+/// The value of the environment variable `name`, or null if it isn't set.
+fn getenv(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    runtime.capabilities().check_env("getenv")?;
+    let [Object::String(name)] = arguments(args) else {
+        unreachable!()
+    };
+    Ok(std::env::var_os(&*name).map_or(Object::Null, |value| {
+        Object::String(value.to_string_lossy().into())
+    }))
+}
+
+/// Runs the program `cmd` with the strings in `args` and waits for it to finish. Gives a hash
+/// with its exit `status`, null if it was killed by a signal, and what it wrote to `stdout` and
+/// `stderr`.
+fn exec(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    runtime.capabilities().check_exec("exec")?;
+
+    let [Object::String(cmd), Object::Array(args)] = arguments(args) else {
+        unreachable!()
+    };
+
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Object::String(arg) => Ok(arg.to_string()),
+            _ => bail!("Arguments to exec must be strings, not {}!", arg.get_type()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let output = Command::new(&*cmd)
+        .args(args)
+        .output()
+        .map_err(|error| anyhow!("Cannot run {}: {}!", cmd, error))?;
+
+    let limits = runtime.limits();
+    limits.check_string(output.stdout.len())?;
+    limits.check_string(output.stderr.len())?;
+
+    let field = |name: &str, value| (Object::String(name.into()), value);
+    let text = |bytes: Vec<u8>| Object::String(String::from_utf8_lossy(&bytes).into());
+    Ok(Object::Hash(
+        HashMap::from([
+            field(
+                "status",
+                output
+                    .status
+                    .code()
+                    .map_or(Object::Null, |code| Object::Int(code.into())),
+            ),
+            field("stdout", text(output.stdout)),
+            field("stderr", text(output.stderr)),
+        ])
+        .into(),
+    ))
+}
+
+fn inspect(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let [value] = arguments(args);
+
+    let source = value.inspect();
+    runtime.limits().check_string(source.len())?;
+    Ok(Object::String(source.into()))
+}
+
+fn stats(runtime: &mut dyn Runtime, _: Vec<Object>) -> Result<Object> {
+    let stats = runtime.heap_stats()?;
+    Ok(Object::Hash(
+        stats
+            .entries()
+            .into_iter()
+            .map(|(name, count)| (Object::String(name.into()), Object::Int(count as i64)))
+            .collect(),
+    ))
+}
+
+fn format(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let limits = runtime.limits();
+    Ok(Object::String(format_args(args, &limits)?.into()))
+}
+
+/// Like `format`, but writes the result to the output instead, without a trailing newline.
+fn printf(runtime: &mut dyn Runtime, args: Vec<Object>) -> Result<Object> {
+    let limits = runtime.limits();
+    let output = format_args(args, &limits)?;
+    write!(runtime.output(), "{}", output)?;
+
+    Ok(Object::Null)
+}
+
+fn format_args(mut args: Vec<Object>, limits: &Limits) -> Result<String> {
+    let Object::String(template) = args.remove(0) else {
+        unreachable!()
+    };
+
+    format_string(&template, &args, limits)
+}
+
+/// Replaces each `{}` in `template` with the next argument, or `{n}` with the n-th one.
+/// A `:` introduces a spec made of an optional fill char and alignment (`<`, `>` or `^`)
+/// followed by a width, e.g. `{:>8}` or `{1:*^5}`. Ints align right by default, everything
+/// else left. `{{` and `}}` stand for literal braces.
+fn format_string(template: &str, args: &[Object], limits: &Limits) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut chars = template.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                output.push('}');
+            }
+            '}' => bail!("Unmatched }} in format string!"),
+            '{' => {
+                let rest = chars.as_str();
+                let Some(end) = rest.find('}') else {
+                    bail!("Unclosed {{ in format string!");
+                };
+                let placeholder = &rest[..end];
+                chars = rest[end + 1..].chars();
+
+                let (index, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+                let index = match index {
+                    "" => {
+                        next += 1;
+                        next - 1
+                    }
+                    index => index
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid placeholder {{{}}}!", placeholder))?,
+                };
+                let Some(arg) = args.get(index) else {
+                    bail!(
+                        "Missing argument {} for placeholder {{{}}}!",
+                        index,
+                        placeholder
+                    );
+                };
+
+                let spec = Spec::parse(spec)
+                    .ok_or_else(|| anyhow!("Invalid placeholder {{{}}}!", placeholder))?;
+                let value = arg.to_string();
+                let padding = spec.width.saturating_sub(value.chars().count());
+                let Some(len) = padding
+                    .checked_mul(spec.fill.len_utf8())
+                    .and_then(|len| len.checked_add(value.len()))
+                    .and_then(|len| len.checked_add(output.len()))
+                else {
+                    bail!("Width of placeholder {{{}}} is too large!", placeholder);
+                };
+                limits.check_string(len)?;
+                // Like `repeat_string`, only memory may bound the width.
+                if output.try_reserve_exact(len - output.len()).is_err() {
+                    bail!("Width of placeholder {{{}}} is too large!", placeholder);
+                }
+                pad(&mut output, arg, &value, padding, &spec);
+            }
+            ch => output.push(ch),
+        }
+    }
+
+    limits.check_string(output.len())?;
+    Ok(output)
+}
+
+/// A `[[fill]align][width]` placeholder spec.
+struct Spec {
+    fill: char,
+    align: Option<char>,
+    width: usize,
+}
+
+impl Spec {
+    /// None if the spec is malformed.
+    fn parse(spec: &str) -> Option<Spec> {
+        let mut spec_chars = spec.chars();
+        let (fill, align, width) = match (spec_chars.next(), spec_chars.next()) {
+            (Some(fill), Some(align @ ('<' | '>' | '^'))) => {
+                (fill, Some(align), &spec[fill.len_utf8() + 1..])
+            }
+            (Some(align @ ('<' | '>' | '^')), _) => (' ', Some(align), &spec[1..]),
+            _ => (' ', None, spec),
+        };
+        let width = match width {
+            "" => 0,
+            width => width.parse().ok()?,
+        };
+
+        Some(Spec { fill, align, width })
+    }
+}
+
+/// Writes `value`, the display form of `arg`, into `output` with `padding` fill chars placed
+/// as `spec` says.
+fn pad(output: &mut String, arg: &Object, value: &str, padding: usize, spec: &Spec) {
+    let Spec { fill, align, .. } = *spec;
+
+    let (before, after) = match align.unwrap_or(match arg {
+        Object::Int(_) => '>',
+        _ => '<',
+    }) {
+        '<' => (0, padding),
+        '>' => (padding, 0),
+        _ => (padding / 2, padding - padding / 2),
+    };
+
+    output.extend(std::iter::repeat_n(fill, before));
+    output.push_str(value);
+    output.extend(std::iter::repeat_n(fill, after));
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, Cursor, Write};
+
+    use anyhow::{bail, Result};
+
+    use super::{lookup, Runtime};
+    use crate::eval::{object::Object, random::Random};
+
+    /// Stands in for an engine: "calling" a function doubles its integer argument.
+    #[derive(Default)]
+    struct Doubler {
+        output: Vec<u8>,
+        input: Cursor<Vec<u8>>,
+        random: Random,
+    }
+
+    impl Runtime for Doubler {
+        fn call(&mut self, _: Object, args: Vec<Object>) -> Result<Object> {
+            match args.as_slice() {
+                [Object::Int(num)] => Ok(Object::Int(num * 2)),
+                _ => bail!("unexpected arguments"),
+            }
+        }
+
+        fn output(&mut self) -> &mut dyn Write {
+            &mut self.output
+        }
+
+        fn read_line(&mut self, line: &mut String) -> std::io::Result<usize> {
+            self.input.read_line(line)
+        }
+
+        fn random(&self) -> &Random {
+            &self.random
+        }
+    }
+
+    #[test]
+    fn builtins_run_on_any_runtime() -> Result<()> {
+        let mut runtime = Doubler::default();
+
+        let map = lookup("map").unwrap();
+        let array = Object::Array(vec![Object::Int(1), Object::Int(2)].into());
+        assert_eq!(
+            (map.func)(&mut runtime, vec![array, Object::Null])?,
+            Object::Array(vec![Object::Int(2), Object::Int(4)].into())
+        );
+
+        let puts = lookup("puts").unwrap();
+        puts.call(
+            &mut runtime,
+            vec![Object::Int(1), Object::String("a".into())],
+        )?;
+        assert_eq!(runtime.output, b"1\na\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn printf_writes_without_newline() -> Result<()> {
+        let mut runtime = Doubler::default();
+
+        let printf = lookup("printf").unwrap();
+        for args in [vec!["{} + {}", "a"], vec![" = {{{}}}\n"]] {
+            let mut args = args
+                .into_iter()
+                .map(|x| Object::String(x.into()))
+                .collect::<Vec<_>>();
+            args.push(Object::Int(1));
+            assert_eq!(printf.call(&mut runtime, args)?, Object::Null);
+        }
+        assert_eq!(runtime.output, b"a + 1 = {1}\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn input_reads_lines() -> Result<()> {
+        let mut runtime = Doubler {
+            input: Cursor::new(b"Ada\r\n\nlast".to_vec()),
+            ..Default::default()
+        };
+
+        let input = lookup("input").unwrap();
+        let mut read = |prompt: &str| input.call(&mut runtime, vec![Object::String(prompt.into())]);
+        assert_eq!(read("Name? ")?, Object::String("Ada".into()));
+        assert_eq!(read("")?, Object::String("".into()));
+        assert_eq!(read("")?, Object::String("last".into()));
+        assert_eq!(read("More? ")?, Object::Null);
+        assert_eq!(runtime.output, b"Name? More? ");
+
+        Ok(())
+    }
+}
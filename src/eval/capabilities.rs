@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+/// What builtins may do outside the interpreter. Everything but the clock is denied by default,
+/// so untrusted scripts can only compute and print.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Directories, and everything under them, that builtins may read files from.
+    pub fs: Vec<PathBuf>,
+    /// Connect to other machines. No builtin needs it yet.
+    pub net: bool,
+    /// Run other programs, see `exec`.
+    pub exec: bool,
+    /// Read environment variables, see `getenv`.
+    pub env: bool,
+    /// Wait on the clock, see `sleep_async`.
+    pub time: bool,
+}
+
+/// What runtimes that don't configure any are allowed.
+pub static DEFAULT: Capabilities = Capabilities::none();
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl Capabilities {
+    /// Only the clock.
+    pub const fn none() -> Self {
+        Self {
+            fs: Vec::new(),
+            net: false,
+            exec: false,
+            env: false,
+            time: true,
+        }
+    }
+
+    /// Anything, including reading any file.
+    pub fn all() -> Self {
+        Self {
+            fs: vec![PathBuf::from("/")],
+            net: true,
+            exec: true,
+            env: true,
+            time: true,
+        }
+    }
+
+    pub fn check_exec(&self, builtin: &str) -> Result<()> {
+        check(self.exec, builtin, "exec")
+    }
+
+    pub fn check_env(&self, builtin: &str) -> Result<()> {
+        check(self.env, builtin, "env")
+    }
+
+    pub fn check_time(&self, builtin: &str) -> Result<()> {
+        check(self.time, builtin, "time")
+    }
+
+    /// Fails unless `path` is under one of the allowed directories, once symlinks and `..` are
+    /// resolved. Paths that don't exist are let through, as there is nothing to read there.
+    pub fn check_path(&self, builtin: &str, path: &Path) -> Result<()> {
+        check(!self.fs.is_empty(), builtin, "fs")?;
+
+        let Ok(path) = path.canonicalize() else {
+            return Ok(());
+        };
+        let allowed = self
+            .fs
+            .iter()
+            .any(|dir| dir.canonicalize().is_ok_and(|dir| path.starts_with(dir)));
+        if !allowed {
+            bail!(
+                "Builtin {} cannot access {}, which is outside the allowed directories!",
+                builtin,
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn check(allowed: bool, builtin: &str, capability: &str) -> Result<()> {
+    if !allowed {
+        bail!(
+            "Builtin {} needs the {} capability, which is disabled!",
+            builtin,
+            capability
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! Conversions between Rust values and `Object`, for applications embedding the interpreter.
+//! `IntoMonkey` and `FromMonkey` do the work, so a type of the application can join in by
+//! implementing them; the standard types below also convert with `From` and `TryFrom`.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use super::object::Object;
+
+pub trait IntoMonkey {
+    fn into_monkey(self) -> Object;
+}
+
+pub trait FromMonkey: Sized {
+    /// Fails with an error naming the expected and given types when `object` isn't one.
+    fn from_monkey(object: &Object) -> Result<Self>;
+}
+
+fn mismatch<T>(expected: &str, object: &Object) -> Result<T> {
+    bail!("Expected {}, got {}!", expected, object.get_type())
+}
+
+impl IntoMonkey for Object {
+    fn into_monkey(self) -> Object {
+        self
+    }
+}
+
+impl FromMonkey for Object {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        Ok(object.clone())
+    }
+}
+
+impl IntoMonkey for i64 {
+    fn into_monkey(self) -> Object {
+        Object::Int(self)
+    }
+}
+
+impl FromMonkey for i64 {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        match object {
+            Object::Int(num) => Ok(*num),
+            _ => mismatch("int", object),
+        }
+    }
+}
+
+impl IntoMonkey for f64 {
+    fn into_monkey(self) -> Object {
+        Object::Float(self)
+    }
+}
+
+/// Ints are promoted, as they are in arithmetic with floats.
+impl FromMonkey for f64 {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        match object {
+            Object::Float(num) => Ok(*num),
+            Object::Int(num) => Ok(*num as f64),
+            _ => mismatch("float", object),
+        }
+    }
+}
+
+impl IntoMonkey for bool {
+    fn into_monkey(self) -> Object {
+        Object::Bool(self)
+    }
+}
+
+impl FromMonkey for bool {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        match object {
+            Object::Bool(bool) => Ok(*bool),
+            _ => mismatch("bool", object),
+        }
+    }
+}
+
+impl IntoMonkey for String {
+    fn into_monkey(self) -> Object {
+        Object::String(self.into())
+    }
+}
+
+impl IntoMonkey for &str {
+    fn into_monkey(self) -> Object {
+        Object::String(self.into())
+    }
+}
+
+impl FromMonkey for String {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        match object {
+            Object::String(s) => Ok(s.to_string()),
+            _ => mismatch("string", object),
+        }
+    }
+}
+
+impl<T: IntoMonkey> IntoMonkey for Vec<T> {
+    fn into_monkey(self) -> Object {
+        Object::Array(self.into_iter().map(T::into_monkey).collect())
+    }
+}
+
+/// From an array or a tuple.
+impl<T: FromMonkey> FromMonkey for Vec<T> {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        match object {
+            Object::Array(elements) | Object::Tuple(elements) => {
+                elements.iter().map(T::from_monkey).collect()
+            }
+            _ => mismatch("array", object),
+        }
+    }
+}
+
+impl<T: IntoMonkey> IntoMonkey for HashMap<String, T> {
+    fn into_monkey(self) -> Object {
+        Object::Hash(
+            self.into_iter()
+                .map(|(key, value)| (key.into_monkey(), value.into_monkey()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: FromMonkey> FromMonkey for HashMap<String, T> {
+    fn from_monkey(object: &Object) -> Result<Self> {
+        match object {
+            Object::Hash(pairs) => pairs
+                .iter()
+                .map(|(key, value)| Ok((String::from_monkey(key)?, T::from_monkey(value)?)))
+                .collect(),
+            _ => mismatch("hash", object),
+        }
+    }
+}
+
+impl From<i64> for Object {
+    fn from(value: i64) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl TryFrom<Object> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(object: Object) -> Result<Self> {
+        Self::from_monkey(&object)
+    }
+}
+
+impl From<f64> for Object {
+    fn from(value: f64) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl TryFrom<Object> for f64 {
+    type Error = anyhow::Error;
+
+    fn try_from(object: Object) -> Result<Self> {
+        Self::from_monkey(&object)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = anyhow::Error;
+
+    fn try_from(object: Object) -> Result<Self> {
+        Self::from_monkey(&object)
+    }
+}
+
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl From<&str> for Object {
+    fn from(value: &str) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl TryFrom<Object> for String {
+    type Error = anyhow::Error;
+
+    fn try_from(object: Object) -> Result<Self> {
+        Self::from_monkey(&object)
+    }
+}
+
+impl<T: IntoMonkey> From<Vec<T>> for Object {
+    fn from(value: Vec<T>) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl<T: FromMonkey> TryFrom<Object> for Vec<T> {
+    type Error = anyhow::Error;
+
+    fn try_from(object: Object) -> Result<Self> {
+        Self::from_monkey(&object)
+    }
+}
+
+impl<T: IntoMonkey> From<HashMap<String, T>> for Object {
+    fn from(value: HashMap<String, T>) -> Self {
+        value.into_monkey()
+    }
+}
+
+impl<T: FromMonkey> TryFrom<Object> for HashMap<String, T> {
+    type Error = anyhow::Error;
+
+    fn try_from(object: Object) -> Result<Self> {
+        Self::from_monkey(&object)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+
+    use crate::eval::object::Object;
+
+    use super::{FromMonkey, IntoMonkey};
+
+    #[test]
+    fn round_trips() -> Result<()> {
+        assert_eq!(Object::from(1), Object::Int(1));
+        assert_eq!(i64::try_from(Object::Int(1))?, 1);
+        assert_eq!(f64::try_from(Object::Int(2))?, 2.0);
+        assert_eq!(Object::from("a"), Object::String("a".into()));
+        assert!(bool::try_from(Object::from(true))?);
+
+        let rows = vec![vec![1, 2], vec![3]];
+        assert_eq!(Vec::<Vec<i64>>::try_from(Object::from(rows.clone()))?, rows);
+
+        let scores = HashMap::from([(String::from("ana"), 1.5)]);
+        let object = scores.clone().into_monkey();
+        assert_eq!(HashMap::<String, f64>::from_monkey(&object)?, scores);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mismatches() {
+        assert_eq!(
+            i64::try_from(Object::from("1")).unwrap_err().to_string(),
+            "Expected int, got string!"
+        );
+        assert_eq!(
+            Vec::<String>::try_from(Object::from(vec![Object::from("a"), Object::Null]))
+                .unwrap_err()
+                .to_string(),
+            "Expected string, got null!"
+        );
+        let hash = Object::Hash(HashMap::from([(Object::Int(1), Object::Int(1))]).into());
+        assert_eq!(
+            HashMap::<String, i64>::from_monkey(&hash)
+                .unwrap_err()
+                .to_string(),
+            "Expected string, got int!"
+        );
+    }
+}
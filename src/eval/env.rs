@@ -1,11 +1,14 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use super::object::Object;
+use crate::symbol::Symbol;
+
+use super::{object::Object, stats::Live};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Env {
-    store: HashMap<String, Object>,
+    store: HashMap<Symbol, Object>,
     pub outer: Option<Rc<RefCell<Env>>>,
+    live: Live,
 }
 
 impl Default for Env {
@@ -19,11 +22,20 @@ impl Env {
         Self {
             store: HashMap::new(),
             outer: None,
+            live: Live::env(),
+        }
+    }
+
+    pub fn new_enclosed(outer: Rc<RefCell<Env>>) -> Self {
+        Self {
+            store: HashMap::new(),
+            outer: Some(outer),
+            live: Live::env(),
         }
     }
 
-    pub fn get(&self, id: &String) -> Option<Object> {
-        match self.store.get(id) {
+    pub fn get(&self, id: Symbol) -> Option<Object> {
+        match self.store.get(&id) {
             Some(value) => Some(value.clone()),
             None => match &self.outer {
                 Some(outer) => outer.borrow().get(id),
@@ -32,7 +44,78 @@ impl Env {
         }
     }
 
-    pub fn assign(&mut self, id: String, value: Object) {
+    /// The bindings of this scope and all the enclosing ones, innermost first, each sorted by name.
+    pub fn scopes(&self) -> Vec<Vec<(Symbol, Object)>> {
+        let mut bindings = self
+            .store
+            .iter()
+            .map(|(id, value)| (*id, value.clone()))
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|(id, _)| id.as_str());
+
+        let mut scopes = vec![bindings];
+        if let Some(outer) = &self.outer {
+            scopes.extend(outer.borrow().scopes());
+        }
+        scopes
+    }
+
+    /// Values bound in this scope, ignoring enclosing ones.
+    pub fn values(&self) -> impl Iterator<Item = &Object> {
+        self.store.values()
+    }
+
+    /// Names bound in this scope or any enclosing one.
+    pub fn names(&self) -> Vec<Symbol> {
+        let mut names = self.store.keys().copied().collect::<Vec<_>>();
+        if let Some(outer) = &self.outer {
+            names.extend(outer.borrow().names());
+        }
+        names
+    }
+
+    /// Number of bindings in this scope.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Whether `id` is bound in this scope, ignoring enclosing ones.
+    pub fn contains(&self, id: Symbol) -> bool {
+        self.store.contains_key(&id)
+    }
+
+    /// Number of bindings in the outermost, global scope.
+    pub fn global_len(&self) -> usize {
+        match &self.outer {
+            Some(outer) => outer.borrow().global_len(),
+            None => self.store.len(),
+        }
+    }
+
+    /// Declares `id` in this scope, shadowing any outer binding with the same name.
+    pub fn define(&mut self, id: Symbol, value: Object) {
         self.store.insert(id, value);
     }
+
+    /// Updates the nearest existing binding of `id`. If it was never declared, it is declared in
+    /// the global scope when `declare_global` is set, otherwise `false` is returned.
+    pub fn assign(&mut self, id: Symbol, value: Object, declare_global: bool) -> bool {
+        if let Some(slot) = self.store.get_mut(&id) {
+            *slot = value;
+            return true;
+        }
+
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(id, value, declare_global),
+            None if declare_global => {
+                self.define(id, value);
+                true
+            }
+            None => false,
+        }
+    }
 }
@@ -0,0 +1,102 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use corosensei::{stack::DefaultStack, Coroutine, CoroutineResult, Yielder};
+
+use crate::ast::Program;
+
+use super::{
+    object::{Object, Promise, GENERATOR_STACK_SIZE},
+    Eval,
+};
+
+/// Top-level program running on a stack of its own, so it can be suspended at an `await` from
+/// any depth of the evaluator. Tasks run with the generators' maximum call depth.
+pub struct Task {
+    coroutine: Coroutine<(), Rc<Promise>, Result<Object>>,
+    /// The promise the task is suspended on.
+    waiting: Option<Rc<Promise>>,
+}
+
+impl Task {
+    pub(super) fn new(
+        body: impl FnOnce(&Yielder<(), Rc<Promise>>) -> Result<Object> + 'static,
+    ) -> Result<Self> {
+        let stack = DefaultStack::new(GENERATOR_STACK_SIZE)?;
+
+        Ok(Self {
+            coroutine: Coroutine::with_stack(stack, |awaiter, ()| body(awaiter)),
+            waiting: None,
+        })
+    }
+
+    /// Runs the task until it finishes, returning its result, or until it awaits a promise that
+    /// hasn't settled yet.
+    fn resume(&mut self) -> Option<Result<Object>> {
+        match self.coroutine.resume(()) {
+            CoroutineResult::Yield(promise) => {
+                self.waiting = Some(promise);
+                None
+            }
+            CoroutineResult::Return(result) => Some(result),
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        self.waiting
+            .as_ref()
+            .is_none_or(|promise| promise.poll().is_some())
+    }
+}
+
+/// Runs programs that await at the top level, e.g. REPL lines, cooperatively: a program runs as
+/// a task until it awaits a promise that hasn't settled, other code runs meanwhile and
+/// `run_ready` resumes the task once the promise settles.
+#[derive(Default)]
+pub struct EventLoop {
+    tasks: Vec<Task>,
+}
+
+impl EventLoop {
+    /// Starts `program` in a task evaluating it in `eval`'s current scope. Returns its result if
+    /// it finished without suspending.
+    pub fn spawn(&mut self, eval: &Eval, program: Program) -> Result<Option<Result<Object>>> {
+        let mut task = eval.start_task(program)?;
+
+        Ok(match task.resume() {
+            Some(result) => Some(result),
+            None => {
+                self.tasks.push(task);
+                None
+            }
+        })
+    }
+
+    /// Resumes the tasks whose promise has settled, returning the results of those that finish,
+    /// in the order they were spawned.
+    pub fn run_ready(&mut self) -> Vec<Result<Object>> {
+        let mut results = vec![];
+        self.tasks.retain_mut(|task| {
+            if !task.is_ready() {
+                return true;
+            }
+            match task.resume() {
+                Some(result) => {
+                    results.push(result);
+                    false
+                }
+                None => true,
+            }
+        });
+        results
+    }
+
+    /// How many tasks are suspended.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
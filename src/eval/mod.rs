@@ -1,21 +1,278 @@
+pub mod builtins;
+pub mod capabilities;
+pub mod convert;
 pub mod env;
+pub mod event_loop;
+pub mod modules;
 pub mod object;
+pub mod observer;
+pub mod operators;
+pub mod pretty;
+pub mod profile;
+pub mod random;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod stats;
+pub mod steps;
+#[cfg(feature = "persistent")]
+pub mod vector;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use crate::ast::{
-    BlockStatement, Expression, Identifier, IfExpression, Infix, Literal, Prefix, Program,
-    Statement,
+use crate::{
+    ast::{
+        BlockStatement, Expression, Identifier, IfExpression, Imports, Infix, Literal, Pattern,
+        Prefix, Program, Statement,
+    },
+    lexer::Lexer,
+    parser::Parser,
+    symbol::Symbol,
 };
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use corosensei::Yielder;
 
-use self::{env::Env, object::Object};
+use self::{
+    builtins::{HostBuiltin, Runtime},
+    capabilities::Capabilities,
+    env::Env,
+    event_loop::Task,
+    modules::{Location, Source},
+    object::{
+        Callable, Function, Generator, Module, Object, Pairs, Promise, GENERATOR_MAX_CALL_DEPTH,
+    },
+    observer::EvalObserver,
+    operators::{InfixFn, Operators},
+    profile::Profiler,
+    random::Random,
+    stats::{HeapStats, Live},
+    steps::{StepResult, Steps},
+};
 
-pub struct Eval {
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Indexing a hash with a missing key is an error instead of returning null.
+    pub strict_index: bool,
+    /// Record call counts and time spent per function.
+    pub profile: bool,
+    /// Record how long each top-level statement takes, see `Eval::take_timings`.
+    pub time: bool,
+    /// Assigning to an undeclared variable is an error instead of declaring it globally.
+    pub strict: bool,
+    /// Deepest nesting of function calls before failing with a stack overflow error. Every call
+    /// takes several recursive Rust calls, so the thread running the evaluation needs a big
+    /// enough stack to reach it.
+    pub max_call_depth: usize,
+    /// What `/` does with two ints.
+    pub division: Division,
+    /// Caps on allocations, see `Limits`.
+    pub limits: Limits,
+    /// What builtins may do outside the interpreter.
+    pub capabilities: Capabilities,
+    /// Directories `import` searches, in order, before the standard library. See
+    /// `modules::search_paths`.
+    pub module_paths: Vec<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strict_index: false,
+            profile: false,
+            time: false,
+            strict: false,
+            max_call_depth: 1000,
+            division: Division::default(),
+            limits: Limits::default(),
+            capabilities: Capabilities::default(),
+            module_paths: vec![],
+        }
+    }
+}
+
+/// Caps on what scripts can allocate, so untrusted code exceeding them fails with an error
+/// instead of exhausting memory. Unlimited by default.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Most elements an array, tuple or set can hold.
+    pub max_array_len: usize,
+    /// Most pairs a hash can hold.
+    pub max_hash_len: usize,
+    /// Most bytes a string can hold.
+    pub max_string_len: usize,
+    /// Most variables a single scope can hold.
+    pub max_env_entries: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_array_len: usize::MAX,
+            max_hash_len: usize::MAX,
+            max_string_len: usize::MAX,
+            max_env_entries: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    pub fn check_array(&self, len: usize) -> Result<()> {
+        if len > self.max_array_len {
+            bail!(
+                "{} elements exceed the array length limit of {}!",
+                len,
+                self.max_array_len
+            );
+        }
+        Ok(())
+    }
+
+    pub fn check_hash(&self, len: usize) -> Result<()> {
+        if len > self.max_hash_len {
+            bail!(
+                "{} pairs exceed the hash size limit of {}!",
+                len,
+                self.max_hash_len
+            );
+        }
+        Ok(())
+    }
+
+    pub fn check_string(&self, len: usize) -> Result<()> {
+        if len > self.max_string_len {
+            bail!(
+                "{} bytes exceed the string length limit of {}!",
+                len,
+                self.max_string_len
+            );
+        }
+        Ok(())
+    }
+
+    pub fn check_env(&self, len: usize) -> Result<()> {
+        if len > self.max_env_entries {
+            bail!(
+                "{} variables exceed the scope size limit of {}!",
+                len,
+                self.max_env_entries
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How `/` divides ints. `//` always floors.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Division {
+    /// Round towards zero: `-7 / 2` is -3.
+    #[default]
+    Truncate,
+    /// Round towards negative infinity: `-7 / 2` is -4.
+    Floor,
+    /// Divide as floats: `-7 / 2` is -3.5.
+    Float,
+}
+
+/// One function activation on the call stack.
+struct Frame {
+    /// Name the function was called by, `<anonymous>` when it wasn't called through an identifier.
+    name: String,
+    /// Innermost scope of the activation. Blocks replace it while they run.
     env: Rc<RefCell<Env>>,
 }
 
+/// Output sink shared with the evaluators running generator bodies.
+#[derive(Clone)]
+struct SharedOutput(Rc<RefCell<Box<dyn Write>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// Global scopes of the modules imported so far, so each runs once. None while a module is
+/// being evaluated, to catch circular imports.
+type LoadedModules = HashMap<Location, Option<Rc<RefCell<Env>>>>;
+
+/// Where `input` reads lines from, shared like `SharedOutput`. None reads from stdin, which is
+/// only locked while reading a line so the REPL can keep reading its own lines from it.
+#[derive(Clone)]
+struct SharedInput(Rc<RefCell<Option<Box<dyn BufRead>>>>);
+
+impl SharedInput {
+    fn read_line(&self, line: &mut String) -> std::io::Result<usize> {
+        match self.0.borrow_mut().as_mut() {
+            Some(input) => input.read_line(line),
+            None => std::io::stdin().read_line(line),
+        }
+    }
+}
+
+/// Aborts the evaluation of the `Eval` it came from at the next statement, with an
+/// "Interrupted!" error. It only sets an atomic flag, so it can be used from any thread and from
+/// signal handlers. Each interrupt aborts one evaluation; one made while idle aborts the next.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Withdraws an interrupt the evaluator hasn't acted on yet.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+pub struct Eval {
+    /// Call stack. The first frame holds the global scope and is never popped.
+    frames: Vec<Frame>,
+    stack_trace: Vec<String>,
+    options: Options,
+    profiler: Option<Profiler>,
+    observers: Vec<Box<dyn EvalObserver>>,
+    output: SharedOutput,
+    input: SharedInput,
+    interrupted: Arc<AtomicBool>,
+    warnings: Vec<String>,
+    /// Wall-clock time of each top-level statement of the last `eval`, with `Options::time`.
+    timings: Vec<Duration>,
+    /// Set while evaluating a generator body, to suspend it at each `yield`.
+    yielder: Option<*const Yielder<(), Object>>,
+    /// Set while evaluating a task, to suspend it at each `await` of an unsettled promise.
+    awaiter: Option<*const Yielder<(), Rc<Promise>>>,
+    /// Set while evaluating a program loaded with `load`, to suspend it once `budget` expressions
+    /// have been evaluated.
+    stepper: Option<*const Yielder<usize, ()>>,
+    budget: usize,
+    /// The program `run_steps` runs.
+    steps: Option<Steps>,
+    /// Shared with generators and tasks, so seeding affects the numbers they draw too.
+    random: Rc<Random>,
+    modules: Rc<RefCell<LoadedModules>>,
+    /// Shared with generators and tasks, so operators registered later apply to them too.
+    operators: Rc<Operators>,
+    /// Builtins of the embedding application, shared with generators and tasks like `modules`.
+    host_builtins: Rc<RefCell<HashMap<Symbol, Object>>>,
+}
+
 impl Default for Eval {
     fn default() -> Self {
         Self::new()
@@ -24,17 +281,217 @@ impl Default for Eval {
 
 impl Eval {
     pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    /// An evaluator with default options, allowed to do what `capabilities` permits.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Self::with_options(Options {
+            capabilities,
+            ..Options::default()
+        })
+    }
+
+    pub fn with_options(options: Options) -> Self {
         Self {
-            env: Rc::new(RefCell::new(Env::new())),
+            frames: vec![Frame {
+                name: String::from("<global>"),
+                env: Rc::new(RefCell::new(Env::new())),
+            }],
+            stack_trace: vec![],
+            profiler: options.profile.then(Profiler::default),
+            observers: vec![],
+            output: SharedOutput(Rc::new(RefCell::new(Box::new(std::io::stdout())))),
+            input: SharedInput(Rc::default()),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            warnings: vec![],
+            timings: vec![],
+            yielder: None,
+            awaiter: None,
+            stepper: None,
+            budget: 0,
+            steps: None,
+            random: Rc::default(),
+            modules: Rc::default(),
+            operators: Rc::default(),
+            host_builtins: Rc::default(),
+            options,
+        }
+    }
+
+    fn env(&self) -> &Rc<RefCell<Env>> {
+        &self
+            .frames
+            .last()
+            .expect("the global frame is never popped")
+            .env
+    }
+
+    fn env_mut(&mut self) -> &mut Rc<RefCell<Env>> {
+        &mut self
+            .frames
+            .last_mut()
+            .expect("the global frame is never popped")
+            .env
+    }
+
+    /// The functions that were being called when the last error was raised, innermost first.
+    pub fn stack_trace(&self) -> &[String] {
+        &self.stack_trace
+    }
+
+    pub fn profiler(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_mut()
+    }
+
+    /// Redirects what scripts print (e.g. through `puts`), which goes to stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        *self.output.0.borrow_mut() = output;
+    }
+
+    /// Replaces where `input` reads lines from, stdin by default.
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        *self.input.0.borrow_mut() = Some(input);
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn EvalObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Makes `operator` call `func` for operands of the given types, see `Operators::register`.
+    pub fn register_infix(
+        &mut self,
+        operator: Infix,
+        left: &'static str,
+        right: &'static str,
+        func: InfixFn,
+    ) {
+        Rc::make_mut(&mut self.operators).register(operator, left, right, func);
+    }
+
+    /// Makes `name` call `func` with `arity` arguments, for applications embedding the
+    /// interpreter to add functions of their own. Monkey code can still bind `name` itself, and
+    /// it replaces a builtin of the same name.
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&mut dyn Runtime, Vec<Object>) -> Result<Object> + 'static,
+    ) {
+        let name = Symbol::intern(name);
+        let builtin = HostBuiltin {
+            name,
+            arity,
+            func: Box::new(func),
+        };
+        self.host_builtins
+            .borrow_mut()
+            .insert(name, Object::Callable(Rc::new(Callable::Host(builtin))));
+    }
+
+    /// Handle other threads and signal handlers can use to abort the running evaluation.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.interrupted.clone())
+    }
+
+    /// Lint warnings (e.g. a `let` shadowing an outer binding of a different type) emitted since
+    /// the last call.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// How long each top-level statement evaluated by the last `eval` took, in order, when
+    /// `Options::time` is set. The statement that failed, if one did, is the last.
+    pub fn take_timings(&mut self) -> Vec<Duration> {
+        std::mem::take(&mut self.timings)
+    }
+
+    /// The bindings visible from the current scope, innermost scope first.
+    pub fn scopes(&self) -> Vec<Vec<(Symbol, Object)>> {
+        self.env().borrow().scopes()
+    }
+
+    /// The value `name` evaluates to in the current scope, which may be a builtin.
+    pub fn lookup(&self, name: &str) -> Result<Object> {
+        self.lookup_symbol(Symbol::intern(name))
+    }
+
+    fn lookup_symbol(&self, id: Symbol) -> Result<Object> {
+        if let Some(obj) = self.env().borrow().get(id) {
+            return Ok(obj);
+        }
+
+        if let Some(builtin) = self.host_builtins.borrow().get(&id) {
+            return Ok(builtin.clone());
+        }
+
+        if let Some(builtin) = builtins::lookup(id.as_str()) {
+            return Ok(Object::Builtin(builtin));
+        }
+
+        Err(self.not_found(id.as_str()))
+    }
+
+    /// Files of the modules imported so far, in no particular order.
+    pub fn module_files(&self) -> Vec<PathBuf> {
+        self.modules
+            .borrow()
+            .keys()
+            .filter_map(|location| match location {
+                Location::File(path) => Some(path.clone()),
+                Location::Stdlib(_) => None,
+            })
+            .collect()
+    }
+
+    /// Counts the scopes, functions and containers alive and reachable, see `HeapStats`. Values
+    /// only held by suspended generators and tasks are live but not reachable.
+    pub fn heap_stats(&self) -> HeapStats {
+        let modules = self.modules.borrow();
+        HeapStats::collect(
+            self.frames
+                .iter()
+                .map(|frame| &frame.env)
+                .chain(modules.values().flatten()),
+        )
+    }
+
+    /// Forgets the modules imported so far, so importing them again reads and runs them anew.
+    pub fn forget_modules(&mut self) {
+        self.modules.borrow_mut().clear();
+    }
+
+    /// Drops back to the global scope, e.g. after a panic interrupted a call
+    /// before it could restore the caller's scope.
+    pub fn reset_scope(&mut self) {
+        self.frames.truncate(1);
+        loop {
+            let outer = self.env().borrow().outer.clone();
+            match outer {
+                Some(outer) => *self.env_mut() = outer,
+                None => break,
+            }
         }
     }
 
     pub fn eval(&mut self, program: Program) -> Result<Object> {
         let mut result = Object::Null;
+        self.stack_trace.clear();
+        self.timings.clear();
 
         for statement in program {
-            match self.eval_statement(statement?) {
-                Err(error) => return Err(error),
+            let start = self.options.time.then(Instant::now);
+            let statement = self.eval_statement(statement?);
+            if let Some(start) = start {
+                self.timings.push(start.elapsed());
+            }
+            match statement {
+                Err(error) => {
+                    for observer in &mut self.observers {
+                        observer.on_error(&error);
+                    }
+                    return Err(error);
+                }
                 Ok(Object::ReturnValue(value)) => return Ok(*value),
                 Ok(obj) => result = obj,
             }
@@ -43,7 +500,89 @@ impl Eval {
         Ok(result)
     }
 
+    /// Evaluates a lone expression in the current scope, like one parsed with
+    /// `Parser::parse_expression_str`.
+    pub fn eval_expression(&mut self, expression: Expression) -> Result<Object> {
+        self.stack_trace.clear();
+
+        let result = self.eval_expr(expression);
+        if let Err(error) = &result {
+            for observer in &mut self.observers {
+                observer.on_error(error);
+            }
+        }
+        result
+    }
+
+    /// Calls the function `name` evaluates to, e.g. a callback a script defined, for
+    /// applications embedding the interpreter to run Monkey code when something happens.
+    pub fn call(&mut self, name: &str, args: Vec<Object>) -> Result<Object> {
+        self.stack_trace.clear();
+
+        let result = self
+            .lookup(name)
+            .and_then(|function| self.apply_named_function(name, function, args));
+        if let Err(error) = &result {
+            for observer in &mut self.observers {
+                observer.on_error(error);
+            }
+        }
+        result
+    }
+
+    /// Prepares `program` to run in the current scope a few steps at a time with `run_steps`,
+    /// replacing the program loaded before if it hasn't finished.
+    pub fn load(&mut self, program: Program) -> Result<()> {
+        let frame = self
+            .frames
+            .last()
+            .expect("the global frame is never popped");
+        let mut eval = self.fork(Frame {
+            name: frame.name.clone(),
+            env: frame.env.clone(),
+        });
+
+        self.steps = Some(Steps::new(move |stepper, budget| {
+            eval.stepper = Some(stepper);
+            eval.budget = budget;
+            eval.eval(program)
+        })?);
+        Ok(())
+    }
+
+    /// Runs the program loaded with `load` for up to `steps` more steps, so an application can
+    /// interleave it with its own work, e.g. a frame of a game, without threads. A step is the
+    /// evaluation of one expression; generators and tasks run to their next `yield` or `await`
+    /// within one.
+    pub fn run_steps(&mut self, steps: usize) -> Result<StepResult> {
+        let Some(running) = self.steps.as_mut() else {
+            bail!("No program loaded to run!");
+        };
+        if steps == 0 {
+            return Ok(StepResult::Pending);
+        }
+
+        match running.resume(steps) {
+            None => Ok(StepResult::Pending),
+            Some(result) => {
+                self.steps = None;
+                result.map(StepResult::Done)
+            }
+        }
+    }
+
+    /// Evaluates `block` in a fresh scope, so its `let` bindings don't leak out of the braces.
     fn eval_block_statement(&mut self, block: BlockStatement) -> Result<Object> {
+        let scope = Env::new_enclosed(self.env().clone());
+        let outer = std::mem::replace(self.env_mut(), Rc::new(RefCell::new(scope)));
+
+        let result = self.eval_statements(block);
+
+        *self.env_mut() = outer;
+        result
+    }
+
+    fn eval_statements(&mut self, block: BlockStatement) -> Result<Object> {
         let mut result = Object::Null;
 
         for statement in block {
@@ -57,39 +596,391 @@ impl Eval {
     }
 
     fn eval_statement(&mut self, statement: Statement) -> Result<Object> {
+        if self.interrupted.swap(false, Ordering::Relaxed) {
+            bail!("Interrupted!");
+        }
+
+        for observer in &mut self.observers {
+            observer.on_statement(&statement);
+        }
+
         Ok(match statement {
-            Statement::Let(id, value) => {
+            Statement::Let(pattern, value) => {
                 let value = self.eval_expr(value)?;
-                self.env.borrow_mut().assign(id.0, value.clone());
+                self.bind_pattern(pattern, value)?;
                 Object::Empty
             }
             Statement::Return(ret_value) => {
                 Object::ReturnValue(Box::new(self.eval_expr(ret_value)?))
             }
+            Statement::Yield(value) => {
+                let value = self.eval_expr(value)?;
+                let Some(yielder) = self.yielder else {
+                    bail!("Cannot yield outside of a generator!");
+                };
+
+                // SAFETY: the yielder lives on the generator's stack, next to this evaluator.
+                unsafe { &*yielder }.suspend(value);
+                Object::Empty
+            }
+            Statement::Import(name, imports) => {
+                let source = modules::resolve(&name, &self.options.module_paths)?;
+                let module = Module {
+                    name,
+                    scope: self.load_module(source)?,
+                };
+                match imports {
+                    Imports::All => {
+                        let bindings = module.scope.borrow().scopes().into_iter().flatten();
+                        for (id, value) in bindings {
+                            self.define(id, value)?;
+                        }
+                    }
+                    Imports::Module(alias) => {
+                        self.define(alias.0, Object::Module(Rc::new(module)))?
+                    }
+                    Imports::Names(names) => {
+                        for name in names {
+                            let value = module.get(name.0)?;
+                            self.define(name.0, value)?;
+                        }
+                    }
+                }
+                Object::Empty
+            }
             Statement::Expression(expr) => self.eval_expr(expr)?,
+            Statement::Empty => Object::Null,
         })
     }
 
+    /// Evaluates `module` in a global scope of its own, unless it was already imported.
+    fn load_module(&mut self, module: Source) -> Result<Rc<RefCell<Env>>> {
+        match self.modules.borrow().get(&module.location) {
+            Some(Some(scope)) => return Ok(scope.clone()),
+            Some(None) => bail!("Circular import of {}!", module.location),
+            None => {}
+        }
+        let in_module = |error: anyhow::Error| anyhow!("In module {}: {}", module.location, error);
+        let program = Parser::new(Lexer::new(&module.source))
+            .parse_program()
+            .map_err(in_module)?
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .map_err(in_module)?;
+
+        self.modules
+            .borrow_mut()
+            .insert(module.location.clone(), None);
+        let scope = Rc::new(RefCell::new(Env::new()));
+        self.frames.push(Frame {
+            name: format!("<module {}>", module.location),
+            env: scope.clone(),
+        });
+
+        let result = program
+            .into_iter()
+            .try_for_each(|statement| self.eval_statement(statement).map(drop));
+
+        self.frames.pop();
+        match result {
+            Ok(()) => {
+                self.modules
+                    .borrow_mut()
+                    .insert(module.location, Some(scope.clone()));
+                Ok(scope)
+            }
+            Err(error) => {
+                self.modules.borrow_mut().remove(&module.location);
+                Err(error)
+            }
+        }
+    }
+
+    /// Declares `id` in the current scope, warning if it shadows an outer binding of another type.
+    fn define(&mut self, id: Symbol, value: Object) -> Result<()> {
+        let len = self.env().borrow().len();
+        if !self.env().borrow().contains(id) {
+            self.options.limits.check_env(len + 1)?;
+        }
+
+        let outer = self.env().borrow().outer.clone();
+        if let Some(shadowed) = outer.and_then(|outer| outer.borrow().get(id)) {
+            if shadowed.get_type() != value.get_type() {
+                self.warnings.push(format!(
+                    "let {} shadows an outer {} with a {}",
+                    id,
+                    shadowed.get_type(),
+                    value.get_type()
+                ));
+            }
+        }
+
+        self.env().borrow_mut().define(id, value);
+        Ok(())
+    }
+
+    fn bind_pattern(&mut self, pattern: Pattern, value: Object) -> Result<()> {
+        let value_type = value.get_type().to_string();
+
+        match (pattern, value) {
+            (Pattern::Identifier(id), value) => self.define(id.0, value)?,
+            (Pattern::Array(patterns), Object::Array(elements))
+            | (Pattern::Tuple(patterns), Object::Tuple(elements)) => {
+                if patterns.len() != elements.len() {
+                    bail!(
+                        "Cannot destructure {} of length {} into {} names!",
+                        value_type,
+                        elements.len(),
+                        patterns.len()
+                    );
+                }
+
+                for (pattern, element) in patterns.into_iter().zip(elements.iter().cloned()) {
+                    self.bind_pattern(pattern, element)?;
+                }
+            }
+            (Pattern::Hash(names), Object::Hash(hash)) => {
+                for id in names {
+                    match hash.get(&Object::String(id.0.as_str().into())) {
+                        Some(value) => self.define(id.0, value.clone())?,
+                        None => bail!("Key {} not found while destructuring hash!", id.0),
+                    }
+                }
+            }
+            (Pattern::Array(_), value) => {
+                bail!("Cannot destructure {} as array!", value.get_type())
+            }
+            (Pattern::Tuple(_), value) => {
+                bail!("Cannot destructure {} as tuple!", value.get_type())
+            }
+            (Pattern::Hash(_), value) => bail!("Cannot destructure {} as hash!", value.get_type()),
+        }
+
+        Ok(())
+    }
+
     fn eval_expr(&mut self, expression: Expression) -> Result<Object> {
+        if let Some(stepper) = self.stepper {
+            if self.budget == 0 {
+                // SAFETY: the yielder lives on the program's stack, next to this evaluator.
+                self.budget = unsafe { &*stepper }.suspend(());
+            }
+            self.budget -= 1;
+        }
+
         match expression {
             Expression::Literal(literal) => self.eval_literal(literal),
             Expression::Prefix(operator, right) => self.eval_prefix(operator, *right),
             Expression::Infix(operator, left, right) => self.eval_infix(operator, *left, *right),
             Expression::If(if_expr) => self.eval_if(if_expr),
             Expression::Identifier(id) => self.eval_identifier(id),
-            Expression::Function { params, body } => {
-                Ok(Object::Function(params, body, self.env.clone()))
+            Expression::Function {
+                params,
+                body,
+                generator,
+            } => Ok(Object::Function(Rc::new(Function {
+                params,
+                body,
+                generator,
+                env: self.env().clone(),
+                live: Live::function(),
+            }))),
+            Expression::Call {
+                function,
+                args,
+                named,
+            } => self.eval_call(*function, args, named),
+            Expression::Range {
+                start,
+                end,
+                inclusive,
+            } => self.eval_range(*start, *end, inclusive),
+            Expression::Await(promise) => self.eval_await(*promise),
+            Expression::Spread(_) => bail!("Spread is only allowed in calls and array literals!"),
+            Expression::Array(elements) => {
+                Ok(Object::Array(self.eval_expressions(elements)?.into()))
             }
-            Expression::Call { function, args } => self.eval_call(*function, args),
+            Expression::Tuple(elements) => {
+                Ok(Object::Tuple(self.eval_expressions(elements)?.into()))
+            }
+            Expression::Hash(pairs) => self.eval_hash(pairs),
+            Expression::Index { left, index } => self.eval_index(*left, *index),
+            Expression::Member { object, attribute } => match self.eval_expr(*object)? {
+                Object::Module(module) => module.get(attribute.0),
+                other => bail!("Attribute access is not defined for {}!", other.get_type()),
+            },
+            Expression::Assign { target, value } => self.eval_assign(target, *value),
         }
     }
 
-    fn eval_identifier(&mut self, id: Identifier) -> Result<Object> {
-        if let Some(obj) = self.env.borrow().get(&id.0) {
-            return Ok(obj);
+    /// Waits for a promise to settle. A task suspends meanwhile, letting its event loop run other
+    /// code, anything else blocks. Other values are ready already.
+    fn eval_await(&mut self, value: Expression) -> Result<Object> {
+        let promise = match self.eval_expr(value)? {
+            Object::Promise(promise) => promise,
+            value => return Ok(value),
+        };
+
+        loop {
+            if let Some(result) = promise.poll() {
+                return result;
+            }
+            let Some(awaiter) = self.awaiter else {
+                return promise.wait();
+            };
+
+            // SAFETY: the awaiter lives on the task's stack, next to this evaluator.
+            unsafe { &*awaiter }.suspend(promise.clone());
+        }
+    }
+
+    fn eval_assign(&mut self, target: Identifier, value: Expression) -> Result<Object> {
+        let value = self.eval_expr(value)?;
+
+        let declare_global = !self.options.strict;
+        if declare_global && self.env().borrow().get(target.0).is_none() {
+            let len = self.env().borrow().global_len();
+            self.options.limits.check_env(len + 1)?;
+        }
+        if !self
+            .env()
+            .borrow_mut()
+            .assign(target.0, value.clone(), declare_global)
+        {
+            return Err(self.not_found(target.0.as_str()));
+        }
+
+        Ok(value)
+    }
+
+    /// Evaluates array elements or call arguments, flattening spread arrays into the list.
+    fn eval_expressions(&mut self, expressions: Vec<Expression>) -> Result<Vec<Object>> {
+        let mut values = vec![];
+
+        for expression in expressions {
+            match expression {
+                Expression::Spread(array) => {
+                    let array = self.eval_expr(*array)?;
+                    let Some(elements) = array.iter() else {
+                        bail!("Cannot spread {}!", array.get_type());
+                    };
+                    let len = values.len() + elements.size_hint().0;
+                    self.options.limits.check_array(len)?;
+                    values.extend(elements);
+                }
+                expression => values.push(self.eval_expr(expression)?),
+            }
+        }
+
+        self.options.limits.check_array(values.len())?;
+        Ok(values)
+    }
+
+    fn eval_hash(&mut self, pairs: Vec<(Expression, Expression)>) -> Result<Object> {
+        let mut hash = HashMap::new();
+
+        for (key, value) in pairs {
+            let key = self.eval_expr(key)?;
+            key.hashable()?;
+            let value = self.eval_expr(value)?;
+            hash.insert(key, value);
+            self.options.limits.check_hash(hash.len())?;
+        }
+
+        Ok(Object::Hash(hash.into()))
+    }
+
+    fn eval_range(
+        &mut self,
+        start: Expression,
+        end: Expression,
+        inclusive: bool,
+    ) -> Result<Object> {
+        let start = self.eval_expr(start)?;
+        let end = self.eval_expr(end)?;
+
+        match (start, end) {
+            (Object::Int(start), Object::Int(end)) if inclusive => match end.checked_add(1) {
+                Some(end) => Ok(Object::Range(start, end)),
+                None => bail!("Range end {} is too large!", end),
+            },
+            (Object::Int(start), Object::Int(end)) => Ok(Object::Range(start, end)),
+            (start, end) => bail!(
+                "Range bounds must be ints, not {} & {}!",
+                start.get_type(),
+                end.get_type()
+            ),
+        }
+    }
+
+    fn eval_index(&mut self, left: Expression, index: Expression) -> Result<Object> {
+        let left = self.eval_expr(left)?;
+        let index = self.eval_expr(index)?;
+
+        match (left, index) {
+            (Object::Array(elements) | Object::Tuple(elements), Object::Int(index)) => {
+                Ok(usize::try_from(index)
+                    .ok()
+                    .and_then(|index| elements.get(index).cloned())
+                    .unwrap_or(Object::Null))
+            }
+            (Object::Range(start, end), Object::Int(index)) => Ok(start
+                .checked_add(index)
+                .filter(|value| index >= 0 && *value < end)
+                .map(Object::Int)
+                .unwrap_or(Object::Null)),
+            (Object::Bytes(bytes), Object::Int(index)) => Ok(usize::try_from(index)
+                .ok()
+                .and_then(|index| bytes.get(index))
+                .map_or(Object::Null, |&byte| Object::Int(byte.into()))),
+            // Slicing clamps the range to the data, like indexing past the end gives null.
+            (Object::Bytes(bytes), Object::Range(start, end)) => {
+                let clamp = |bound: i64| bound.clamp(0, bytes.len() as i64) as usize;
+                let (start, end) = (clamp(start), clamp(end));
+                Ok(Object::Bytes(bytes[start..end.max(start)].into()))
+            }
+            (Object::Hash(hash), index) => self.eval_hash_index(&hash, index),
+            (left, index) => bail!(
+                "Index operator not supported for {}[{}]!",
+                left.get_type(),
+                index.get_type()
+            ),
+        }
+    }
+
+    fn eval_hash_index(&self, hash: &Pairs, key: Object) -> Result<Object> {
+        key.hashable()?;
+
+        match hash.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None if self.options.strict_index => bail!("Key {} not found in hash!", key),
+            None => Ok(Object::Null),
         }
+    }
+
+    fn eval_identifier(&mut self, id: Identifier) -> Result<Object> {
+        self.lookup_symbol(id.0)
+    }
+
+    /// Suggests the visible name closest to `id`, if it is close enough to be a likely typo:
+    /// at most one edit for every three chars of `id`.
+    fn not_found(&self, id: &str) -> anyhow::Error {
+        let threshold = id.chars().count() / 3;
+        let names = self.env().borrow().names();
+        let host_builtins = self.host_builtins.borrow();
+        let suggestion = names
+            .iter()
+            .chain(host_builtins.keys())
+            .map(|name| name.as_str())
+            .chain(builtins::names())
+            .map(|name| (edit_distance(id, name), name))
+            .filter(|&(distance, _)| distance <= threshold)
+            .min();
 
-        bail!("Identifier {} not found!", id.0);
+        match suggestion {
+            Some((_, name)) => anyhow!("Identifier {} not found! Did you mean `{}`?", id, name),
+            None => anyhow!("Identifier {} not found!", id),
+        }
     }
 
     fn eval_if(&mut self, if_expr: IfExpression) -> Result<Object> {
@@ -105,8 +996,11 @@ impl Eval {
     fn eval_literal(&self, literal: Literal) -> Result<Object> {
         Ok(match literal {
             Literal::Int(num) => Object::Int(num),
+            Literal::Float(num) => Object::Float(num),
             Literal::Bool(bool) => Object::Bool(bool),
-            Literal::String(s) => Object::String(s),
+            Literal::String(s) => Object::String(s.into()),
+            Literal::Bytes(bytes) => Object::Bytes(bytes.into()),
+            Literal::Null => Object::Null,
         })
     }
 
@@ -119,61 +1013,32 @@ impl Eval {
         let left = self.eval_expr(left)?;
         let right = self.eval_expr(right)?;
 
-        match (&left, &right) {
-            (Object::Int(l), Object::Int(r)) => {
-                return Ok(self.eval_integer_infix(operator, *l, *r))
-            }
+        if let Infix::StrictEqual | Infix::StrictNotEqual = operator {
+            return self.eval_strict_equality_infix(operator, left, right);
+        }
 
-            (Object::Bool(_), Object::Bool(_)) => {
-                return self.eval_bool_infix(operator, left, right)
-            }
-            (Object::String(ref l), Object::String(ref r)) => {
-                return self.eval_string_infix(operator, l, r)
-            }
-            _ => {}
-        };
-        bail!(format!(
-            "Infix operator {} not found for the operands: {} & {}!",
-            operator,
-            left.get_type(),
-            right.get_type()
-        ));
+        self.operators.eval(self, operator, &left, &right)
     }
 
-    fn eval_bool_infix(&self, operator: Infix, left: Object, right: Object) -> Result<Object> {
-        Ok(match operator {
-            Infix::Equal => Object::Bool(left == right),
-            Infix::NotEqual => Object::Bool(left != right),
-            _ => bail!(format!(
-                "Infix operator {} not found for the operands: {} & {}!",
-                operator,
-                left.get_type(),
-                right.get_type()
-            )),
-        })
-    }
-
-    fn eval_string_infix(&self, operator: Infix, left: &String, right: &String) -> Result<Object> {
-        Ok(match operator {
-            Infix::Plus => Object::String(String::from(left) + right),
-            _ => bail!(format!(
-                "Infix operator {} not found for the operands: string & string!",
+    /// `===` and `!==` compare like `==` and `!=`, but only values of the same type.
+    fn eval_strict_equality_infix(
+        &self,
+        operator: Infix,
+        left: Object,
+        right: Object,
+    ) -> Result<Object> {
+        if left.get_type() != right.get_type() {
+            bail!(
+                "Operator {} cannot compare {} & {}!",
                 operator,
-            )),
-        })
-    }
-
-    fn eval_integer_infix(&self, operator: Infix, left: i64, right: i64) -> Object {
-        match operator {
-            Infix::Plus => Object::Int(left + right),
-            Infix::Minus => Object::Int(left - right),
-            Infix::Divide => Object::Int(left / right),
-            Infix::Product => Object::Int(left * right),
-            Infix::Equal => Object::Bool(left == right),
-            Infix::GreaterThan => Object::Bool(left > right),
-            Infix::LessThan => Object::Bool(left < right),
-            Infix::NotEqual => Object::Bool(left != right),
+                left.get_type(),
+                right.get_type()
+            );
         }
+
+        Ok(Object::Bool(
+            left.equals(&right) == (operator == Infix::StrictEqual),
+        ))
     }
 
     fn eval_prefix(&mut self, operator: Prefix, right: Expression) -> Result<Object> {
@@ -188,14 +1053,18 @@ impl Eval {
 
     fn eval_prefix_plus(&self, obj: Object) -> Result<Object> {
         Ok(match obj {
-            Object::Int(_) => obj,
+            Object::Int(_) | Object::Float(_) => obj,
             _ => bail!("Operator prefix + is not defined for {}!", obj.get_type()),
         })
     }
 
     fn eval_prefix_minus(&self, obj: Object) -> Result<Object> {
         Ok(match obj {
-            Object::Int(num) => Object::Int(-num),
+            Object::Int(num) => Object::Int(
+                num.checked_neg()
+                    .ok_or_else(|| anyhow!("Integer overflow in -({})!", num))?,
+            ),
+            Object::Float(num) => Object::Float(-num),
             _ => bail!("Operator prefix - is not defined for {}!", obj.get_type()),
         })
     }
@@ -203,6 +1072,7 @@ impl Eval {
     fn eval_bang(&self, obj: Object) -> Result<Object> {
         Ok(match obj {
             Object::Bool(value) => Object::Bool(!value),
+            Object::Null => Object::Bool(true),
             _ => bail!("Operator prefix ! is not defined for {}!", obj.get_type()),
         })
     }
@@ -211,19 +1081,159 @@ impl Eval {
         !matches!(condition, Object::Null | Object::Bool(false))
     }
 
-    fn eval_call(&mut self, function: Expression, args: Vec<Expression>) -> Result<Object> {
-        let args = args
-            .iter()
-            .map(|x| self.eval_expr(x.clone()))
-            .collect::<Vec<_>>();
+    fn eval_call(
+        &mut self,
+        function: Expression,
+        args: Vec<Expression>,
+        named: Vec<(Identifier, Expression)>,
+    ) -> Result<Object> {
+        let name = match &function {
+            Expression::Identifier(id) => id.0.to_string(),
+            _ => String::from("<anonymous>"),
+        };
 
+        let mut args = self.eval_expressions(args)?;
+        let named = named
+            .into_iter()
+            .map(|(id, value)| Ok((id, self.eval_expr(value)?)))
+            .collect::<Result<Vec<_>>>()?;
         let function = self.eval_expr(function)?;
 
-        let (params, body, env) = match &function {
-            Object::Function(p, b, e) => (p, b, e),
-            _ => bail!("{} is not a valid function!", function),
+        if !named.is_empty() {
+            args = Self::bind_named_arguments(&function, args, named)?;
+        }
+
+        let start = self.profiler.as_mut().map(|profiler| profiler.enter(&name));
+        let result = self.apply_named_function(&name, function, args);
+        if let (Some(profiler), Some(start)) = (self.profiler.as_mut(), start) {
+            profiler.exit(&name, start);
+        }
+
+        result
+    }
+
+    /// Puts named arguments in the position of the parameter with the same name, after the
+    /// positional ones. Builtins name their parameters in their signature.
+    fn bind_named_arguments(
+        function: &Object,
+        args: Vec<Object>,
+        named: Vec<(Identifier, Object)>,
+    ) -> Result<Vec<Object>> {
+        let params = match function {
+            Object::Function(function) => function.params.iter().map(|id| id.0).collect(),
+            Object::Builtin(builtin) if !builtin.signature.variadic => builtin
+                .signature
+                .params
+                .iter()
+                .map(|param| Symbol::intern(param.name))
+                .collect::<Vec<_>>(),
+            _ => bail!(
+                "Named arguments are not supported for {}!",
+                function.get_type()
+            ),
+        };
+
+        if args.len() > params.len() {
+            bail!(
+                "Wrong number of arguments. Expected: {}. Given: {}",
+                params.len(),
+                args.len() + named.len()
+            );
+        }
+
+        let mut slots = vec![None; params.len()];
+        for (slot, arg) in slots.iter_mut().zip(args) {
+            *slot = Some(arg);
+        }
+
+        for (id, value) in named {
+            let Some(position) = params.iter().position(|param| *param == id.0) else {
+                bail!("Function has no parameter named {}!", id.0);
+            };
+            if slots[position].is_some() {
+                bail!("Argument {} given more than once!", id.0);
+            }
+            slots[position] = Some(value);
+        }
+
+        slots
+            .into_iter()
+            .zip(params)
+            .map(|(slot, param)| slot.ok_or_else(|| anyhow!("Missing argument {}!", param)))
+            .collect()
+    }
+
+    fn apply_function(&mut self, function: Object, args: Vec<Object>) -> Result<Object> {
+        self.apply_named_function("<anonymous>", function, args)
+    }
+
+    fn apply_named_function(
+        &mut self,
+        name: &str,
+        function: Object,
+        args: Vec<Object>,
+    ) -> Result<Object> {
+        if !function.is_callable() {
+            bail!("{} is not a valid function!", function);
+        }
+
+        for observer in &mut self.observers {
+            observer.on_call(&function, &args);
+        }
+
+        let value = match &function {
+            Object::Function(inner) => self.call_function(name, inner.clone(), args)?,
+            Object::Builtin(builtin) => builtin.call(self, args)?,
+            Object::Callable(callable) => match callable.as_ref() {
+                Callable::Composed(outer, inner) => {
+                    let value = self.apply_function(inner.clone(), args)?;
+                    self.apply_function(outer.clone(), vec![value])?
+                }
+                Callable::Partial(function, bound) => {
+                    let args = bound.iter().cloned().chain(args).collect();
+                    self.apply_function(function.clone(), args)?
+                }
+                Callable::Memoized(function, cache) => {
+                    let hashable = args.iter().all(|arg| arg.hashable().is_ok());
+                    let cached = hashable
+                        .then(|| cache.borrow().get(&args).cloned())
+                        .flatten();
+                    match cached {
+                        Some(value) => value,
+                        None if !hashable => self.apply_function(function.clone(), args)?,
+                        None => {
+                            let value = self.apply_function(function.clone(), args.clone())?;
+                            cache.borrow_mut().insert(args, value.clone());
+                            value
+                        }
+                    }
+                }
+                Callable::Host(builtin) => builtin.call(self, args)?,
+            },
+            _ => unreachable!(),
         };
 
+        for observer in &mut self.observers {
+            observer.on_return(&function, &value);
+        }
+
+        Ok(value)
+    }
+
+    fn call_function(
+        &mut self,
+        name: &str,
+        function: Rc<Function>,
+        args: Vec<Object>,
+    ) -> Result<Object> {
+        let Function {
+            params,
+            body,
+            env,
+            generator,
+            ..
+        } = function.as_ref();
+
         if params.len() != args.len() {
             bail!(
                 "Wrong number of arguments. Expected: {}. Given: {}",
@@ -232,44 +1242,215 @@ impl Eval {
             );
         }
 
-        let current_env = self.env.clone();
+        if self.frames.len() > self.options.max_call_depth {
+            bail!(
+                "Stack overflow: more than {} nested calls!",
+                self.options.max_call_depth
+            );
+        }
+
+        let mut scope = Env::new_enclosed(env.clone());
+        for (id, value) in params.iter().zip(args) {
+            scope.define(id.0, value);
+        }
+
+        let frame = Frame {
+            name: name.to_string(),
+            env: Rc::new(RefCell::new(scope)),
+        };
+        if *generator {
+            return self.start_generator(frame, body.clone());
+        }
+
+        self.frames.push(frame);
+        let obj = self.eval_statements(body.clone());
+
+        // The innermost failing call records the trace, while every frame is still on the stack.
+        if obj.is_err() && self.stack_trace.is_empty() {
+            self.stack_trace = self.frames[1..]
+                .iter()
+                .rev()
+                .map(|frame| frame.name.clone())
+                .collect();
+        }
+        self.frames.pop();
+
+        match obj? {
+            Object::ReturnValue(value) => Ok(*value),
+            obj => Ok(obj),
+        }
+    }
+}
+
+impl Eval {
+    /// An evaluator running in `frame` with the same output, interrupt flag and options, apart
+    /// from the lower maximum call depth that fits in a generator's or task's stack.
+    fn fork(&self, frame: Frame) -> Eval {
+        Eval {
+            frames: vec![frame],
+            stack_trace: vec![],
+            options: Options {
+                max_call_depth: self.options.max_call_depth.min(GENERATOR_MAX_CALL_DEPTH),
+                ..self.options.clone()
+            },
+            profiler: None,
+            observers: vec![],
+            output: self.output.clone(),
+            input: self.input.clone(),
+            interrupted: self.interrupted.clone(),
+            warnings: vec![],
+            timings: vec![],
+            yielder: None,
+            awaiter: None,
+            stepper: None,
+            budget: 0,
+            steps: None,
+            random: self.random.clone(),
+            modules: self.modules.clone(),
+            operators: self.operators.clone(),
+            host_builtins: self.host_builtins.clone(),
+        }
+    }
+
+    /// Wraps the body of a generator function, with its arguments already bound in `frame`, in
+    /// a generator that evaluates it on demand with an evaluator of its own.
+    fn start_generator(&self, frame: Frame, body: BlockStatement) -> Result<Object> {
+        let mut eval = self.fork(frame);
+
+        Ok(Object::Generator(Rc::new(Generator::new(
+            move |yielder| {
+                eval.yielder = Some(yielder);
+                eval.eval_statements(body)
+            },
+        )?)))
+    }
+
+    /// Wraps `program` in a task that evaluates it in the current scope with an evaluator of its
+    /// own, see `EventLoop`.
+    fn start_task(&self, program: Program) -> Result<Task> {
+        let frame = self
+            .frames
+            .last()
+            .expect("the global frame is never popped");
+        let mut eval = self.fork(Frame {
+            name: frame.name.clone(),
+            env: frame.env.clone(),
+        });
+
+        Task::new(move |awaiter| {
+            eval.awaiter = Some(awaiter);
+            eval.eval(program)
+        })
+    }
+}
+
+impl Runtime for Eval {
+    fn call(&mut self, function: Object, args: Vec<Object>) -> Result<Object> {
+        self.apply_function(function, args)
+    }
 
-        let mut scoped_env = Env::new();
-        scoped_env.outer = Some(env.clone());
+    /// Unlike `Eval::eval`, leaves the stack trace and error observers to the evaluation the
+    /// builtin was called from, which the error propagates to.
+    fn eval_source(&mut self, source: &str) -> Result<Object> {
+        let program = Parser::new(Lexer::new(source)).parse_program()?;
 
-        for (id, value) in params.iter().zip(args.into_iter()) {
-            scoped_env.assign(id.0.clone(), value?);
+        let mut result = Object::Null;
+        for statement in program {
+            match self.eval_statement(statement?)? {
+                Object::ReturnValue(value) => return Ok(*value),
+                value => result = value,
+            }
         }
+        Ok(result)
+    }
+
+    fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    fn read_line(&mut self, line: &mut String) -> std::io::Result<usize> {
+        self.input.read_line(line)
+    }
+
+    fn limits(&self) -> Limits {
+        self.options.limits
+    }
+
+    fn capabilities(&self) -> &Capabilities {
+        &self.options.capabilities
+    }
+
+    fn heap_stats(&self) -> Result<HeapStats> {
+        Ok(self.heap_stats())
+    }
+
+    fn random(&self) -> &Random {
+        &self.random
+    }
+}
 
-        self.env = Rc::new(RefCell::new(scoped_env));
-        let obj = self.eval_block_statement(body.clone());
+/// `left / right` rounded towards negative infinity, or None if it overflows.
+fn floor_div(left: i64, right: i64) -> Option<i64> {
+    let quotient = left.checked_div(right)?;
+    let rounded_down = left % right != 0 && (left < 0) != (right < 0);
+    Some(if rounded_down { quotient - 1 } else { quotient })
+}
 
-        self.env = current_env;
+/// Levenshtein distance: the fewest char insertions, deletions and substitutions turning
+/// `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
 
-        obj
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
     }
+
+    previous[b.len()]
 }
 
 #[cfg(test)]
 mod test {
-    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+    use std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        path::PathBuf,
+        rc::Rc,
+    };
+
+    use super::observer::EvalObserver;
 
     use crate::{
         ast::{Expression, Identifier, Infix, Literal, Statement},
-        eval::Object,
+        eval::{object::Function, Object},
         lexer::Lexer,
         parser::Parser,
     };
 
-    use super::{env::Env, Eval};
+    use super::{
+        capabilities::Capabilities,
+        env::Env,
+        stats::{HeapStats, Live},
+        steps::StepResult,
+        Division, Eval, Limits, Options,
+    };
 
     use anyhow::{anyhow, Result};
 
     fn test(tests: HashMap<&str, Result<Object>>) {
+        test_with_options(tests, Options::default());
+    }
+
+    fn test_with_options(tests: HashMap<&str, Result<Object>>, options: Options) {
         for (input, output) in tests {
             let lexer = Lexer::new(input);
             let mut parser = Parser::new(lexer);
-            let mut eval = Eval::new();
+            let mut eval = Eval::with_options(options.clone());
 
             let result = eval.eval(parser.parse_program().unwrap());
 
@@ -336,6 +1517,285 @@ mod test {
         test(tests);
     }
 
+    #[test]
+    fn string_repetition() {
+        let tests = HashMap::from([
+            (r#""ab" * 3"#, Ok(Object::String("ababab".into()))),
+            (r#"2 * "ab""#, Ok(Object::String("abab".into()))),
+            (r#""ab" * 0"#, Ok(Object::String("".into()))),
+            (r#"repeat("-", 4)"#, Ok(Object::String("----".into()))),
+            (
+                r#""ab" * -1"#,
+                Err(anyhow!("Cannot repeat a string -1 times!")),
+            ),
+            (
+                r#""a" * 3000000000000"#,
+                Err(anyhow!(
+                    "Repeating a string 3000000000000 times makes it too long!"
+                )),
+            ),
+            (
+                r#""ab" * 9223372036854775807"#,
+                Err(anyhow!(
+                    "Repeating a string 9223372036854775807 times makes it too long!"
+                )),
+            ),
+            (
+                r#""ab" + 3"#,
+                Err(anyhow!(
+                    "Infix operator + not found for the operands: string & int!"
+                )),
+            ),
+            (
+                r#"repeat(3, "ab")"#,
+                Err(anyhow!("Builtin repeat is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn concatenation() {
+        let array =
+            |elements: &[i64]| Object::Array(elements.iter().map(|&n| Object::Int(n)).collect());
+        let hash = |pairs: &[(&str, i64)]| {
+            Object::Hash(
+                pairs
+                    .iter()
+                    .map(|&(key, value)| (Object::String(key.into()), Object::Int(value)))
+                    .collect(),
+            )
+        };
+
+        let tests = HashMap::from([
+            ("[1, 2] + [3]", Ok(array(&[1, 2, 3]))),
+            ("[] + []", Ok(array(&[]))),
+            (
+                "let xs = [1]; let ys = xs + xs; [xs, ys]",
+                Ok(Object::Array(vec![array(&[1]), array(&[1, 1])].into())),
+            ),
+            ("concat([1], [2, 3])", Ok(array(&[1, 2, 3]))),
+            (
+                r#"{"a": 1, "b": 2} + {"b": 3, "c": 4}"#,
+                Ok(hash(&[("a", 1), ("b", 3), ("c", 4)])),
+            ),
+            (
+                r#"let h = {"a": 1}; merge(h, {"a": 2}); h"#,
+                Ok(hash(&[("a", 1)])),
+            ),
+            (r#"merge({"a": 1}, {"a": 2})"#, Ok(hash(&[("a", 2)]))),
+            (
+                "[1] + (2, 3)",
+                Err(anyhow!(
+                    "Infix operator + not found for the operands: array & tuple!"
+                )),
+            ),
+            (
+                "concat([1], 2)",
+                Err(anyhow!("Builtin concat is not defined for int!")),
+            ),
+            (
+                r#"merge([1], {"a": 1})"#,
+                Err(anyhow!("Builtin merge is not defined for array!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn membership() {
+        let tests = HashMap::from([
+            ("2 in [1, 2, 3]", Ok(Object::Bool(true))),
+            ("4 in [1, 2, 3]", Ok(Object::Bool(false))),
+            ("4 !in [1, 2, 3]", Ok(Object::Bool(true))),
+            ("[1] in [[1], [2]]", Ok(Object::Bool(true))),
+            ("1 in (1, 2)", Ok(Object::Bool(true))),
+            (r#""key" in {"key": 1}"#, Ok(Object::Bool(true))),
+            (r#"1 in {"1": 1}"#, Ok(Object::Bool(false))),
+            (r#""sub" in "substring""#, Ok(Object::Bool(true))),
+            (r#""" in "abc""#, Ok(Object::Bool(true))),
+            (r#""x" !in "abc""#, Ok(Object::Bool(true))),
+            ("2 in set([1, 2])", Ok(Object::Bool(true))),
+            ("3 in 1..3", Ok(Object::Bool(false))),
+            ("3 in 1..=3", Ok(Object::Bool(true))),
+            ("1 + 1 in [1 < 2, 2]", Ok(Object::Bool(true))),
+            (
+                r#"1 in "1""#,
+                Err(anyhow!(
+                    "Infix operator in not found for the operands: int & string!"
+                )),
+            ),
+            (
+                "1 !in 2",
+                Err(anyhow!(
+                    "Infix operator !in not found for the operands: int & int!"
+                )),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn division() {
+        let tests = [
+            (
+                "-7 / 2",
+                [Object::Int(-3), Object::Int(-4), Object::Float(-3.5)],
+            ),
+            (
+                "7 / 2",
+                [Object::Int(3), Object::Int(3), Object::Float(3.5)],
+            ),
+            (
+                "-7 // 2",
+                [Object::Int(-4), Object::Int(-4), Object::Int(-4)],
+            ),
+            (
+                "7 // -7",
+                [Object::Int(-1), Object::Int(-1), Object::Int(-1)],
+            ),
+            (
+                "-7.0 / 2",
+                [
+                    Object::Float(-3.5),
+                    Object::Float(-3.5),
+                    Object::Float(-3.5),
+                ],
+            ),
+        ];
+
+        for (i, division) in [Division::Truncate, Division::Floor, Division::Float]
+            .into_iter()
+            .enumerate()
+        {
+            let tests = HashMap::from(
+                tests
+                    .clone()
+                    .map(|(input, outputs)| (input, Ok(outputs[i].clone()))),
+            );
+            let errors = HashMap::from([
+                ("1 / 0", Err(anyhow!("Division by zero!"))),
+                ("1 // 0", Err(anyhow!("Division by zero!"))),
+                (
+                    "let min = -9223372036854775807 - 1; min // -1",
+                    Err(anyhow!("Integer overflow in -9223372036854775808 // -1!")),
+                ),
+            ]);
+
+            for tests in [tests, errors] {
+                test_with_options(
+                    tests,
+                    Options {
+                        division,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn integer_overflow() {
+        let tests = HashMap::from([
+            (
+                "9223372036854775807 + 1",
+                Err(anyhow!("Integer overflow in 9223372036854775807 + 1!")),
+            ),
+            (
+                "-9223372036854775807 - 2",
+                Err(anyhow!("Integer overflow in -9223372036854775807 - 2!")),
+            ),
+            (
+                "9223372036854775807 * 2",
+                Err(anyhow!("Integer overflow in 9223372036854775807 * 2!")),
+            ),
+            (
+                "-(-9223372036854775807 - 1)",
+                Err(anyhow!("Integer overflow in -(-9223372036854775808)!")),
+            ),
+            ("-9223372036854775807 - 1", Ok(Object::Int(i64::MIN))),
+            ("9223372036854775806 + 1", Ok(Object::Int(i64::MAX))),
+            ("-(-9223372036854775807)", Ok(Object::Int(i64::MAX))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn floats() {
+        let tests = HashMap::from([
+            ("1.5 + 1", Ok(Object::Float(2.5))),
+            ("2 * 0.25 - 1.0", Ok(Object::Float(-0.5))),
+            ("-1.5", Ok(Object::Float(-1.5))),
+            ("7.5 // 2", Ok(Object::Float(3.0))),
+            ("1.0 / 0", Ok(Object::Float(f64::INFINITY))),
+            ("1 == 1.0", Ok(Object::Bool(true))),
+            ("[1] == [1.0]", Ok(Object::Bool(true))),
+            (r#"{"a": 1} == {"a": 1.0}"#, Ok(Object::Bool(true))),
+            ("(1, [2.0]) != (1.0, [2])", Ok(Object::Bool(false))),
+            ("[1] == [1.5]", Ok(Object::Bool(false))),
+            ("1.0 in [1]", Ok(Object::Bool(true))),
+            ("1 < 1.5", Ok(Object::Bool(true))),
+            ("len([1.5, 2])", Ok(Object::Int(2))),
+            (
+                "1 === 1.0",
+                Err(anyhow!("Operator === cannot compare int & float!")),
+            ),
+            (
+                "1.5 + true",
+                Err(anyhow!(
+                    "Infix operator + not found for the operands: float & bool!"
+                )),
+            ),
+        ]);
+
+        test(tests);
+        assert_eq!(Object::Float(2.0).to_string(), "2.0");
+    }
+
+    #[test]
+    fn strict_equality() {
+        let tests = HashMap::from([
+            ("1 === 1", Ok(Object::Bool(true))),
+            ("1 !== 2", Ok(Object::Bool(true))),
+            (r#""a" === "a""#, Ok(Object::Bool(true))),
+            ("[1, [2]] === [1, [2]]", Ok(Object::Bool(true))),
+            ("null === null", Ok(Object::Bool(true))),
+            ("1 == true", Ok(Object::Bool(false))),
+            (r#"1 != "1""#, Ok(Object::Bool(true))),
+            (
+                "1 === true",
+                Err(anyhow!("Operator === cannot compare int & bool!")),
+            ),
+            (
+                r#"1 !== "1""#,
+                Err(anyhow!("Operator !== cannot compare int & string!")),
+            ),
+            (
+                "[1] === null",
+                Err(anyhow!("Operator === cannot compare array & null!")),
+            ),
+            ("1 + 1 === 2 == true", Ok(Object::Bool(true))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn string_comparison() {
+        let tests = HashMap::from([
+            (r#""monkey" == "monkey""#, Ok(Object::Bool(true))),
+            (r#""monkey" == "business""#, Ok(Object::Bool(false))),
+            (r#""monkey" != "business""#, Ok(Object::Bool(true))),
+            (r#""mon" + "key" == "monkey""#, Ok(Object::Bool(true))),
+        ]);
+
+        test(tests);
+    }
+
     #[test]
     fn bool_expr() {
         let tests = HashMap::from([
@@ -497,15 +1957,20 @@ mod test {
     fn function() {
         let tests = HashMap::from([(
             "fn(x) { x + 2; }; ",
-            Ok(Object::Function(
-                vec![Identifier("x".into())],
-                vec![Statement::Expression(Expression::Infix(
-                    Infix::Plus,
-                    Box::new(Expression::Identifier(Identifier("x".into()))),
-                    Box::new(Expression::Literal(Literal::Int(2))),
-                ))],
-                Rc::new(RefCell::new(Env::new())),
-            )),
+            Ok(Object::Function(Rc::new(Function {
+                params: vec![Identifier("x".into())],
+                body: vec![
+                    Statement::Expression(Expression::Infix(
+                        Infix::Plus,
+                        Box::new(Expression::Identifier(Identifier("x".into()))),
+                        Box::new(Expression::Literal(Literal::Int(2))),
+                    )),
+                    Statement::Empty,
+                ],
+                generator: false,
+                env: Rc::new(RefCell::new(Env::new())),
+                live: Live::function(),
+            }))),
         )]);
 
         test(tests);
@@ -515,7 +1980,7 @@ mod test {
     fn function_application() {
         let tests = HashMap::from([
             (
-                "let identity = fn(x) { x; }; identity(5);",
+                "let identity = fn(x) { x }; identity(5);",
                 Ok(Object::Int(5)),
             ),
             (
@@ -523,19 +1988,53 @@ mod test {
                 Ok(Object::Int(5)),
             ),
             (
-                "let double = fn(x) { x * 2; }; double(5);",
+                "let double = fn(x) { x * 2 }; double(5);",
                 Ok(Object::Int(10)),
             ),
             (
-                "let add = fn(x, y) { x + y; }; add(5, 5);",
+                "let add = fn(x, y) { x + y }; add(5, 5);",
                 Ok(Object::Int(10)),
             ),
-            ("let id = fn(x) { x; }; id(id(5));", Ok(Object::Int(5))),
+            ("let id = fn(x) { x }; id(id(5));", Ok(Object::Int(5))),
             (
-                "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+                "let add = fn(x, y) { x + y }; add(5 + 5, add(5, 5));",
                 Ok(Object::Int(20)),
             ),
-            ("fn(x) { x; }(5)", Ok(Object::Int(5))),
+            ("fn(x) { x }(5)", Ok(Object::Int(5))),
+            ("let double = x => x * 2; double(5)", Ok(Object::Int(10))),
+            ("((x, y) => x + y)(5, 5)", Ok(Object::Int(10))),
+            ("let add = x => y => x + y; add(2)(3)", Ok(Object::Int(5))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn block_values() {
+        let tests = HashMap::from([
+            ("fn() { 1 }()", Ok(Object::Int(1))),
+            ("fn() { 1; }()", Ok(Object::Null)),
+            ("fn() { 1;; }()", Ok(Object::Null)),
+            ("fn() { 1; 2 }()", Ok(Object::Int(2))),
+            ("fn() { ;; 1; 2 }()", Ok(Object::Int(2))),
+            ("fn() { 1\n 2\n }()", Ok(Object::Int(2))),
+            ("fn() { 1\n 2; }()", Ok(Object::Null)),
+            ("fn(x) {\n x\n     + 1\n }(1)", Ok(Object::Int(2))),
+            ("fn(x) {\n x\n     - 1\n }(1)", Ok(Object::Int(0))),
+            ("fn(x) {\n x\n -1\n }(1)", Ok(Object::Int(-1))),
+            ("fn() { return 1; }()", Ok(Object::Int(1))),
+            ("fn() { let x = 1; x }()", Ok(Object::Int(1))),
+            ("fn() { }()", Ok(Object::Null)),
+            ("fn() { ; }()", Ok(Object::Null)),
+            ("if (true) { 1 }", Ok(Object::Int(1))),
+            ("if (true) { 1; }", Ok(Object::Null)),
+            ("if (false) { 1 } else { 2 }", Ok(Object::Int(2))),
+            ("if (false) { 1 } else { 2; }", Ok(Object::Null)),
+            ("if (true) { if (true) { 1 } }", Ok(Object::Int(1))),
+            ("if (true) { if (true) { 1 }; }", Ok(Object::Null)),
+            ("if (true) { if (true) { 1; } }", Ok(Object::Null)),
+            ("fn() { if (true) { 1; }; 2 }()", Ok(Object::Int(2))),
+            ("1;", Ok(Object::Int(1))),
         ]);
 
         test(tests);
@@ -546,7 +2045,7 @@ mod test {
         let tests = HashMap::from([(
             "
                 let newAdder = fn(x) {
-                    fn(y) { x + y };
+                    fn(y) { x + y }
                 };
                 let addTwo = newAdder(2);
                 addTwo(2);",
@@ -555,4 +2054,2175 @@ mod test {
 
         test(tests);
     }
+
+    #[test]
+    fn hash_index() {
+        let tests = HashMap::from([
+            (r#"{"foo": 5}["foo"]"#, Ok(Object::Int(5))),
+            (r#"{"foo": 5}["bar"]"#, Ok(Object::Null)),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Ok(Object::Int(5))),
+            (r#"{}["foo"]"#, Ok(Object::Null)),
+            ("{5: 5}[5]", Ok(Object::Int(5))),
+            ("{true: 5}[true]", Ok(Object::Int(5))),
+            ("{false: 5}[false]", Ok(Object::Int(5))),
+            ("{1: 5}[true]", Ok(Object::Null)),
+            ("{[1, (2,)]: 5}[[1, (2,)]]", Ok(Object::Int(5))),
+            ("{[1, (2,)]: 5}[[1, [2]]]", Ok(Object::Null)),
+            (
+                "{fn(x) { x }: 5}",
+                Err(anyhow!("Unusable as hash key: function!")),
+            ),
+            ("{[1, {}]: 5}", Err(anyhow!("Unusable as hash key: hash!"))),
+            (
+                "{1: 5}[len]",
+                Err(anyhow!("Unusable as hash key: builtin!")),
+            ),
+            (
+                "5[0]",
+                Err(anyhow!("Index operator not supported for int[int]!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn strict_hash_index() {
+        let tests = HashMap::from([
+            (r#"{"foo": 5}["foo"]"#, Ok(Object::Int(5))),
+            (
+                r#"{"foo": 5}["bar"]"#,
+                Err(anyhow!("Key bar not found in hash!")),
+            ),
+            ("{}[1]", Err(anyhow!("Key 1 not found in hash!"))),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                strict_index: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn array_index() {
+        let tests = HashMap::from([
+            (
+                "[1, 2 * 2, 3 + 3]",
+                Ok(Object::Array(
+                    vec![Object::Int(1), Object::Int(4), Object::Int(6)].into(),
+                )),
+            ),
+            ("[1, 2, 3][0]", Ok(Object::Int(1))),
+            ("[1, 2, 3][1 + 1]", Ok(Object::Int(3))),
+            ("let i = 0; [1][i]", Ok(Object::Int(1))),
+            (
+                "let arr = [1, 2, 3]; arr[0] + arr[1] + arr[2]",
+                Ok(Object::Int(6)),
+            ),
+            ("[1, 2, 3][3]", Ok(Object::Null)),
+            ("[1, 2, 3][-1]", Ok(Object::Null)),
+            (
+                r#"[1]["a"]"#,
+                Err(anyhow!("Index operator not supported for array[string]!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn destructuring_let() {
+        let tests = HashMap::from([
+            ("let [a, b, c] = [1, 2, 3]; a + b + c", Ok(Object::Int(6))),
+            (
+                "let [a, [b, c]] = [1, [2, 3]]; a * b * c",
+                Ok(Object::Int(6)),
+            ),
+            (
+                r#"let point = {"x": 1, "y": 2}; let {x, y} = point; x - y"#,
+                Ok(Object::Int(-1)),
+            ),
+            (
+                "let [a, b] = [1, 2, 3];",
+                Err(anyhow!(
+                    "Cannot destructure array of length 3 into 2 names!"
+                )),
+            ),
+            (
+                r#"let {x, z} = {"x": 1, "y": 2};"#,
+                Err(anyhow!("Key z not found while destructuring hash!")),
+            ),
+            (
+                "let [a] = 5;",
+                Err(anyhow!("Cannot destructure int as array!")),
+            ),
+            (
+                "let {a} = [1];",
+                Err(anyhow!("Cannot destructure array as hash!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn tuples() {
+        let tests = HashMap::from([
+            (
+                "(1, 2 + 3)",
+                Ok(Object::Tuple(vec![Object::Int(1), Object::Int(5)].into())),
+            ),
+            ("(1, 2, 3)[2]", Ok(Object::Int(3))),
+            (
+                "let divmod = fn(a, b) { (a / b, a - a / b * b) }; let (q, r) = divmod(7, 2); q * 10 + r",
+                Ok(Object::Int(31)),
+            ),
+            ("let (a, [b, c]) = (1, [2, 3]); a + b + c", Ok(Object::Int(6))),
+            (
+                "let (a, b) = (1, 2, 3);",
+                Err(anyhow!("Cannot destructure tuple of length 3 into 2 names!")),
+            ),
+            (
+                "let (a, b) = [1, 2];",
+                Err(anyhow!("Cannot destructure array as tuple!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn block_scoping() {
+        let tests = HashMap::from([
+            (
+                "if (true) { let x = 1; } x",
+                Err(anyhow!("Identifier x not found!")),
+            ),
+            ("let x = 1; if (true) { let x = 2; x }", Ok(Object::Int(2))),
+            ("let x = 1; if (true) { let x = 2; } x", Ok(Object::Int(1))),
+            (
+                "let x = 1; if (false) { 0 } else { let x = x + 1; x }",
+                Ok(Object::Int(2)),
+            ),
+            (
+                "let f = fn() { if (true) { let y = 3; } y }; f()",
+                Err(anyhow!("Identifier y not found!")),
+            ),
+            (
+                "let f = if (true) { let z = 5; fn() { z } }; f()",
+                Ok(Object::Int(5)),
+            ),
+            (
+                "let z = 1; let f = if (true) { let z = 5; fn() { z } }; f() + z",
+                Ok(Object::Int(6)),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn shadowing_warnings() {
+        let tests = [
+            ("let x = 1; if (true) { let x = 2; }", vec![]),
+            (
+                "let x = 1; if (true) { let x = \"a\"; }",
+                vec!["let x shadows an outer int with a string"],
+            ),
+            (
+                "let x = 1; let f = fn() { let [x, y] = [true, 2]; y }; f()",
+                vec!["let x shadows an outer int with a bool"],
+            ),
+            ("let x = 1; let x = true;", vec![]),
+        ];
+
+        for (input, expected) in tests {
+            let mut eval = Eval::new();
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            eval.eval(program).unwrap();
+
+            assert_eq!(eval.take_warnings(), expected, "{}", input);
+            assert!(eval.take_warnings().is_empty());
+        }
+    }
+
+    #[test]
+    fn stack_trace() {
+        let mut eval = Eval::new();
+        let input = "let inner = fn(x) { x + true };
+        let outer = fn() { inner(1) };
+        let call = fn(f) { f() };
+        call(outer)";
+
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        assert!(eval.eval(program).is_err());
+        assert_eq!(eval.stack_trace(), ["inner", "f", "call"]);
+        assert_eq!(eval.frames.len(), 1);
+
+        let program = Parser::new(Lexer::new("map([1], fn(x) { -true })"))
+            .parse_program()
+            .unwrap();
+        assert!(eval.eval(program).is_err());
+        assert_eq!(eval.stack_trace(), ["<anonymous>"]);
+
+        let program = Parser::new(Lexer::new("outer")).parse_program().unwrap();
+        assert!(eval.eval(program).is_ok());
+        assert!(eval.stack_trace().is_empty());
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let tests = HashMap::from([
+            (
+                "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(19)",
+                Ok(Object::Int(19)),
+            ),
+            (
+                "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(20)",
+                Err(anyhow!("Stack overflow: more than 20 nested calls!")),
+            ),
+            (
+                "let f = fn() { f() }; f()",
+                Err(anyhow!("Stack overflow: more than 20 nested calls!")),
+            ),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                max_call_depth: 20,
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn assignment() {
+        let tests = HashMap::from([
+            ("let x = 1; x = 2; x", Ok(Object::Int(2))),
+            ("let x = 1; x = x + 1", Ok(Object::Int(2))),
+            (
+                "let x = 1; let y = 1; x = y = 5; x + y",
+                Ok(Object::Int(10)),
+            ),
+            ("let x = 1; if (true) { x = 2; } x", Ok(Object::Int(2))),
+            (
+                "let x = 1; if (true) { let x = 2; x = 3; } x",
+                Ok(Object::Int(1)),
+            ),
+            ("x = 1; x", Ok(Object::Int(1))),
+            ("if (true) { x = 1; } x", Ok(Object::Int(1))),
+            (
+                "let f = fn() { counter = 5; }; f(); counter",
+                Ok(Object::Int(5)),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn did_you_mean() {
+        let tests = HashMap::from([
+            (
+                "let counter = 1; countr",
+                Err(anyhow!(
+                    "Identifier countr not found! Did you mean `counter`?"
+                )),
+            ),
+            (
+                "let total = 0; let f = fn(items) { totl + itemz }; f([])",
+                Err(anyhow!("Identifier totl not found! Did you mean `total`?")),
+            ),
+            (
+                "let f = fn(items) { itemz }; f([])",
+                Err(anyhow!("Identifier itemz not found! Did you mean `items`?")),
+            ),
+            (
+                "fist([1])",
+                Err(anyhow!("Identifier fist not found! Did you mean `first`?")),
+            ),
+            ("let ab = 1; ax", Err(anyhow!("Identifier ax not found!"))),
+            (
+                "let apple = 1; orange",
+                Err(anyhow!("Identifier orange not found!")),
+            ),
+        ]);
+
+        test(tests);
+
+        assert_eq!(super::edit_distance("kitten", "sitting"), 3);
+        assert_eq!(super::edit_distance("", "abc"), 3);
+        assert_eq!(super::edit_distance("ñu", "nu"), 1);
+    }
+
+    #[test]
+    fn strict_assignment() {
+        let tests = HashMap::from([
+            ("let x = 1; x = 2; x", Ok(Object::Int(2))),
+            ("x = 1", Err(anyhow!("Identifier x not found!"))),
+            (
+                "let f = fn() { counter = 5; }; f()",
+                Err(anyhow!("Identifier counter not found!")),
+            ),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                strict: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn closures_capture_by_reference() {
+        let tests = HashMap::from([
+            (
+                "let counter = fn() { let count = 0; fn() { count = count + 1; count } };
+                let c = counter(); c(); c(); c()",
+                Ok(Object::Int(3)),
+            ),
+            (
+                "let counter = fn() { let count = 0; fn() { count = count + 1; count } };
+                let a = counter(); let b = counter(); a(); a(); b()",
+                Ok(Object::Int(1)),
+            ),
+            (
+                "let x = 1; let adder = fn(y) { x + y }; x = 10; adder(1)",
+                Ok(Object::Int(11)),
+            ),
+            (
+                "let x = 1; let set = fn(v) { x = v; }; set(7); x",
+                Ok(Object::Int(7)),
+            ),
+            (
+                "let a = [1]; let get = fn() { a }; a = [2]; get()",
+                Ok(Object::Array(vec![Object::Int(2)].into())),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn null_comparison() {
+        let tests = HashMap::from([
+            ("null", Ok(Object::Null)),
+            ("null == null", Ok(Object::Bool(true))),
+            ("null != null", Ok(Object::Bool(false))),
+            ("null == 5", Ok(Object::Bool(false))),
+            ("5 != null", Ok(Object::Bool(true))),
+            (r#""null" == null"#, Ok(Object::Bool(false))),
+            (r#"{"a": 1}["b"] == null"#, Ok(Object::Bool(true))),
+            ("!null", Ok(Object::Bool(true))),
+            ("!!null", Ok(Object::Bool(false))),
+            ("if (null) { 1 } else { 2 }", Ok(Object::Int(2))),
+            ("1 == true", Ok(Object::Bool(false))),
+            (
+                "null + 1",
+                Err(anyhow!(
+                    "Infix operator + not found for the operands: null & int!"
+                )),
+            ),
+            (
+                "null < null",
+                Err(anyhow!(
+                    "Infix operator < not found for the operands: null & null!"
+                )),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn composite_equality() {
+        let tests = HashMap::from([
+            ("[1, 2, 3] == [1, 2, 3]", Ok(Object::Bool(true))),
+            ("[1, 2, 3] == [1, 2]", Ok(Object::Bool(false))),
+            ("[1, [2, 3]] != [1, [2, 4]]", Ok(Object::Bool(true))),
+            ("[] == []", Ok(Object::Bool(true))),
+            ("(1, 2) == (1, 2)", Ok(Object::Bool(true))),
+            ("(1, 2) == [1, 2]", Ok(Object::Bool(false))),
+            (
+                r#"{"a": 1, "b": [2]} == {"b": [2], "a": 1}"#,
+                Ok(Object::Bool(true)),
+            ),
+            (r#"{"a": 1} == {"a": 2}"#, Ok(Object::Bool(false))),
+            (r#"{"a": 1} == {"b": 1}"#, Ok(Object::Bool(false))),
+            ("let f = fn(x) { x }; f == f", Ok(Object::Bool(true))),
+            (
+                "let f = fn(x) { x }; let g = f; f == g",
+                Ok(Object::Bool(true)),
+            ),
+            ("fn(x) { x } == fn(x) { x }", Ok(Object::Bool(false))),
+            ("let f = fn(x) { x }; [f] == [f]", Ok(Object::Bool(true))),
+            (
+                "let f = fn(x) { x }; f != fn(x) { x }",
+                Ok(Object::Bool(true)),
+            ),
+            (
+                "[1] - [2]",
+                Err(anyhow!(
+                    "Infix operator - not found for the operands: array & array!"
+                )),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn early_return_from_function() {
+        let tests = HashMap::from([
+            ("let f = fn() { return 1; }; f() + 1", Ok(Object::Int(2))),
+            (
+                "let f = fn(x) { if (x > 0) { return x; } 0 }; f(5) + f(-5)",
+                Ok(Object::Int(5)),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn generators() {
+        let tests = HashMap::from([
+            (
+                "let gen = fn() { yield 1; yield 2; }; let g = gen(); [next(g), next(g), next(g)]",
+                Ok(Object::Array(
+                    vec![Object::Int(1), Object::Int(2), Object::Null].into(),
+                )),
+            ),
+            (
+                "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } };
+                let gen = fn() { yield sum(10); yield sum(100); }; let g = gen(); next(g); next(g)",
+                Ok(Object::Int(5050)),
+            ),
+            (
+                "let count = 0; let gen = fn(x) { count = count + 1; yield x; count = count + 1; };
+                let g = gen(5); let before = count; let value = next(g); [before, value, count]",
+                Ok(Object::Array(
+                    vec![Object::Int(0), Object::Int(5), Object::Int(1)].into(),
+                )),
+            ),
+            (
+                "let gen = fn(n) { if (n > 0) { yield n; } yield 0; }; let g = gen(3); let h = gen(-1); [next(g), next(h), next(g)]",
+                Ok(Object::Array(
+                    vec![Object::Int(3), Object::Int(0), Object::Int(0)].into(),
+                )),
+            ),
+            (
+                "let gen = fn() { yield 1; 1 + true }; let g = gen(); next(g); next(g)",
+                Err(anyhow!(
+                    "Infix operator + not found for the operands: int & bool!"
+                )),
+            ),
+            (
+                "let g = 0; let gen = fn() { yield next(g); }; g = gen(); next(g)",
+                Err(anyhow!("Generator is already running!")),
+            ),
+            (
+                "let gen = fn() { yield 1; }; let g = gen(); g == g",
+                Ok(Object::Bool(true)),
+            ),
+            (
+                "next(1)",
+                Err(anyhow!("Builtin next is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn thunks() {
+        let tests = HashMap::from([
+            (
+                "let calls = 0; let t = delay(fn() { calls = calls + 1; 42 }); let before = calls;
+                [before, force(t), force(t), calls]",
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(0),
+                        Object::Int(42),
+                        Object::Int(42),
+                        Object::Int(1),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "let integers = fn(n) { [n, delay(fn() { integers(n + 1) })] };
+                let take = fn(stream, k, taken) {
+                    if (k == 0) { taken } else {
+                        let [head, tail] = stream;
+                        take(force(tail), k - 1, push(taken, head))
+                    }
+                };
+                take(integers(1), 4, [])",
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(1),
+                        Object::Int(2),
+                        Object::Int(3),
+                        Object::Int(4),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "let t = 0; t = delay(fn() { force(t) }); force(t)",
+                Err(anyhow!("Thunk depends on its own value!")),
+            ),
+            (
+                "delay(1)",
+                Err(anyhow!("Builtin delay is not defined for int!")),
+            ),
+            (
+                "force(1)",
+                Err(anyhow!("Builtin force is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn compose_and_partial() {
+        let tests = HashMap::from([
+            (
+                "let inc = fn(x) { x + 1 }; let double = fn(x) { x * 2 }; compose(inc, double)(5)",
+                Ok(Object::Int(11)),
+            ),
+            (
+                "let add = fn(a, b) { a + b }; let len_plus = compose(partial(add, 10), len); len_plus([1, 2])",
+                Ok(Object::Int(12)),
+            ),
+            (
+                "let sub = fn(a, b, c) { a - b - c }; partial(partial(sub, 10), 3)(2)",
+                Ok(Object::Int(5)),
+            ),
+            (
+                "map([1, 2, 3], partial(fn(a, b) { a * b }, 3))",
+                Ok(Object::Array(
+                    vec![Object::Int(3), Object::Int(6), Object::Int(9)].into(),
+                )),
+            ),
+            (
+                "partial(fn(a, b) { a }, 1)(2, 3)",
+                Err(anyhow!("Wrong number of arguments. Expected: 2. Given: 3")),
+            ),
+            (
+                "let f = partial(len, \"abc\"); [f == f, f == partial(len, \"abc\")]",
+                Ok(Object::Array(
+                    vec![Object::Bool(true), Object::Bool(false)].into(),
+                )),
+            ),
+            (
+                "compose(len, 1)",
+                Err(anyhow!("Builtin compose is not defined for int!")),
+            ),
+            (
+                "partial(1, 2)",
+                Err(anyhow!("Builtin partial is not defined for int!")),
+            ),
+            (
+                "partial()",
+                Err(anyhow!(
+                    "Wrong number of arguments. Expected: at least 1. Given: 0"
+                )),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn memoize() {
+        let tests = HashMap::from([
+            (
+                "let calls = 0;
+                let fib = memoize(fn(n) { calls = calls + 1; if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } });
+                [fib(60), calls, fib(60), calls]",
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(1548008755920),
+                        Object::Int(61),
+                        Object::Int(1548008755920),
+                        Object::Int(61),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "let calls = 0; let f = memoize(fn(x) { calls = calls + 1; len(x) });
+                f([1, 2]); f([1, 2]); f(\"ab\"); f({1: 2}); f({1: 2}); calls",
+                Ok(Object::Int(4)),
+            ),
+            (
+                "memoize(1)",
+                Err(anyhow!("Builtin memoize is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn named_arguments() {
+        let tests = HashMap::from([
+            (
+                r#"let greet = fn(greeting, title, name) { join([greeting, title, name], " ") };
+                greet("Hi", name: "Ana", title: "Dr")"#,
+                Ok(Object::String("Hi Dr Ana".into())),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; sub(b: 1, a: 10)",
+                Ok(Object::Int(9)),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; sub(1, c: 2)",
+                Err(anyhow!("Function has no parameter named c!")),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; sub(1, a: 2)",
+                Err(anyhow!("Argument a given more than once!")),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; sub(b: 1, b: 2)",
+                Err(anyhow!("Duplicate named argument b!")),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; sub(b: 1)",
+                Err(anyhow!("Missing argument a!")),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; sub(1, 2, 3, a: 1)",
+                Err(anyhow!("Wrong number of arguments. Expected: 2. Given: 4")),
+            ),
+            (
+                r#"join(separator: "-", array: [1, 2])"#,
+                Ok(Object::String("1-2".into())),
+            ),
+            (
+                "len(x: [1])",
+                Err(anyhow!("Function has no parameter named x!")),
+            ),
+            (
+                r#"format(template: "{}")"#,
+                Err(anyhow!("Named arguments are not supported for builtin!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn spread() {
+        let tests = HashMap::from([
+            (
+                "let rest = [2, 3]; [1, ...rest, ...[], 9]",
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(1),
+                        Object::Int(2),
+                        Object::Int(3),
+                        Object::Int(9),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "let add = fn(a, b, c) { a + b + c }; let args = [2, 3]; add(1, ...args)",
+                Ok(Object::Int(6)),
+            ),
+            (
+                "let add = fn(a, b) { a + b }; add(...[1, 2, 3])",
+                Err(anyhow!("Wrong number of arguments. Expected: 2. Given: 3")),
+            ),
+            ("[...1]", Err(anyhow!("Cannot spread int!"))),
+            ("len(...(1, 2))", Err(anyhow!("Cannot spread tuple!"))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn ranges() {
+        let ints = |values: &[i64]| {
+            Ok(Object::Array(
+                values.iter().copied().map(Object::Int).collect(),
+            ))
+        };
+        let tests = HashMap::from([
+            ("1..4", Ok(Object::Range(1, 4))),
+            ("let n = 3; 0..=n", Ok(Object::Range(0, 4))),
+            ("to_array(1..4)", ints(&[1, 2, 3])),
+            ("to_array(1..=4)", ints(&[1, 2, 3, 4])),
+            ("to_array(4..1)", ints(&[])),
+            ("[0, ...(1..3)]", ints(&[0, 1, 2])),
+            ("map(1..4, fn(x) { x * x })", ints(&[1, 4, 9])),
+            ("len(0..1000000000000)", Ok(Object::Int(1000000000000))),
+            ("len(5..1)", Ok(Object::Int(0))),
+            ("(10..1000000000000)[5]", Ok(Object::Int(15))),
+            ("(10..20)[10]", Ok(Object::Null)),
+            ("(10..20)[-1]", Ok(Object::Null)),
+            ("1..3 == 1..=2", Ok(Object::Bool(true))),
+            (
+                "1..true",
+                Err(anyhow!("Range bounds must be ints, not int & bool!")),
+            ),
+            (
+                "0..=9223372036854775807",
+                Err(anyhow!("Range end 9223372036854775807 is too large!")),
+            ),
+            (
+                "to_array([1])",
+                Err(anyhow!("Builtin to_array is not defined for array!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn pipe() {
+        let tests = HashMap::from([
+            (
+                "[1, 2, 3] |> map(fn(x) { x * 2 }) |> join(\"-\")",
+                Ok(Object::String("2-4-6".into())),
+            ),
+            ("[1, 2, 3] |> rest |> len == 2", Ok(Object::Bool(true))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn deques() {
+        let ints =
+            |values: &[i64]| Object::Array(values.iter().copied().map(Object::Int).collect());
+        let tests = HashMap::from([
+            ("push_front([2, 3], 1)", Ok(ints(&[1, 2, 3]))),
+            (
+                "let (last, rest) = pop([1, 2, 3]); [last, rest]",
+                Ok(Object::Array(vec![Object::Int(3), ints(&[1, 2])].into())),
+            ),
+            (
+                "pop_front([1, 2, 3])",
+                Ok(Object::Tuple(vec![Object::Int(1), ints(&[2, 3])].into())),
+            ),
+            ("pop([])", Ok(Object::Null)),
+            ("pop_front([])", Ok(Object::Null)),
+            (
+                "let a = [1, 2]; let (_, b) = pop(a); [push(b, 9), a]",
+                Ok(Object::Array(vec![ints(&[1, 9]), ints(&[1, 2])].into())),
+            ),
+            (
+                "let bfs = fn(queue, seen) { if (len(queue) == 0) { return seen; } let (n, queue) = pop_front(queue); let queue = if (n < 4) { push(push(queue, 2 * n), 2 * n + 1) } else { queue }; bfs(queue, push(seen, n)) }; bfs([1], [])",
+                Ok(ints(&[1, 2, 3, 4, 5, 6, 7])),
+            ),
+            (
+                "pop(1)",
+                Err(anyhow!("Builtin pop is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn clone() {
+        let tests = HashMap::from([
+            (
+                r#"let a = [1, "two", {"three": (3, [4])}]; let b = clone(a); [b == a, b]"#,
+                Ok(Object::Array(
+                    vec![
+                        Object::Bool(true),
+                        Object::Array(
+                            vec![
+                                Object::Int(1),
+                                Object::String("two".into()),
+                                Object::Hash(
+                                    HashMap::from([(
+                                        Object::String("three".into()),
+                                        Object::Tuple(
+                                            vec![
+                                                Object::Int(3),
+                                                Object::Array(vec![Object::Int(4)].into()),
+                                            ]
+                                            .into(),
+                                        ),
+                                    )])
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "clone(rest([1, 2, 3]))",
+                Ok(Object::Array(vec![Object::Int(2), Object::Int(3)].into())),
+            ),
+            ("let f = fn() { 1 }; clone(f) == f", Ok(Object::Bool(true))),
+            ("clone(set([1])) == set([1])", Ok(Object::Bool(true))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn freeze() {
+        let tests = HashMap::from([
+            (
+                "push(freeze([1]), 2)",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "push_front(freeze([1]), 2)",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "pop(freeze([1]))",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "pop_front(freeze([1]))",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "concat(freeze([1]), [2])",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "freeze([1]) + [2]",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "push(freeze({\"a\": [1]})[\"a\"], 2)",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "rest(freeze([1, 2]))",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "concat([2], freeze([1]))",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "let [xs] = freeze([[1]]); push(xs, 2)",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "merge(freeze({\"a\": 1}), {})",
+                Err(anyhow!("Cannot change a frozen hash!")),
+            ),
+            ("freeze([1]) == [1]", Ok(Object::Bool(true))),
+            (
+                "[2] + freeze([1])",
+                Err(anyhow!("Cannot change a frozen array!")),
+            ),
+            (
+                "merge({}, freeze({\"a\": 1}))",
+                Err(anyhow!("Cannot change a frozen hash!")),
+            ),
+            (
+                "freeze({\"a\": 1}) + {}",
+                Err(anyhow!("Cannot change a frozen hash!")),
+            ),
+            (
+                "{} + freeze({\"a\": 1})",
+                Err(anyhow!("Cannot change a frozen hash!")),
+            ),
+            (
+                "let a = freeze([1, 2]); [first(a), last(a), len(a), a[1]]",
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(1),
+                        Object::Int(2),
+                        Object::Int(2),
+                        Object::Int(2),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "let a = [1]; freeze(a); push(a, 2)",
+                Ok(Object::Array(vec![Object::Int(1), Object::Int(2)].into())),
+            ),
+            (
+                "push(clone(freeze([1])), 2)",
+                Ok(Object::Array(vec![Object::Int(1), Object::Int(2)].into())),
+            ),
+            ("is_frozen(freeze([1]))", Ok(Object::Bool(true))),
+            ("is_frozen(first(freeze([[1]])))", Ok(Object::Bool(true))),
+            ("is_frozen(freeze(set([[1]])))", Ok(Object::Bool(false))),
+            ("is_frozen([1])", Ok(Object::Bool(false))),
+            ("freeze(1)", Ok(Object::Int(1))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn reflection() {
+        let strings = |values: &[&str]| {
+            Ok(Object::Array(
+                values
+                    .iter()
+                    .map(|value| Object::String((*value).into()))
+                    .collect(),
+            ))
+        };
+        let tests = HashMap::from([
+            ("arity(fn(a, b) { a })", Ok(Object::Int(2))),
+            ("params(fn(a, b) { a })", strings(&["a", "b"])),
+            (
+                "params(partial(fn(a, b, c) { a }, 1))",
+                strings(&["b", "c"]),
+            ),
+            ("arity(partial(fn(a) { a }, 1, 2))", Ok(Object::Int(0))),
+            (
+                "params(compose(fn(x) { x }, fn(y, z) { y }))",
+                strings(&["y", "z"]),
+            ),
+            ("arity(memoize(fn(n) { n }))", Ok(Object::Int(1))),
+            (
+                "body_source(fn(x) { let y = x * 2; if (y > 2) { y } else { 0 } })",
+                Ok(Object::String(
+                    "let y = (x * 2);\nif ((y > 2)) {\n    y\n} else {\n    0\n}".into(),
+                )),
+            ),
+            (
+                "arity(len)",
+                Err(anyhow!("Builtin arity is not defined for builtin!")),
+            ),
+            (
+                "body_source(1)",
+                Err(anyhow!("Builtin body_source is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn docstrings() {
+        let tests = HashMap::from([
+            (
+                r#"doc(fn(x) { "Doubles x."; x * 2 })"#,
+                Ok(Object::String("Doubles x.".into())),
+            ),
+            (
+                r#"let f = fn(x) { "Doubles x."; x * 2 }; f(2)"#,
+                Ok(Object::Int(4)),
+            ),
+            (
+                r#"doc(fn() { "Not a doc, the result." })"#,
+                Ok(Object::Null),
+            ),
+            ("doc(fn(x) { x })", Ok(Object::Null)),
+            (
+                r#"doc(memoize(partial(fn(a, b) { "Adds."; a + b }, 1)))"#,
+                Ok(Object::String("Adds.".into())),
+            ),
+            (
+                "doc(push)",
+                Ok(Object::String("push(array: array, value)".into())),
+            ),
+            (
+                "doc(1)",
+                Err(anyhow!("Builtin doc is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn builtin_signatures() {
+        let tests = HashMap::from([
+            (
+                "len()",
+                Err(anyhow!("Wrong number of arguments. Expected: 1. Given: 0")),
+            ),
+            (
+                "input(1, 2)",
+                Err(anyhow!("Wrong number of arguments. Expected: 1. Given: 2")),
+            ),
+            (
+                "push(1, 2)",
+                Err(anyhow!("Builtin push is not defined for int!")),
+            ),
+            (
+                "join([1], 2)",
+                Err(anyhow!("Builtin join is not defined for int!")),
+            ),
+            (
+                "map([1], 2)",
+                Err(anyhow!("Builtin map is not defined for int!")),
+            ),
+            (
+                r#"format("{}-{}", 1, "a")"#,
+                Ok(Object::String("1-a".into())),
+            ),
+            ("partial(len)([1])", Ok(Object::Int(1))),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn await_blocks_outside_tasks() {
+        let tests = HashMap::from([
+            ("await sleep_async(1)", Ok(Object::Null)),
+            ("await 5", Ok(Object::Int(5))),
+            (
+                "let p = sleep_async(1); [await p, await p]",
+                Ok(Object::Array(vec![Object::Null, Object::Null].into())),
+            ),
+            (
+                r#"let f = fn(path) { await async_read_file(path) }; len(f("Cargo.toml")) > 0"#,
+                Ok(Object::Bool(true)),
+            ),
+            (
+                r#"await async_read_file("missing.monkey")"#,
+                Err(anyhow!(
+                    "Cannot read missing.monkey: No such file or directory (os error 2)!"
+                )),
+            ),
+            ("sleep_async(-1)", Err(anyhow!("Cannot sleep for -1 ms!"))),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                capabilities: Capabilities {
+                    fs: vec![PathBuf::from(".")],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn sets() {
+        let string = |s: &str| Ok(Object::String(s.into()));
+        let tests = HashMap::from([
+            ("inspect(set([3, 1, 3, 2]))", string("set([1, 2, 3])")),
+            (
+                r#"inspect(set(["b", [1], "a"]))"#,
+                string(r#"set(["a", "b", [1]])"#),
+            ),
+            ("inspect(insert(set(1..3), 5))", string("set([1, 2, 5])")),
+            (
+                "inspect(union(set([1, 2]), set([2, 3])))",
+                string("set([1, 2, 3])"),
+            ),
+            (
+                "inspect(intersection(set([1, 2]), set([2, 3])))",
+                string("set([2])"),
+            ),
+            (
+                "inspect(difference(set([1, 2]), set([2, 3])))",
+                string("set([1])"),
+            ),
+            (
+                "let s = set([1]); insert(s, 2); inspect(s)",
+                string("set([1])"),
+            ),
+        ]);
+        test(tests);
+
+        let tests = HashMap::from([
+            ("contains(set([1, 2]), 2)", Ok(Object::Bool(true))),
+            (r#"contains(set([1, 2]), "2")"#, Ok(Object::Bool(false))),
+            ("len(set([1, 1, 2]))", Ok(Object::Int(2))),
+            ("set([1, 2]) == set([2, 1])", Ok(Object::Bool(true))),
+            (
+                "let s = set([1, 2, 3]); len(map(s, fn(x) { x * 2 }))",
+                Ok(Object::Int(3)),
+            ),
+            (
+                "set([fn() {}])",
+                Err(anyhow!("Builtin set cannot add function to a set!")),
+            ),
+            (
+                "insert(set([]), {})",
+                Err(anyhow!("Builtin insert cannot add hash to a set!")),
+            ),
+            (
+                "union(set([]), [1])",
+                Err(anyhow!("Builtin union is not defined for array!")),
+            ),
+            (
+                "set(1)",
+                Err(anyhow!("Builtin set is not defined for int!")),
+            ),
+        ]);
+        test(tests);
+    }
+
+    #[test]
+    fn binary_data() {
+        let bytes = |value: &[u8]| Ok(Object::Bytes(value.into()));
+        let ints = |values: &[i64]| {
+            Ok(Object::Array(
+                values.iter().copied().map(Object::Int).collect(),
+            ))
+        };
+        let tests = HashMap::from([
+            (r#"b"abc""#, bytes(b"abc")),
+            (r#"len(b"añ")"#, Ok(Object::Int(3))),
+            (r#"b"abc"[1]"#, Ok(Object::Int(98))),
+            (r#"b"abc"[3]"#, Ok(Object::Null)),
+            (r#"b"abcdef"[1..3]"#, bytes(b"bc")),
+            (r#"b"abc"[2..10]"#, bytes(b"c")),
+            (r#"b"abc"[-5..=0]"#, bytes(b"a")),
+            (r#"b"abc"[2..1]"#, bytes(b"")),
+            (r#"encode("añ")"#, bytes("añ".as_bytes())),
+            (r#"decode(b"añ")"#, Ok(Object::String("añ".into()))),
+            (
+                r#"decode(encode("hé")[0..2])"#,
+                Err(anyhow!(
+                    "Builtin decode needs UTF-8 text: incomplete utf-8 byte sequence from index 1!"
+                )),
+            ),
+            (r#"bytes(b"hi")"#, ints(&[104, 105])),
+            (r#"map(b"ab", fn(x) { x + 1 })"#, ints(&[98, 99])),
+            (r#"b"hi" == encode("hi")"#, Ok(Object::Bool(true))),
+            (r#"b"hi" == "hi""#, Ok(Object::Bool(false))),
+            (
+                "decode(1)",
+                Err(anyhow!("Builtin decode is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+        assert_eq!(
+            Object::Bytes(b"a\"\n\xff".as_slice().into()).to_string(),
+            r#"b"a\"\n\xff""#
+        );
+    }
+
+    #[test]
+    fn chars_and_bytes() {
+        let strings = |values: &[&str]| {
+            Ok(Object::Array(
+                values.iter().map(|x| Object::String((*x).into())).collect(),
+            ))
+        };
+        let tests = HashMap::from([
+            (r#"len("héllo")"#, Ok(Object::Int(5))),
+            (r#"len(bytes("héllo"))"#, Ok(Object::Int(6))),
+            (r#"chars("añ😀")"#, strings(&["a", "ñ", "😀"])),
+            (r#"chars("")"#, strings(&[])),
+            (
+                r#"bytes("añ")"#,
+                Ok(Object::Array(
+                    vec![Object::Int(97), Object::Int(195), Object::Int(177)].into(),
+                )),
+            ),
+            (
+                "chars([1])",
+                Err(anyhow!("Builtin chars is not defined for array!")),
+            ),
+            (
+                "bytes(1)",
+                Err(anyhow!("Builtin bytes is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn inspect() {
+        let string = |s: &str| Ok(Object::String(s.into()));
+        let tests = HashMap::from([
+            ("inspect(-5)", string("-5")),
+            (r#"inspect("a b")"#, string(r#""a b""#)),
+            (
+                r#"inspect([1, "x", null, true, len])"#,
+                string(r#"[1, "x", null, true, len]"#),
+            ),
+            (r#"inspect(("a",))"#, string(r#"("a",)"#)),
+            (r#"inspect((1, [2]))"#, string("(1, [2])")),
+            (
+                r#"inspect({"b": [1], "a": {2: "c"}})"#,
+                string(r#"{"a": {2: "c"}, "b": [1]}"#),
+            ),
+            ("inspect(0..=3)", string("0..4")),
+            ("inspect(fn(x) { x })", string("fn(x)")),
+        ]);
+
+        test(tests);
+
+        let source = r#"let x = [("a",), {"k": [1, null]}, 2..5]; inspect(x)"#;
+        let mut eval = Eval::new();
+        let Ok(Object::String(inspected)) =
+            eval.eval(Parser::new(Lexer::new(source)).parse_program().unwrap())
+        else {
+            panic!("inspect should return a string");
+        };
+        let program = Parser::new(Lexer::new(&inspected)).parse_program().unwrap();
+        let x = eval.eval(Parser::new(Lexer::new("x")).parse_program().unwrap());
+        assert_eq!(eval.eval(program).unwrap(), x.unwrap());
+    }
+
+    #[test]
+    fn format() {
+        let string = |s: &str| Ok(Object::String(s.into()));
+        let tests = HashMap::from([
+            (
+                r#"format("x = {}, y = {}", 1, "a")"#,
+                string("x = 1, y = a"),
+            ),
+            (r#"format("{1} {0} {1}", "a", "b")"#, string("b a b")),
+            (r#"format("{{{}}} }}", [1, 2])"#, string("{[1, 2]} }")),
+            (
+                r#"format("[{:5}|{:5}]", 42, "ab")"#,
+                string("[   42|ab   ]"),
+            ),
+            (r#"format("[{:<4}|{:>4}]", 7, "é")"#, string("[7   |   é]")),
+            (
+                r#"format("[{0:*^6}|{0:-^5}]", "ab")"#,
+                string("[**ab**|-ab--]"),
+            ),
+            (r#"format("{:2}", "long")"#, string("long")),
+            (
+                "format()",
+                Err(anyhow!(
+                    "Wrong number of arguments. Expected: at least 1. Given: 0"
+                )),
+            ),
+            (
+                "format(1)",
+                Err(anyhow!("Builtin format is not defined for int!")),
+            ),
+            (
+                r#"format("{} {}", 1)"#,
+                Err(anyhow!("Missing argument 1 for placeholder {{}}!")),
+            ),
+            (
+                r#"format("{", 1)"#,
+                Err(anyhow!("Unclosed {{ in format string!")),
+            ),
+            (
+                r#"format("}", 1)"#,
+                Err(anyhow!("Unmatched }} in format string!")),
+            ),
+            (
+                r#"format("{x}", 1)"#,
+                Err(anyhow!("Invalid placeholder {{x}}!")),
+            ),
+            (
+                r#"format("{:>x}", 1)"#,
+                Err(anyhow!("Invalid placeholder {{:>x}}!")),
+            ),
+            (
+                r#"format("{:€>9223372036854775807}", 1)"#,
+                Err(anyhow!(
+                    "Width of placeholder {{:€>9223372036854775807}} is too large!"
+                )),
+            ),
+            (
+                r#"format("{:>99999999999999}", 1)"#,
+                Err(anyhow!(
+                    "Width of placeholder {{:>99999999999999}} is too large!"
+                )),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn builtins() {
+        let tests = HashMap::from([
+            (r#"len("four")"#, Ok(Object::Int(4))),
+            ("len([1, 2, 3])", Ok(Object::Int(3))),
+            (r#"len({"a": 1})"#, Ok(Object::Int(1))),
+            ("first([1, 2, 3])", Ok(Object::Int(1))),
+            ("first([])", Ok(Object::Null)),
+            ("last([1, 2, 3])", Ok(Object::Int(3))),
+            (
+                "rest([1, 2, 3])",
+                Ok(Object::Array(vec![Object::Int(2), Object::Int(3)].into())),
+            ),
+            ("rest(rest(rest([1, 2, 3])))", Ok(Object::Array(vec![].into()))),
+            ("rest([])", Ok(Object::Null)),
+            (
+                "let a = [1]; let b = push(a, 2); [a, b]",
+                Ok(Object::Array(
+                    vec![
+                        Object::Array(vec![Object::Int(1)].into()),
+                        Object::Array(vec![Object::Int(1), Object::Int(2)].into()),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "let a = [1, 2, 3]; let r = rest(a); [push(r, 4), a] == [[2, 3, 4], [1, 2, 3]]",
+                Ok(Object::Bool(true)),
+            ),
+            (
+                "map([1, 2, 3], fn(x) { x * 2 })",
+                Ok(Object::Array(
+                    vec![Object::Int(2), Object::Int(4), Object::Int(6)].into(),
+                )),
+            ),
+            (
+                "let sum = fn(arr) { if (len(arr) == 0) { return 0; } first(arr) + sum(rest(arr)) }; sum([1, 2, 3, 4])",
+                Ok(Object::Int(10)),
+            ),
+            (
+                "len(1)",
+                Err(anyhow!("Builtin len is not defined for int!")),
+            ),
+            (
+                "len([], [])",
+                Err(anyhow!("Wrong number of arguments. Expected: 1. Given: 2")),
+            ),
+            ("let len = 5; len", Ok(Object::Int(5))),
+            (
+                r#"join(["a", "b", "c"], ", ")"#,
+                Ok(Object::String("a, b, c".into())),
+            ),
+            (r#"join([], "-")"#, Ok(Object::String("".into()))),
+            (
+                r#"join([1, true, "x"], "")"#,
+                Ok(Object::String("1truex".into())),
+            ),
+            (
+                r#"join("abc", "")"#,
+                Err(anyhow!("Builtin join is not defined for string!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn profile_calls() {
+        let input = "
+            let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) };
+            let twice = fn(f, x) { f(f(x)) };
+            fib(10);
+            twice(fn(x) { x + 1 }, 1);
+            len([]);
+        ";
+
+        let mut eval = Eval::with_options(Options {
+            profile: true,
+            ..Default::default()
+        });
+        eval.eval(Parser::new(Lexer::new(input)).parse_program().unwrap())
+            .unwrap();
+
+        let profiler = eval.profiler().unwrap();
+        assert_eq!(profiler.get("fib").unwrap().calls, 177);
+        assert_eq!(profiler.get("twice").unwrap().calls, 1);
+        assert_eq!(profiler.get("f").unwrap().calls, 2);
+        assert_eq!(profiler.get("len").unwrap().calls, 1);
+        assert!(profiler.to_string().starts_with("function"));
+
+        assert!(Eval::new().profiler().is_none());
+    }
+
+    #[test]
+    fn observer_callbacks() {
+        struct Recorder(Rc<RefCell<Vec<String>>>);
+
+        impl EvalObserver for Recorder {
+            fn on_statement(&mut self, _: &Statement) {
+                self.0.borrow_mut().push("statement".into());
+            }
+
+            fn on_call(&mut self, function: &Object, args: &[Object]) {
+                self.0
+                    .borrow_mut()
+                    .push(format!("call {} with {}", function, args.len()));
+            }
+
+            fn on_return(&mut self, _: &Object, value: &Object) {
+                self.0.borrow_mut().push(format!("return {}", value));
+            }
+
+            fn on_error(&mut self, error: &anyhow::Error) {
+                self.0.borrow_mut().push(format!("error {}", error));
+            }
+        }
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut eval = Eval::new();
+        eval.add_observer(Box::new(Recorder(events.clone())));
+
+        let input = "let double = fn(x) { x * 2 }; len([double(2)]); foo";
+        let result = eval.eval(Parser::new(Lexer::new(input)).parse_program().unwrap());
+        assert!(result.is_err());
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "statement",
+                "statement",
+                "call fn(x) with 1",
+                "statement",
+                "return 4",
+                "call builtin len with 1",
+                "return 1",
+                "statement",
+                "error Identifier foo not found!",
+            ]
+        );
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut eval = Eval::new();
+        let program = |input| Parser::new(Lexer::new(input)).parse_program().unwrap();
+
+        eval.eval(program("let x = 1;")).unwrap();
+
+        eval.interrupt_handle().interrupt();
+        assert_eq!(
+            eval.eval(program("x = 2; x = 3;")).unwrap_err().to_string(),
+            "Interrupted!"
+        );
+
+        assert_eq!(eval.eval(program("x")).unwrap(), Object::Int(1));
+
+        eval.interrupt_handle().interrupt();
+        eval.interrupt_handle().reset();
+        assert_eq!(eval.eval(program("x")).unwrap(), Object::Int(1));
+    }
+
+    #[test]
+    fn interrupt_from_another_thread() {
+        let mut eval = Eval::new();
+        let handle = eval.interrupt_handle();
+        let program = Parser::new(Lexer::new(
+            "let f = fn(n) { if (n > 0) { f(n - 1); f(n - 1) } }; f(60)",
+        ))
+        .parse_program()
+        .unwrap();
+
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            handle.interrupt();
+        });
+        assert_eq!(eval.eval(program).unwrap_err().to_string(), "Interrupted!");
+        interrupter.join().unwrap();
+    }
+
+    #[test]
+    fn limits() {
+        let tests = HashMap::from([
+            (
+                "[1, 2, 3, 4]",
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(1),
+                        Object::Int(2),
+                        Object::Int(3),
+                        Object::Int(4),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                "[1, 2, 3, 4, 5]",
+                Err(anyhow!("5 elements exceed the array length limit of 4!")),
+            ),
+            (
+                "let a = [1, 2, 3]; [...a, ...a]",
+                Err(anyhow!("6 elements exceed the array length limit of 4!")),
+            ),
+            (
+                "push([1, 2, 3, 4], 5)",
+                Err(anyhow!("5 elements exceed the array length limit of 4!")),
+            ),
+            (
+                "to_array(0..10)",
+                Err(anyhow!("10 elements exceed the array length limit of 4!")),
+            ),
+            (
+                r#"{"a": 1, "b": 2, "c": 3}"#,
+                Err(anyhow!("3 pairs exceed the hash size limit of 2!")),
+            ),
+            (
+                r#"{"a": 1, "b": 2} + {"c": 3}"#,
+                Err(anyhow!("3 pairs exceed the hash size limit of 2!")),
+            ),
+            (
+                r#"len(merge({"a": 1, "b": 2}, {"b": 3}))"#,
+                Ok(Object::Int(2)),
+            ),
+            (
+                r#""abcd" + "efghi""#,
+                Err(anyhow!("9 bytes exceed the string length limit of 8!")),
+            ),
+            (
+                r#"format("{:>20}", 1)"#,
+                Err(anyhow!("20 bytes exceed the string length limit of 8!")),
+            ),
+            (
+                r#""abc" * 3"#,
+                Err(anyhow!("9 bytes exceed the string length limit of 8!")),
+            ),
+            (
+                r#"join(["abcd", "efgh"], "")"#,
+                Ok(Object::String("abcdefgh".into())),
+            ),
+            (
+                "let a = 1; let b = 2; let c = 3",
+                Err(anyhow!("3 variables exceed the scope size limit of 2!")),
+            ),
+            (
+                "let f = fn(x, y) { let z = x + y; z }; f(1, 2)",
+                Err(anyhow!("3 variables exceed the scope size limit of 2!")),
+            ),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                limits: Limits {
+                    max_array_len: 4,
+                    max_hash_len: 2,
+                    max_string_len: 8,
+                    max_env_entries: 2,
+                },
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn seeded_random() {
+        let draw = |source: &str| {
+            let program = Parser::new(Lexer::new(source)).parse_program().unwrap();
+            Eval::new().eval(program).unwrap()
+        };
+
+        let source = "seed(42); map(to_array(0..20), fn(_) { random_int(1, 6) })";
+        let rolls = draw(source);
+        assert_eq!(rolls, draw(source));
+        let Object::Array(rolls) = rolls else {
+            panic!("expected an array, got {}", rolls);
+        };
+        assert!(rolls.iter().all(|roll| matches!(roll, Object::Int(1..=6))));
+
+        assert_ne!(
+            draw("seed(1); random_int(0, 1000000)"),
+            draw("seed(2); random_int(0, 1000000)")
+        );
+        assert_eq!(draw("random_int(3, 3)"), Object::Int(3));
+        assert_eq!(
+            draw("seed(7); let gen = fn() { yield random_int(0, 1000000) }; let a = next(gen()); seed(7); a == random_int(0, 1000000)"),
+            Object::Bool(true)
+        );
+
+        let tests = HashMap::from([
+            (
+                "random_int(2, 1)",
+                Err(anyhow!("Cannot draw a random int between 2 and 1!")),
+            ),
+            (
+                "random_int(1, \"2\")",
+                Err(anyhow!("Builtin random_int is not defined for string!")),
+            ),
+            (
+                "seed(1.5)",
+                Err(anyhow!("Builtin seed is not defined for float!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn number_parsing() {
+        let tests = HashMap::from([
+            (r#"parse_int("42", 10)"#, Ok(Object::Int(42))),
+            (r#"parse_int(" -ff ", 16)"#, Ok(Object::Int(-255))),
+            (r#"parse_int("101", 2)"#, Ok(Object::Int(5))),
+            (r#"parse_int("12a", 10)"#, Ok(Object::Null)),
+            (r#"parse_int("", 10)"#, Ok(Object::Null)),
+            (r#"parse_int("9223372036854775808", 10)"#, Ok(Object::Null)),
+            (
+                r#"parse_int("1", 37)"#,
+                Err(anyhow!("Invalid base 37, expected 2 to 36!")),
+            ),
+            (
+                "parse_int(1, 10)",
+                Err(anyhow!("Builtin parse_int is not defined for int!")),
+            ),
+            (r#"parse_float("1.5")"#, Ok(Object::Float(1.5))),
+            (r#"parse_float(" -2e3 ")"#, Ok(Object::Float(-2000.0))),
+            (r#"parse_float("7")"#, Ok(Object::Float(7.0))),
+            (r#"parse_float("1,5")"#, Ok(Object::Null)),
+            (r#"parse_float("abc")"#, Ok(Object::Null)),
+            (
+                "parse_float(null)",
+                Err(anyhow!("Builtin parse_float is not defined for null!")),
+            ),
+            ("to_fixed(3.14159, 2)", Ok(Object::String("3.14".into()))),
+            ("to_fixed(2.5, 0)", Ok(Object::String("2".into()))),
+            ("to_fixed(-1, 3)", Ok(Object::String("-1.000".into()))),
+            (
+                "to_fixed(1.0, -1)",
+                Err(anyhow!("Builtin to_fixed needs digits from 0 to 100!")),
+            ),
+            (
+                "to_fixed(1.5, 100000)",
+                Err(anyhow!("Builtin to_fixed needs digits from 0 to 100!")),
+            ),
+            ("len(to_fixed(1, 100))", Ok(Object::Int(102))),
+            (
+                r#"to_fixed("1", 2)"#,
+                Err(anyhow!("Builtin to_fixed is not defined for string!")),
+            ),
+        ]);
+
+        test(tests);
+    }
+
+    #[test]
+    fn exec() {
+        test(HashMap::from([(
+            r#"exec("echo", ["hi"])"#,
+            Err(anyhow!(
+                "Builtin exec needs the exec capability, which is disabled!"
+            )),
+        )]));
+
+        let tests = HashMap::from([
+            (
+                r#"let result = exec("sh", ["-c", "echo out; echo err >&2; exit 3"]);
+                [result["status"], result["stdout"], result["stderr"]]"#,
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(3),
+                        Object::String("out\n".into()),
+                        Object::String("err\n".into()),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                r#"exec("echo", [1])"#,
+                Err(anyhow!("Arguments to exec must be strings, not int!")),
+            ),
+            (
+                r#"exec("echo", "hi")"#,
+                Err(anyhow!("Builtin exec is not defined for string!")),
+            ),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                capabilities: Capabilities {
+                    exec: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn capabilities() {
+        let denied = |builtin: &str, capability: &str| {
+            Err(anyhow!(
+                "Builtin {} needs the {} capability, which is disabled!",
+                builtin,
+                capability
+            ))
+        };
+        let tests = HashMap::from([
+            (
+                r#"async_read_file("Cargo.toml")"#,
+                denied("async_read_file", "fs"),
+            ),
+            (r#"getenv("HOME")"#, denied("getenv", "env")),
+            (r#"exec("true", [])"#, denied("exec", "exec")),
+        ]);
+        test(tests);
+
+        let outside = PathBuf::from("Cargo.toml").canonicalize().unwrap();
+        let tests = HashMap::from([
+            (
+                r#"len(await async_read_file("src/main.rs")) > 0"#,
+                Ok(Object::Bool(true)),
+            ),
+            (
+                r#"async_read_file("src/../Cargo.toml")"#,
+                Err(anyhow!(
+                    "Builtin async_read_file cannot access {}, which is outside the allowed directories!",
+                    outside.display()
+                )),
+            ),
+            (r#"getenv("MONKEY_SURELY_UNSET")"#, Ok(Object::Null)),
+            ("sleep_async(1)", denied("sleep_async", "time")),
+        ]);
+        test_with_options(
+            tests,
+            Options {
+                capabilities: Capabilities {
+                    fs: vec![PathBuf::from("src")],
+                    env: true,
+                    time: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+
+        let mut eval = Eval::with_capabilities(Capabilities::all());
+        let program = Parser::new(Lexer::new(
+            r#"len(await async_read_file("Cargo.toml")) > 0"#,
+        ))
+        .parse_program()
+        .unwrap();
+        assert_eq!(eval.eval(program).unwrap(), Object::Bool(true));
+    }
+
+    #[test]
+    fn imports() {
+        let dir = std::env::temp_dir().join(format!("monkey-imports-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("utils")).unwrap();
+        std::fs::write(
+            dir.join("counter.monkey"),
+            "let count = 0; let bump = fn() { count = count + 1; count }",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("utils/strings.monkey"),
+            r#"import "list"; let shout = fn(s) { s + "!" }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("math.monkey"), "let pi = 3").unwrap();
+        std::fs::write(dir.join("a.monkey"), r#"import "b""#).unwrap();
+        std::fs::write(dir.join("b.monkey"), r#"import "a""#).unwrap();
+        std::fs::write(dir.join("broken.monkey"), "let = 1").unwrap();
+
+        let missing = format!(
+            "Module nope not found, searched: {}, the standard library!",
+            dir.join("nope.monkey").display()
+        );
+        let tests = HashMap::from([
+            (
+                r#"import "math"; [pi, sqrt]"#,
+                Err(anyhow!("Identifier sqrt not found!")),
+            ),
+            (
+                r#"import "utils/strings"; [shout("hi"), sum([1, 2])]"#,
+                Ok(Object::Array(
+                    vec![Object::String("hi!".into()), Object::Int(3)].into(),
+                )),
+            ),
+            (
+                r#"import "counter"; bump(); import "counter"; bump()"#,
+                Ok(Object::Int(2)),
+            ),
+            (
+                r#"import "list" as l; import "counter" as c; c.bump(); [l.sum([1, 2]), c.bump(), [3, 4] |> l.reverse]"#,
+                Ok(Object::Array(
+                    vec![
+                        Object::Int(3),
+                        Object::Int(2),
+                        Object::Array(vec![Object::Int(4), Object::Int(3)].into()),
+                    ]
+                    .into(),
+                )),
+            ),
+            (
+                r#"import "math" as m; import "math" as n; [m == n, format("{}", m)]"#,
+                Ok(Object::Array(
+                    vec![Object::Bool(true), Object::String("module math".into())].into(),
+                )),
+            ),
+            (
+                r#"from "utils/strings" import shout; [shout("a"), sum]"#,
+                Err(anyhow!("Identifier sum not found!")),
+            ),
+            (
+                r#"from "list" import sum, nope"#,
+                Err(anyhow!("Module list doesn't define nope!")),
+            ),
+            (
+                r#"import "list" as l; l.nope"#,
+                Err(anyhow!("Module list doesn't define nope!")),
+            ),
+            (
+                r#"let h = {"a": 1}; h.a"#,
+                Err(anyhow!("Attribute access is not defined for hash!")),
+            ),
+            ("import \"nope\"", Err(anyhow!(missing))),
+            (
+                r#"import "a""#,
+                Err(anyhow!(
+                    "Circular import of {}!",
+                    dir.join("a.monkey").display()
+                )),
+            ),
+            (
+                r#"import "../etc/passwd""#,
+                Err(anyhow!(
+                    "Invalid module name ../etc/passwd, expected a relative path without ..!"
+                )),
+            ),
+        ]);
+
+        test_with_options(
+            tests,
+            Options {
+                module_paths: vec![dir.clone()],
+                ..Default::default()
+            },
+        );
+
+        let mut eval = Eval::with_options(Options {
+            module_paths: vec![dir.clone()],
+            ..Default::default()
+        });
+        let program = Parser::new(Lexer::new(r#"import "broken""#))
+            .parse_program()
+            .unwrap();
+        assert!(eval
+            .eval(program)
+            .unwrap_err()
+            .to_string()
+            .starts_with(&format!(
+                "In module {}: ",
+                dir.join("broken.monkey").display()
+            )));
+
+        let program = Parser::new(Lexer::new(r#"import "math"; [sqrt(16.0), gcd(12, 18)]"#))
+            .parse_program()
+            .unwrap();
+        assert_eq!(
+            Eval::new().eval(program).unwrap(),
+            Object::Array(vec![Object::Float(4.0), Object::Int(6)].into())
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reentrant_eval() {
+        let tests = HashMap::from([
+            (r#"eval("1 + 2")"#, Ok(Object::Int(3))),
+            (r#"eval("let y = 5"); y"#, Ok(Object::Int(5))),
+            (
+                r#"let f = fn(x) { let double = 2; eval("x * double") }; f(21)"#,
+                Ok(Object::Int(42)),
+            ),
+            (
+                r#"map([1, 2], fn(x) { eval("map([x], fn(y) { y + 1 })")[0] })"#,
+                Ok(Object::Array(vec![Object::Int(2), Object::Int(3)].into())),
+            ),
+            (r#"eval("return 1; 2") + 1"#, Ok(Object::Int(2))),
+            (r#"eval("")"#, Ok(Object::Null)),
+            (
+                "eval(1)",
+                Err(anyhow!("Builtin eval is not defined for int!")),
+            ),
+        ]);
+
+        test(tests);
+
+        test_with_options(
+            HashMap::from([(
+                r#"let f = fn() { eval("f()") }; f()"#,
+                Err(anyhow!("Stack overflow: more than 20 nested calls!")),
+            )]),
+            Options {
+                max_call_depth: 20,
+                ..Default::default()
+            },
+        );
+
+        let mut eval = Eval::new();
+        let program = Parser::new(Lexer::new(
+            r#"let inner = fn() { -true }; let outer = fn() { eval("inner()") }; outer()"#,
+        ))
+        .parse_program()
+        .unwrap();
+        assert!(eval.eval(program).is_err());
+        assert_eq!(eval.stack_trace(), ["inner", "outer"]);
+    }
+
+    #[test]
+    fn single_expression() -> Result<()> {
+        let mut eval = Eval::new();
+        let program = Parser::new(Lexer::new(r#"let x = 5; let status = "ok""#)).parse_program()?;
+        eval.eval(program)?;
+
+        for (source, expected) in [
+            (r#"x > 3"#, Object::Bool(true)),
+            (r#"status == "ok""#, Object::Bool(true)),
+            (
+                r#"if (x > 10) { "big" } else { status }"#,
+                Object::String("ok".into()),
+            ),
+        ] {
+            assert_eq!(
+                eval.eval_expression(Parser::parse_expression_str(source)?)?,
+                expected
+            );
+        }
+
+        let error = eval.eval_expression(Parser::parse_expression_str("missing + 1")?);
+        assert!(error.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn heap_stats() -> Result<()> {
+        let mut eval = Eval::new();
+        let program = Parser::new(Lexer::new(
+            r#"let xs = [1, "a", {"k": [(2, 3)]}]; let make = fn() { let f = fn() { f }; 0 };"#,
+        ))
+        .parse_program()?;
+        eval.eval(program)?;
+
+        let before = eval.heap_stats();
+        assert_eq!(
+            HeapStats {
+                live_envs: 0,
+                live_functions: 0,
+                ..before.clone()
+            },
+            HeapStats {
+                envs: 1,
+                functions: 1,
+                arrays: 2,
+                tuples: 1,
+                hashes: 1,
+                strings: 2,
+                ..Default::default()
+            }
+        );
+
+        // Each call leaves behind a scope holding a closure over that same scope.
+        let program = Parser::new(Lexer::new("make(); make(); make()")).parse_program()?;
+        eval.eval(program)?;
+        let after = eval.heap_stats();
+        assert_eq!(after.live_envs, before.live_envs + 3);
+        assert_eq!(after.live_functions, before.live_functions + 3);
+        assert_eq!(after.envs, before.envs);
+        assert_eq!(after.functions, before.functions);
+
+        let program = Parser::new(Lexer::new(r#"stats()["envs"]"#)).parse_program()?;
+        assert_eq!(eval.eval(program)?, Object::Int(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn register_infix() -> Result<()> {
+        /// The elements of `left` that aren't in `right`.
+        fn without(_: &Eval, _: Infix, left: &Object, right: &Object) -> Result<Object> {
+            match (left, right) {
+                (Object::Array(left), Object::Array(right)) => Ok(Object::Array(
+                    left.iter()
+                        .filter(|element| !right.iter().any(|other| element.equals(other)))
+                        .cloned()
+                        .collect(),
+                )),
+                _ => unreachable!(),
+            }
+        }
+
+        let mut eval = Eval::new();
+        eval.register_infix(Infix::Minus, "array", "array", without);
+
+        let program = Parser::new(Lexer::new("[1, 2, 3, 2] - [2]")).parse_program()?;
+        assert_eq!(
+            eval.eval(program)?,
+            Object::Array(vec![Object::Int(1), Object::Int(3)].into())
+        );
+
+        // Generators evaluate with the same table.
+        let program = Parser::new(Lexer::new(
+            "let gen = fn() { yield [1, 2] - [1] }; next(gen())",
+        ))
+        .parse_program()?;
+        assert_eq!(
+            eval.eval(program)?,
+            Object::Array(vec![Object::Int(2)].into())
+        );
+
+        let program = Parser::new(Lexer::new("[1] * [2]")).parse_program()?;
+        assert_eq!(
+            eval.eval(program).unwrap_err().to_string(),
+            "Infix operator * not found for the operands: array & array!"
+        );
+
+        Ok(())
+    }
+    #[test]
+    fn register_builtin() -> Result<()> {
+        let mut eval = Eval::new();
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        eval.register_builtin("query", 1, move |_, args| {
+            counter.set(counter.get() + 1);
+            Ok(Object::String(format!("rows of {}", args[0]).into()))
+        });
+        eval.register_builtin("len", 1, |_, _| Ok(Object::Int(-1)));
+
+        let run = |eval: &mut Eval, source: &str| -> Result<Object> {
+            eval.eval(Parser::new(Lexer::new(source)).parse_program()?)
+        };
+        assert_eq!(
+            run(&mut eval, r#"map(["a", "b"], query)"#)?,
+            Object::Array(
+                vec![
+                    Object::String("rows of a".into()),
+                    Object::String("rows of b".into()),
+                ]
+                .into()
+            )
+        );
+        assert_eq!(calls.get(), 2);
+        assert_eq!(run(&mut eval, "len([1])")?, Object::Int(-1));
+        assert_eq!(
+            run(&mut eval, "query + 1").unwrap_err().to_string(),
+            "Infix operator + not found for the operands: builtin & int!"
+        );
+        assert_eq!(
+            run(&mut eval, "query()").unwrap_err().to_string(),
+            "Wrong number of arguments. Expected: 1. Given: 0"
+        );
+        assert_eq!(
+            run(&mut eval, "quer").unwrap_err().to_string(),
+            "Identifier quer not found! Did you mean `query`?"
+        );
+        assert_eq!(
+            run(&mut eval, "let query = fn(x) { x }; query(1)")?,
+            Object::Int(1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_from_rust() -> Result<()> {
+        let mut eval = Eval::new();
+        let program = Parser::new(Lexer::new(
+            r#"let greet = fn(name) { "Hi " + name }; let n = 1;"#,
+        ))
+        .parse_program()?;
+        eval.eval(program)?;
+
+        assert_eq!(
+            eval.call("greet", vec![Object::from("Ana")])?,
+            Object::from("Hi Ana")
+        );
+        assert_eq!(eval.call("len", vec![Object::from("Ana")])?, Object::Int(3));
+        assert_eq!(
+            eval.call("greet", vec![]).unwrap_err().to_string(),
+            "Wrong number of arguments. Expected: 1. Given: 0"
+        );
+        assert_eq!(
+            eval.call("n", vec![]).unwrap_err().to_string(),
+            "1 is not a valid function!"
+        );
+        assert_eq!(
+            eval.call("gret", vec![]).unwrap_err().to_string(),
+            "Identifier gret not found! Did you mean `greet`?"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_steps() -> Result<()> {
+        let mut eval = Eval::new();
+        assert_eq!(
+            eval.run_steps(1).unwrap_err().to_string(),
+            "No program loaded to run!"
+        );
+
+        let program = Parser::new(Lexer::new(
+            "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; let total = sum(50); total",
+        ))
+        .parse_program()?;
+        eval.load(program)?;
+
+        let mut pending = 0;
+        let result = loop {
+            match eval.run_steps(10)? {
+                StepResult::Pending => pending += 1,
+                StepResult::Done(result) => break result,
+            }
+        };
+        assert_eq!(result, Object::Int(1275));
+        assert!(pending > 10);
+        assert_eq!(eval.lookup("total")?, Object::Int(1275));
+        assert!(eval.run_steps(1).is_err());
+
+        let program = Parser::new(Lexer::new("let x = 1; x + y")).parse_program()?;
+        eval.load(program)?;
+        assert_eq!(eval.run_steps(0)?, StepResult::Pending);
+        assert_eq!(
+            eval.run_steps(100).unwrap_err().to_string(),
+            "Identifier y not found!"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn external_values() -> Result<()> {
+        struct Handle(i64);
+
+        let mut eval = Eval::new();
+        eval.register_builtin("open", 1, |_, args| match &args[0] {
+            Object::Int(fd) => Ok(Object::external(Handle(*fd))),
+            _ => unreachable!(),
+        });
+        eval.register_builtin("fd", 1, |_, args| {
+            Ok(Object::Int(args[0].expect_external::<Handle>()?.0))
+        });
+
+        let run = |eval: &mut Eval, source: &str| -> Result<Object> {
+            eval.eval(Parser::new(Lexer::new(source)).parse_program()?)
+        };
+        assert_eq!(
+            run(&mut eval, "let handle = open(3); fd(handle)")?,
+            Object::Int(3)
+        );
+        assert_eq!(
+            run(&mut eval, "[handle == handle, handle == open(3)]")?,
+            Object::Array(vec![Object::Bool(true), Object::Bool(false)].into())
+        );
+        assert_eq!(
+            run(&mut eval, "handle")?.to_string(),
+            "external interpreter::eval::test::external_values::Handle"
+        );
+        assert_eq!(
+            run(&mut eval, "fd(1)").unwrap_err().to_string(),
+            "Expected interpreter::eval::test::external_values::Handle, got int!"
+        );
+        assert!(Object::external(1_u8).downcast_ref::<i64>().is_none());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,95 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Modules shipped inside the interpreter, found when no directory has a module of that name.
+static STDLIB: &[(&str, &str)] = &[
+    ("list", include_str!("../stdlib/list.monkey")),
+    ("math", include_str!("../stdlib/math.monkey")),
+];
+
+/// Environment variable with extra directories to search for modules, separated like `PATH`.
+pub const MONKEY_PATH: &str = "MONKEY_PATH";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Location {
+    File(PathBuf),
+    Stdlib(&'static str),
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Stdlib(name) => write!(f, "<stdlib>/{}", name),
+        }
+    }
+}
+
+/// Source of a module, before it is evaluated.
+pub struct Source {
+    pub location: Location,
+    pub source: Cow<'static, str>,
+}
+
+/// Where `import` looks for modules: `dir`, usually the directory of the script being run,
+/// followed by the directories in `MONKEY_PATH`.
+pub fn search_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![dir.to_path_buf()];
+    if let Some(monkey_path) = std::env::var_os(MONKEY_PATH) {
+        paths.extend(
+            std::env::split_paths(&monkey_path).filter(|path| !path.as_os_str().is_empty()),
+        );
+    }
+    paths
+}
+
+/// Finds the module `import name` refers to: `name.monkey` in the first of `paths` that has
+/// it, or else the standard library module called `name`. Names are relative paths without
+/// `..`, like `utils` or `utils/strings`, so imports can't reach outside the search paths.
+pub fn resolve(name: &str, paths: &[PathBuf]) -> Result<Source> {
+    let relative = PathBuf::from(format!("{}.monkey", name));
+    if name.is_empty()
+        || !Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!(
+            "Invalid module name {}, expected a relative path without ..!",
+            name
+        );
+    }
+
+    let mut searched = vec![];
+    for dir in paths {
+        let path = dir.join(&relative);
+        if path.is_file() {
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read module {}", path.display()))?;
+            return Ok(Source {
+                location: Location::File(path),
+                source: Cow::Owned(source),
+            });
+        }
+        searched.push(path.display().to_string());
+    }
+
+    if let Some((name, source)) = STDLIB.iter().find(|(module, _)| *module == name) {
+        return Ok(Source {
+            location: Location::Stdlib(name),
+            source: Cow::Borrowed(source),
+        });
+    }
+    searched.push(String::from("the standard library"));
+
+    bail!(
+        "Module {} not found, searched: {}!",
+        name,
+        searched.join(", ")
+    )
+}
@@ -1,46 +1,908 @@
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::{
+    any::{type_name, Any},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
 
-use crate::ast::{BlockStatement, Identifier};
+use anyhow::{anyhow, bail, Result};
+use corosensei::{stack::DefaultStack, Coroutine, CoroutineResult, Yielder};
 
-use super::env::Env;
+use crate::{
+    ast::{BlockStatement, Expression, Identifier, Literal, Statement},
+    symbol::Symbol,
+};
+
+use super::{
+    builtins::{Builtin, HostBuiltin, Runtime},
+    env::Env,
+    pretty::{Pretty, PrettyOptions},
+    stats::Live,
+};
+
+#[cfg(feature = "persistent")]
+use super::vector::Vector;
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Object {
     Int(i64),
+    Float(f64),
     Bool(bool),
-    String(String),
+    /// Shared so that passing a string around never copies it.
+    String(Rc<str>),
+    /// Binary data, e.g. `b"abc"`. Shared like strings.
+    Bytes(Rc<[u8]>),
     Null,
     ReturnValue(Box<Object>),
     Empty,
-    Function(Vec<Identifier>, BlockStatement, Rc<RefCell<Env>>),
+    Function(Rc<Function>),
+    Builtin(&'static Builtin),
+    Array(Elements),
+    Tuple(Elements),
+    Hash(Pairs),
+    /// Distinct hashable values (see `hashable`), in no particular order.
+    Set(HashSet<Object>),
+    Generator(Rc<Generator>),
+    Thunk(Rc<Thunk>),
+    Callable(Rc<Callable>),
+    /// Result of an async builtin, see `Promise`.
+    Promise(Rc<Promise>),
+    /// A module imported with `import "name" as m`.
+    Module(Rc<Module>),
+    /// The ints from `start` up to, but excluding, `end`. Never materialized unless converted
+    /// to an array.
+    Range(i64, i64),
+    /// A value of the embedding application, see `Object::external`.
+    External(Rc<External>),
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Function {
+    pub params: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub generator: bool,
+    /// Captured by reference, so a closure observes later assignments to the
+    /// variables it closes over.
+    pub env: Rc<RefCell<Env>>,
+    pub live: Live,
+}
+
+/// The global scope of an imported module, whose bindings are read with `m.name`.
+#[derive(Debug)]
+pub struct Module {
+    /// As the import statement named it.
+    pub name: String,
+    pub scope: Rc<RefCell<Env>>,
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+/// Value of the application embedding the interpreter, such as a file or a game entity, that
+/// Monkey code passes around between its builtins without looking into it.
+pub struct External {
+    /// Rust type of the value, shown when it is printed.
+    pub type_name: &'static str,
+    value: Box<dyn Any>,
+}
+
+impl PartialEq for External {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for External {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "External({})", self.type_name)
+    }
+}
+
+impl Module {
+    pub fn get(&self, name: Symbol) -> Result<Object> {
+        match self.scope.borrow().get(name) {
+            Some(value) => Ok(value),
+            None => bail!("Module {} doesn't define {}!", self.name, name),
+        }
+    }
+}
+
+/// Native stack of each generator. Generators run with a lower maximum call depth to fit in it.
+pub const GENERATOR_STACK_SIZE: usize = 8 * 1024 * 1024;
+pub const GENERATOR_MAX_CALL_DEPTH: usize = 256;
+
+/// Suspended call to a function containing `yield`. Its body runs on its own stack, so it can be
+/// paused at a `yield` from any depth of the evaluator and resumed by `next`.
+pub struct Generator {
+    /// `None` once the body has finished.
+    coroutine: RefCell<Option<Coroutine<(), Object, Result<Object>>>>,
+}
+
+impl Generator {
+    pub fn new(
+        body: impl FnOnce(&Yielder<(), Object>) -> Result<Object> + 'static,
+    ) -> Result<Self> {
+        let stack = DefaultStack::new(GENERATOR_STACK_SIZE)?;
+
+        Ok(Self {
+            coroutine: RefCell::new(Some(Coroutine::with_stack(stack, |yielder, ()| {
+                body(yielder)
+            }))),
+        })
+    }
+
+    /// Runs the body up to its next `yield` and returns the yielded value, or `None` once the
+    /// body has finished.
+    pub fn resume(&self) -> Result<Option<Object>> {
+        let Ok(mut coroutine) = self.coroutine.try_borrow_mut() else {
+            bail!("Generator is already running!");
+        };
+        let Some(running) = coroutine.as_mut() else {
+            return Ok(None);
+        };
+
+        match running.resume(()) {
+            CoroutineResult::Yield(value) => Ok(Some(value)),
+            CoroutineResult::Return(result) => {
+                coroutine.take();
+                result.map(|_| None)
+            }
+        }
+    }
+}
+
+impl PartialEq for Generator {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Generator")
+    }
+}
+
+/// Value an async builtin produces in the background, e.g. the contents of a file another thread
+/// is reading. `await` gets it, once the promise has settled.
+#[derive(Debug)]
+pub struct Promise {
+    state: RefCell<PromiseState>,
+}
+
+#[derive(Debug)]
+enum PromiseState {
+    Sleeping(Instant),
+    /// Waiting for a thread to send the string it produced, or an error message.
+    Working(Receiver<Result<String, String>>),
+    Settled(Result<Object, String>),
+}
+
+impl Promise {
+    /// Settles with null once `duration` has passed.
+    pub fn sleep(duration: Duration) -> Self {
+        Self {
+            state: RefCell::new(PromiseState::Sleeping(Instant::now() + duration)),
+        }
+    }
+
+    /// Runs `work` on a thread of its own, settling with the string it returns.
+    pub fn spawn(work: impl FnOnce() -> Result<String, String> + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(work()));
+
+        Self {
+            state: RefCell::new(PromiseState::Working(receiver)),
+        }
+    }
+
+    /// The result once the promise has settled, without blocking.
+    pub fn poll(&self) -> Option<Result<Object>> {
+        self.settle(false)
+    }
+
+    /// Blocks until the promise settles.
+    pub fn wait(&self) -> Result<Object> {
+        self.settle(true)
+            .expect("blocking until the promise settles")
+    }
+
+    fn settle(&self, block: bool) -> Option<Result<Object>> {
+        let mut state = self.state.borrow_mut();
+        let result = match &*state {
+            PromiseState::Sleeping(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if !left.is_zero() {
+                    if !block {
+                        return None;
+                    }
+                    thread::sleep(left);
+                }
+                Ok(Object::Null)
+            }
+            PromiseState::Working(receiver) => {
+                let received = match block {
+                    true => receiver.recv().map_err(|_| TryRecvError::Disconnected),
+                    false => receiver.try_recv(),
+                };
+                match received {
+                    Ok(result) => result.map(|value| Object::String(value.into())),
+                    Err(TryRecvError::Empty) => return None,
+                    Err(TryRecvError::Disconnected) => Err(String::from("Async builtin failed!")),
+                }
+            }
+            PromiseState::Settled(result) => result.clone(),
+        };
+
+        *state = PromiseState::Settled(result.clone());
+        Some(result.map_err(|message| anyhow!(message)))
+    }
+}
+
+impl PartialEq for Promise {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Function built by a builtin out of other callables.
+#[derive(Debug, PartialEq)]
+pub enum Callable {
+    /// `compose(f, g)`: calls `g` with the arguments, then `f` with its result.
+    Composed(Object, Object),
+    /// `partial(f, a, b)`: calls `f` with `a` and `b` followed by the arguments.
+    Partial(Object, Vec<Object>),
+    /// `memoize(f)`: calls `f`, caching its results by argument values.
+    Memoized(Object, RefCell<HashMap<Vec<Object>, Object>>),
+    /// Builtin of the embedding application, a builtin like any other to Monkey code.
+    Host(HostBuiltin),
+}
+
+/// Delayed call created by `delay`, evaluated at most once by `force`.
+#[derive(Debug)]
+pub struct Thunk {
+    state: RefCell<ThunkState>,
+}
+
+#[derive(Debug)]
+enum ThunkState {
+    Delayed(Object),
+    Forcing,
+    Forced(Object),
+}
+
+impl Thunk {
+    pub fn new(function: Object) -> Self {
+        Self {
+            state: RefCell::new(ThunkState::Delayed(function)),
+        }
+    }
+
+    /// Calls the delayed function the first time, returning the memoized result afterwards. If
+    /// the call fails, the next `force` tries again.
+    pub fn force(&self, runtime: &mut dyn Runtime) -> Result<Object> {
+        let function = match self.state.replace(ThunkState::Forcing) {
+            ThunkState::Delayed(function) => function,
+            ThunkState::Forcing => bail!("Thunk depends on its own value!"),
+            ThunkState::Forced(value) => {
+                self.state.replace(ThunkState::Forced(value.clone()));
+                return Ok(value);
+            }
+        };
+
+        match runtime.call(function.clone(), vec![]) {
+            Ok(value) => {
+                self.state.replace(ThunkState::Forced(value.clone()));
+                Ok(value)
+            }
+            Err(error) => {
+                self.state.replace(ThunkState::Delayed(function));
+                Err(error)
+            }
+        }
+    }
+}
+
+impl PartialEq for Thunk {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Immutable element storage shared between copies of the same array or tuple.
+/// Cloning is O(1), `rest` and `pop` are views into the same buffer and `push`
+/// and `push_front` only copy when the buffer is shared with another value.
+/// With the `persistent` feature that copy is O(log n) instead of O(n), see `Vector`.
+#[derive(Debug, Clone, Default)]
+pub struct Elements {
+    buffer: Buffer,
+    start: usize,
+    end: usize,
+    /// Set by `freeze`, see `Object::freeze`.
+    frozen: bool,
+}
+
+#[cfg(not(feature = "persistent"))]
+type Buffer = Rc<Vec<Object>>;
+#[cfg(feature = "persistent")]
+type Buffer = Vector;
+
+/// Stores `value` at `index`, at most one past the end of the buffer, unless other values
+/// sharing the buffer would see it change. The value is handed back when it isn't stored.
+#[cfg(not(feature = "persistent"))]
+fn write(buffer: &mut Buffer, index: usize, value: Object) -> Result<(), Object> {
+    let Some(buffer) = Rc::get_mut(buffer) else {
+        return Err(value);
+    };
+    if index == buffer.len() {
+        buffer.push(value);
+    } else {
+        buffer[index] = value;
+    }
+    Ok(())
+}
+
+/// Persistent buffers can always be written, copying the nodes other values share.
+#[cfg(feature = "persistent")]
+fn write(buffer: &mut Buffer, index: usize, value: Object) -> Result<(), Object> {
+    if index == buffer.len() {
+        buffer.push(value);
+    } else {
+        buffer.set(index, value);
+    }
+    Ok(())
+}
+
+impl Elements {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Object> {
+        (index < self.len()).then(|| &self.buffer[self.start + index])
+    }
+
+    pub fn first(&self) -> Option<&Object> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&Object> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Object> + ExactSizeIterator {
+        (self.start..self.end).map(|index| &self.buffer[index])
+    }
+
+    pub fn to_vec(&self) -> Vec<Object> {
+        self.iter().cloned().collect()
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// A view of a frozen array is frozen too.
+    pub fn rest(&self) -> Elements {
+        Elements {
+            buffer: self.buffer.clone(),
+            start: (self.start + 1).min(self.end),
+            end: self.end,
+            frozen: self.frozen,
+        }
+    }
+
+    /// Everything but the last element.
+    pub fn pop(&self) -> Elements {
+        Elements {
+            buffer: self.buffer.clone(),
+            start: self.start,
+            end: self.end.saturating_sub(1).max(self.start),
+            frozen: self.frozen,
+        }
+    }
+
+    /// Whatever `pop` left past the end is overwritten.
+    pub fn push(mut self, value: Object) -> Elements {
+        match write(&mut self.buffer, self.end, value) {
+            Ok(()) => {
+                self.end += 1;
+                self
+            }
+            Err(value) => {
+                let mut elements = self.to_vec();
+                elements.push(value);
+                elements.into()
+            }
+        }
+    }
+
+    /// Amortized O(1) like `push`: a copied buffer gets as much room at the front as it has
+    /// elements, filled with placeholders outside the view, which later calls overwrite.
+    pub fn push_front(mut self, value: Object) -> Elements {
+        let value = match self.start.checked_sub(1) {
+            Some(index) => match write(&mut self.buffer, index, value) {
+                Ok(()) => {
+                    self.start = index;
+                    return self;
+                }
+                Err(value) => value,
+            },
+            None => value,
+        };
+
+        let room = self.len().max(1);
+        let mut buffer = Vec::with_capacity(room + self.len());
+        buffer.resize(room - 1, Object::Null);
+        buffer.push(value);
+        buffer.extend(self.iter().cloned());
+        Elements {
+            start: room - 1,
+            end: buffer.len(),
+            buffer: buffer.into(),
+            frozen: self.frozen,
+        }
+    }
+}
+
+impl PartialEq for Elements {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl From<Vec<Object>> for Elements {
+    fn from(elements: Vec<Object>) -> Self {
+        Self {
+            end: elements.len(),
+            buffer: elements.into(),
+            start: 0,
+            frozen: false,
+        }
+    }
+}
+
+impl FromIterator<Object> for Elements {
+    fn from_iter<T: IntoIterator<Item = Object>>(iter: T) -> Self {
+        iter.into_iter().collect::<Vec<_>>().into()
+    }
+}
+
+/// The pairs of a hash, shared between copies of it like `Elements`, so cloning is O(1). Derefs
+/// to the map, which is only changed while building a new hash and is copied first if another
+/// hash shares it. Carries the flag `freeze` sets like `Elements`.
+#[derive(Debug, Clone, Default)]
+pub struct Pairs {
+    map: Rc<HashMap<Object, Object>>,
+    frozen: bool,
+}
+
+impl Pairs {
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+}
+
+impl std::ops::Deref for Pairs {
+    type Target = HashMap<Object, Object>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl std::ops::DerefMut for Pairs {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Rc::make_mut(&mut self.map)
+    }
 }
 
+impl PartialEq for Pairs {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl From<HashMap<Object, Object>> for Pairs {
+    fn from(map: HashMap<Object, Object>) -> Self {
+        Self {
+            map: Rc::new(map),
+            frozen: false,
+        }
+    }
+}
+
+impl FromIterator<(Object, Object)> for Pairs {
+    fn from_iter<T: IntoIterator<Item = (Object, Object)>>(iter: T) -> Self {
+        iter.into_iter().collect::<HashMap<_, _>>().into()
+    }
+}
+
+impl<'a> IntoIterator for &'a Pairs {
+    type Item = (&'a Object, &'a Object);
+    type IntoIter = std::collections::hash_map::Iter<'a, Object, Object>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
+
+impl Eq for Object {}
+
+/// What a hash key or set element is hashed and compared by: the contents of a value that has
+/// nothing else to it. See `Object::hashable`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub enum HashKey<'a> {
+    Int(i64),
+    Bool(bool),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Null,
+    Range(i64, i64),
+    Array(Vec<HashKey<'a>>),
+    Tuple(Vec<HashKey<'a>>),
+}
+
+/// Hashes by `HashKey`, so equal keys hash alike on every run. Values that can't be keys
+/// hash by their type alone.
+impl Hash for Object {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.hashable() {
+            Ok(key) => key.hash(state),
+            Err(_) => std::mem::discriminant(self).hash(state),
+        }
+    }
+}
+
+/// `{:#}` renders large arrays, tuples and hashes over several lines, see `Pretty`.
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return write!(f, "{}", Pretty::new(self, &PrettyOptions::default()));
+        }
+
         match self {
             Self::Int(num) => write!(f, "{}", num),
+            // Debug keeps the `.0` of whole numbers, telling them apart from ints.
+            Self::Float(num) => write!(f, "{:?}", num),
             Self::Bool(bool) => write!(f, "{}", bool),
             Self::String(s) => write!(f, "{}", s),
+            Self::Bytes(bytes) => write!(f, "b\"{}\"", bytes.escape_ascii()),
             Self::Null => write!(f, "NULL"),
             Self::ReturnValue(value) => write!(f, "{}", *value),
             Self::Empty => Ok(()),
-            Self::Function(params, _, _) => {
-                write!(f, "fn({})", params.join(","))
+            Self::Function(function) => {
+                let params = function.params.iter().map(|id| id.0.as_str());
+                write!(f, "fn({})", params.collect::<Vec<_>>().join(","))
+            }
+            Self::Builtin(builtin) => write!(f, "builtin {}", builtin.name),
+            Self::Array(elements) => {
+                let elements = elements.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Self::Tuple(elements) => {
+                let elements = elements.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
+            Self::Hash(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", pairs.join(", "))
             }
+            Self::Set(elements) => {
+                let mut elements = elements.iter().map(Object::inspect).collect::<Vec<_>>();
+                elements.sort();
+                write!(f, "set([{}])", elements.join(", "))
+            }
+            Self::Generator(_) => write!(f, "generator"),
+            Self::Thunk(_) => write!(f, "thunk"),
+            Self::Callable(callable) => match callable.as_ref() {
+                Callable::Composed(outer, inner) => write!(f, "compose({}, {})", outer, inner),
+                Callable::Partial(function, bound) => {
+                    let bound = bound.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                    write!(f, "partial({}, {})", function, bound.join(", "))
+                }
+                Callable::Memoized(function, _) => write!(f, "memoize({})", function),
+                Callable::Host(builtin) => write!(f, "builtin {}", builtin.name),
+            },
+            Self::Promise(_) => write!(f, "promise"),
+            Self::Module(module) => write!(f, "module {}", module.name),
+            Self::Range(start, end) => write!(f, "{}..{}", start, end),
+            Self::External(external) => write!(f, "external {}", external.type_name),
         }
     }
 }
 
 impl Object {
-    pub fn get_type(&self) -> &str {
+    pub fn get_type(&self) -> &'static str {
         match self {
             Object::Int(_) => "int",
+            Object::Float(_) => "float",
             Object::Bool(_) => "bool",
             Object::String(_) => "string",
+            Object::Bytes(_) => "bytes",
             Object::Null => "null",
             Object::ReturnValue(val) => val.get_type(),
             Object::Empty => "empty",
-            Object::Function(_, _, _) => "function",
+            Object::Function(_) => "function",
+            Object::Builtin(_) => "builtin",
+            Object::Array(_) => "array",
+            Object::Tuple(_) => "tuple",
+            Object::Hash(_) => "hash",
+            Object::Set(_) => "set",
+            Object::Generator(_) => "generator",
+            Object::Thunk(_) => "thunk",
+            Object::Callable(callable) => match callable.as_ref() {
+                Callable::Host(_) => "builtin",
+                _ => "function",
+            },
+            Object::Promise(_) => "promise",
+            Object::Module(_) => "module",
+            Object::Range(..) => "range",
+            Object::External(_) => "external",
+        }
+    }
+
+    /// The key of a value compared by its contents alone, so it can key a hash, a set or a
+    /// cache. Functions, hashes and other values with identity or interior mutability can't.
+    pub fn hashable(&self) -> Result<HashKey<'_>> {
+        fn elements(elements: &Elements) -> Result<Vec<HashKey<'_>>> {
+            elements.iter().map(Object::hashable).collect()
+        }
+
+        Ok(match self {
+            Object::Int(num) => HashKey::Int(*num),
+            Object::Bool(bool) => HashKey::Bool(*bool),
+            Object::String(s) => HashKey::String(s),
+            Object::Bytes(bytes) => HashKey::Bytes(bytes),
+            Object::Null => HashKey::Null,
+            Object::Range(start, end) => HashKey::Range(*start, *end),
+            Object::Array(list) => HashKey::Array(elements(list)?),
+            Object::Tuple(list) => HashKey::Tuple(elements(list)?),
+            _ => bail!("Unusable as hash key: {}!", self.get_type()),
+        })
+    }
+
+    /// Monkey source evaluating to an equal value, unlike the display form: strings are quoted
+    /// and hash keys sorted, so the output is stable enough for golden tests. Values without a
+    /// literal syntax, such as functions, fall back to their display form.
+    pub fn inspect(&self) -> String {
+        let list = |elements: &Elements| {
+            elements
+                .iter()
+                .map(Object::inspect)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Object::String(s) => format!("\"{}\"", s),
+            Object::Null => String::from("null"),
+            Object::Builtin(builtin) => builtin.name.to_string(),
+            Object::Callable(callable) => match callable.as_ref() {
+                Callable::Host(builtin) => builtin.name.to_string(),
+                _ => self.to_string(),
+            },
+            Object::ReturnValue(value) => value.inspect(),
+            Object::Array(elements) => format!("[{}]", list(elements)),
+            Object::Tuple(elements) if elements.len() == 1 => format!("({},)", list(elements)),
+            Object::Tuple(elements) => format!("({})", list(elements)),
+            Object::Hash(pairs) => {
+                let mut pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key.inspect(), value.inspect()))
+                    .collect::<Vec<_>>();
+                pairs.sort();
+                format!("{{{}}}", pairs.join(", "))
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// The elements of an array or set, the ints of a range produced one at a time, or the bytes
+    /// of binary data as ints.
+    pub fn iter(&self) -> Option<Box<dyn Iterator<Item = Object> + '_>> {
+        match self {
+            Object::Array(elements) => Some(Box::new(elements.iter().cloned())),
+            Object::Range(start, end) => Some(Box::new((*start..*end).map(Object::Int))),
+            Object::Bytes(bytes) => {
+                Some(Box::new(bytes.iter().map(|&byte| Object::Int(byte.into()))))
+            }
+            Object::Set(elements) => Some(Box::new(elements.iter().cloned())),
+            _ => None,
+        }
+    }
+
+    /// The docstring of a function: a string literal starting its body, when more statements
+    /// follow it. Partial applications and memoized functions have the doc of what they wrap,
+    /// and builtins document how to call them.
+    pub fn doc(&self) -> Option<String> {
+        match self {
+            Object::Function(function) => match function.body.as_slice() {
+                [Statement::Expression(Expression::Literal(Literal::String(doc))), _, ..] => {
+                    Some(doc.to_string())
+                }
+                _ => None,
+            },
+            Object::Callable(callable) => match callable.as_ref() {
+                Callable::Partial(function, _) | Callable::Memoized(function, _) => function.doc(),
+                Callable::Composed(..) | Callable::Host(_) => None,
+            },
+            Object::Builtin(builtin) => Some(builtin.usage()),
+            _ => None,
         }
     }
+
+    /// A copy of an array or hash that the builtins updating one refuse, e.g. `push` and
+    /// `merge`, along with every array and hash inside it.
+    pub fn freeze(&self) -> Object {
+        match self {
+            Object::Array(elements) => Object::Array(Elements {
+                frozen: true,
+                ..elements.iter().map(Object::freeze).collect()
+            }),
+            Object::Tuple(elements) => Object::Tuple(Elements {
+                frozen: true,
+                ..elements.iter().map(Object::freeze).collect()
+            }),
+            Object::Hash(pairs) => Object::Hash(Pairs {
+                frozen: true,
+                ..pairs
+                    .iter()
+                    .map(|(key, value)| (key.freeze(), value.freeze()))
+                    .collect()
+            }),
+            Object::Set(elements) => Object::Set(elements.iter().map(Object::freeze).collect()),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        match self {
+            Object::Array(elements) | Object::Tuple(elements) => elements.is_frozen(),
+            Object::Hash(pairs) => pairs.is_frozen(),
+            _ => false,
+        }
+    }
+
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Object::Function(_) | Object::Builtin(_) | Object::Callable(_)
+        )
+    }
+
+    /// Wraps a value of the embedding application, e.g. a native handle, for builtins it
+    /// registers to give to Monkey code and get back. It is only equal to itself.
+    pub fn external<T: Any>(value: T) -> Self {
+        Object::External(Rc::new(External {
+            type_name: type_name::<T>(),
+            value: Box::new(value),
+        }))
+    }
+
+    /// The value `Object::external` wrapped, if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match self {
+            Object::External(external) => external.value.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Like `downcast_ref`, failing with an error naming the expected and given types.
+    pub fn expect_external<T: Any>(&self) -> Result<&T> {
+        self.downcast_ref().ok_or_else(|| {
+            let given = match self {
+                Object::External(external) => external.type_name,
+                _ => self.get_type(),
+            };
+            anyhow!("Expected {}, got {}!", type_name::<T>(), given)
+        })
+    }
+
+    /// Equality as seen by the `==` operator: arrays, tuples and hashes are
+    /// compared structurally, functions by identity. Ints and floats compare by value, also
+    /// inside arrays and hashes.
+    pub fn equals(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Int(left), Object::Float(right))
+            | (Object::Float(right), Object::Int(left)) => *left as f64 == *right,
+            (Object::Function(left), Object::Function(right)) => Rc::ptr_eq(left, right),
+            (Object::Generator(left), Object::Generator(right)) => Rc::ptr_eq(left, right),
+            (Object::Thunk(left), Object::Thunk(right)) => Rc::ptr_eq(left, right),
+            (Object::Callable(left), Object::Callable(right)) => Rc::ptr_eq(left, right),
+            (Object::Promise(left), Object::Promise(right)) => Rc::ptr_eq(left, right),
+            (Object::Array(left), Object::Array(right))
+            | (Object::Tuple(left), Object::Tuple(right)) => {
+                left.len() == right.len() && left.iter().zip(right.iter()).all(|(l, r)| l.equals(r))
+            }
+            (Object::Hash(left), Object::Hash(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .all(|(key, l)| right.get(key).is_some_and(|r| l.equals(r)))
+            }
+            _ => self == other,
+        }
+    }
+}
+
+// Sharing is observed through the buffer, which is a `Vector` with the persistent feature.
+#[cfg(all(test, not(feature = "persistent")))]
+mod test {
+    use std::{collections::HashMap, rc::Rc};
+
+    use super::{Elements, Object, Pairs};
+
+    #[test]
+    fn hashes_share_pairs() {
+        let pairs = Pairs::from(HashMap::from([(Object::Int(1), Object::Int(2))]));
+        assert!(Rc::ptr_eq(&pairs.map, &pairs.clone().map));
+
+        let mut updated = pairs.clone();
+        updated.insert(Object::Int(3), Object::Int(4));
+        assert!(!Rc::ptr_eq(&pairs.map, &updated.map));
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(updated.len(), 2);
+    }
+
+    #[test]
+    fn elements_share_buffer() {
+        let elements = Elements::from(vec![Object::Int(1), Object::Int(2), Object::Int(3)]);
+
+        let rest = elements.rest();
+        assert!(Rc::ptr_eq(&elements.buffer, &rest.buffer));
+        assert_eq!(rest.to_vec(), [Object::Int(2), Object::Int(3)]);
+        assert_eq!(rest.rest().rest().rest().to_vec(), []);
+
+        let pushed = elements.clone().push(Object::Int(4));
+        assert!(!Rc::ptr_eq(&elements.buffer, &pushed.buffer));
+        assert_eq!(elements.len(), 3);
+        assert_eq!(pushed.len(), 4);
+
+        let unique = Elements::from(vec![Object::Int(1)]);
+        let buffer = Rc::as_ptr(&unique.buffer);
+        assert_eq!(Rc::as_ptr(&unique.push(Object::Int(2)).buffer), buffer);
+    }
+
+    #[test]
+    fn elements_work_as_a_deque() {
+        let mut elements = Elements::from(vec![Object::Int(1)]);
+        for i in 2..=5 {
+            elements = elements.push_front(Object::Int(i));
+        }
+        let buffer = Rc::as_ptr(&elements.buffer);
+        elements = elements.push_front(Object::Int(6));
+        assert_eq!(Rc::as_ptr(&elements.buffer), buffer);
+        assert_eq!(elements.to_vec(), [6, 5, 4, 3, 2, 1].map(Object::Int));
+
+        let popped = elements.pop().pop();
+        drop(elements);
+        assert_eq!(popped.to_vec(), [6, 5, 4, 3].map(Object::Int));
+        let pushed = popped.push(Object::Int(7));
+        assert_eq!(pushed.to_vec(), [6, 5, 4, 3, 7].map(Object::Int));
+        assert_eq!(Rc::as_ptr(&pushed.buffer), buffer);
+
+        let shared = pushed.clone().pop();
+        assert_eq!(
+            shared.push_front(Object::Int(0)).to_vec(),
+            [0, 6, 5, 4, 3].map(Object::Int)
+        );
+        assert_eq!(pushed.to_vec(), [6, 5, 4, 3, 7].map(Object::Int));
+        assert_eq!(Elements::default().pop().to_vec(), []);
+    }
 }
@@ -0,0 +1,21 @@
+use anyhow::Error;
+
+use crate::ast::Statement;
+
+use super::object::Object;
+
+/// Hooks into evaluation for tracing, coverage or debugging tools.
+/// Every callback defaults to doing nothing.
+pub trait EvalObserver {
+    /// Called before each statement, including the ones inside blocks and function bodies.
+    fn on_statement(&mut self, _statement: &Statement) {}
+
+    /// Called before a function or builtin is applied to its evaluated arguments.
+    fn on_call(&mut self, _function: &Object, _args: &[Object]) {}
+
+    /// Called when a function or builtin returns successfully.
+    fn on_return(&mut self, _function: &Object, _value: &Object) {}
+
+    /// Called once when a runtime error aborts the program.
+    fn on_error(&mut self, _error: &Error) {}
+}
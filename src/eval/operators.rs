@@ -0,0 +1,233 @@
+//! Binary operators. `eval_infix` looks each operator up by the types of its two operands, so
+//! supporting a new combination is one more entry in the table rather than another match arm.
+//! Combinations missing from the table fail, except for `==` and `!=`, which compare any two
+//! values, and `in` and `!in`, which look into the containers the language has. `===` and
+//! `!==` never reach the table.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::ast::Infix;
+
+use super::{builtins, floor_div, object::Object, Division, Eval};
+
+/// Evaluates `operator` for two operands of the types it was registered for.
+pub type InfixFn = fn(&Eval, Infix, &Object, &Object) -> Result<Object>;
+
+const NUMERIC: [Infix; 9] = [
+    Infix::Plus,
+    Infix::Minus,
+    Infix::Divide,
+    Infix::FloorDivide,
+    Infix::Product,
+    Infix::Equal,
+    Infix::NotEqual,
+    Infix::GreaterThan,
+    Infix::LessThan,
+];
+
+#[derive(Clone)]
+pub struct Operators {
+    table: HashMap<(Infix, &'static str, &'static str), InfixFn>,
+}
+
+impl Default for Operators {
+    fn default() -> Self {
+        let mut operators = Operators {
+            table: HashMap::new(),
+        };
+
+        operators.register_all(&NUMERIC, "int", "int", integer_infix);
+        operators.register_all(&NUMERIC, "float", "float", float_infix);
+        // Mixing ints and floats promotes the int, so `1 == 1.0` like numbers on paper.
+        operators.register_all(&NUMERIC, "int", "float", float_infix);
+        operators.register_all(&NUMERIC, "float", "int", float_infix);
+        operators.register(Infix::Plus, "string", "string", concat_strings);
+        operators.register(Infix::Product, "string", "int", repeat_string);
+        operators.register(Infix::Product, "int", "string", repeat_string);
+        operators.register(Infix::Plus, "array", "array", concat_arrays);
+        operators.register(Infix::Plus, "hash", "hash", merge_hashes);
+
+        operators
+    }
+}
+
+impl Operators {
+    /// Makes `operator` call `func` for operands of the given types, as named by
+    /// `Object::get_type`, replacing what it did before.
+    pub fn register(
+        &mut self,
+        operator: Infix,
+        left: &'static str,
+        right: &'static str,
+        func: InfixFn,
+    ) {
+        self.table.insert((operator, left, right), func);
+    }
+
+    fn register_all(
+        &mut self,
+        operators: &[Infix],
+        left: &'static str,
+        right: &'static str,
+        func: InfixFn,
+    ) {
+        for &operator in operators {
+            self.register(operator, left, right, func);
+        }
+    }
+
+    pub fn eval(
+        &self,
+        eval: &Eval,
+        operator: Infix,
+        left: &Object,
+        right: &Object,
+    ) -> Result<Object> {
+        if let Some(func) = self
+            .table
+            .get(&(operator, left.get_type(), right.get_type()))
+        {
+            return func(eval, operator, left, right);
+        }
+
+        Ok(match operator {
+            Infix::Equal => Object::Bool(left.equals(right)),
+            Infix::NotEqual => Object::Bool(!left.equals(right)),
+            Infix::In => Object::Bool(contains(operator, left, right)?),
+            Infix::NotIn => Object::Bool(!contains(operator, left, right)?),
+            _ => return Err(not_found(operator, left, right)),
+        })
+    }
+}
+
+fn not_found(operator: Infix, left: &Object, right: &Object) -> anyhow::Error {
+    anyhow!(
+        "Infix operator {} not found for the operands: {} & {}!",
+        operator,
+        left.get_type(),
+        right.get_type()
+    )
+}
+
+/// Whether `container` holds `element`: an equal element of an array, tuple or set, a key of a
+/// hash, a substring of a string or an int within a range.
+fn contains(operator: Infix, element: &Object, container: &Object) -> Result<bool> {
+    Ok(match (element, container) {
+        (_, Object::Array(elements) | Object::Tuple(elements)) => {
+            elements.iter().any(|other| other.equals(element))
+        }
+        (_, Object::Hash(pairs)) => pairs.contains_key(element),
+        (_, Object::Set(elements)) => elements.contains(element),
+        (Object::String(needle), Object::String(haystack)) => haystack.contains(&**needle),
+        (Object::Int(num), Object::Range(start, end)) => (start..end).contains(&num),
+        _ => return Err(not_found(operator, element, container)),
+    })
+}
+
+fn as_float(object: &Object) -> f64 {
+    match object {
+        Object::Int(num) => *num as f64,
+        Object::Float(num) => *num,
+        _ => unreachable!("only registered for numbers"),
+    }
+}
+
+fn integer_infix(eval: &Eval, operator: Infix, left: &Object, right: &Object) -> Result<Object> {
+    let (&Object::Int(left), &Object::Int(right)) = (left, right) else {
+        unreachable!("only registered for ints");
+    };
+
+    if matches!(operator, Infix::Divide | Infix::FloorDivide) && right == 0 {
+        bail!("Division by zero!");
+    }
+
+    Ok(match operator {
+        Infix::Plus => Object::Int(
+            left.checked_add(right)
+                .ok_or_else(|| anyhow!("Integer overflow in {} + {}!", left, right))?,
+        ),
+        Infix::Minus => Object::Int(
+            left.checked_sub(right)
+                .ok_or_else(|| anyhow!("Integer overflow in {} - {}!", left, right))?,
+        ),
+        Infix::Divide => match eval.options.division {
+            Division::Truncate => Object::Int(
+                left.checked_div(right)
+                    .ok_or_else(|| anyhow!("Integer overflow in {} / {}!", left, right))?,
+            ),
+            Division::Floor => Object::Int(
+                floor_div(left, right)
+                    .ok_or_else(|| anyhow!("Integer overflow in {} / {}!", left, right))?,
+            ),
+            Division::Float => Object::Float(left as f64 / right as f64),
+        },
+        Infix::FloorDivide => Object::Int(
+            floor_div(left, right)
+                .ok_or_else(|| anyhow!("Integer overflow in {} // {}!", left, right))?,
+        ),
+        Infix::Product => Object::Int(
+            left.checked_mul(right)
+                .ok_or_else(|| anyhow!("Integer overflow in {} * {}!", left, right))?,
+        ),
+        Infix::Equal | Infix::StrictEqual => Object::Bool(left == right),
+        Infix::GreaterThan => Object::Bool(left > right),
+        Infix::LessThan => Object::Bool(left < right),
+        Infix::NotEqual | Infix::StrictNotEqual => Object::Bool(left != right),
+        Infix::In | Infix::NotIn => unreachable!("not registered for numbers"),
+    })
+}
+
+/// Floats follow IEEE 754, so dividing by zero gives an infinity rather than an error.
+fn float_infix(_: &Eval, operator: Infix, left: &Object, right: &Object) -> Result<Object> {
+    let (left, right) = (as_float(left), as_float(right));
+
+    Ok(match operator {
+        Infix::Plus => Object::Float(left + right),
+        Infix::Minus => Object::Float(left - right),
+        Infix::Divide => Object::Float(left / right),
+        Infix::FloorDivide => Object::Float((left / right).floor()),
+        Infix::Product => Object::Float(left * right),
+        Infix::Equal | Infix::StrictEqual => Object::Bool(left == right),
+        Infix::GreaterThan => Object::Bool(left > right),
+        Infix::LessThan => Object::Bool(left < right),
+        Infix::NotEqual | Infix::StrictNotEqual => Object::Bool(left != right),
+        Infix::In | Infix::NotIn => unreachable!("not registered for numbers"),
+    })
+}
+
+fn concat_strings(eval: &Eval, _: Infix, left: &Object, right: &Object) -> Result<Object> {
+    let (Object::String(left), Object::String(right)) = (left, right) else {
+        unreachable!("only registered for strings");
+    };
+
+    eval.options.limits.check_string(left.len() + right.len())?;
+    Ok(Object::String([&**left, &**right].concat().into()))
+}
+
+fn repeat_string(eval: &Eval, _: Infix, left: &Object, right: &Object) -> Result<Object> {
+    let ((Object::String(string), Object::Int(count))
+    | (Object::Int(count), Object::String(string))) = (left, right)
+    else {
+        unreachable!("only registered for a string and an int");
+    };
+
+    builtins::repeat_string(string, *count, &eval.options.limits)
+}
+
+fn concat_arrays(eval: &Eval, _: Infix, left: &Object, right: &Object) -> Result<Object> {
+    let (Object::Array(left), Object::Array(right)) = (left, right) else {
+        unreachable!("only registered for arrays");
+    };
+
+    builtins::concat_arrays(left, right, &eval.options.limits)
+}
+
+fn merge_hashes(eval: &Eval, _: Infix, left: &Object, right: &Object) -> Result<Object> {
+    let (Object::Hash(left), Object::Hash(right)) = (left, right) else {
+        unreachable!("only registered for hashes");
+    };
+
+    builtins::merge_hashes(left, right, &eval.options.limits)
+}
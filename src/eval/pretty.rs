@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+use super::object::Object;
+
+/// Cutoffs for rendering values to people, e.g. when the REPL echoes a result.
+#[derive(Debug, Clone)]
+pub struct PrettyOptions {
+    /// Arrays, tuples and hashes longer than this many columns are split over several lines.
+    pub width: usize,
+    /// Arrays, tuples and hashes nested deeper than this are elided as `[...]`.
+    pub depth: usize,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            depth: 8,
+        }
+    }
+}
+
+/// Displays an object over several indented lines when it doesn't fit in one.
+/// Objects can't contain themselves (arrays and hashes are values), so the depth cutoff is
+/// all it takes to bound the output.
+pub struct Pretty<'a> {
+    object: &'a Object,
+    options: &'a PrettyOptions,
+}
+
+impl<'a> Pretty<'a> {
+    pub fn new(object: &'a Object, options: &'a PrettyOptions) -> Self {
+        Self { object, options }
+    }
+}
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut output = String::new();
+        write_pretty(&mut output, self.object, self.options, 0, 0);
+        write!(f, "{}", output)
+    }
+}
+
+/// Elements of an array or tuple, or key-value pairs of a hash.
+type Children<'a> = Vec<(Option<&'a Object>, &'a Object)>;
+
+/// The children of an array, tuple or hash, with the brackets around them.
+fn children(object: &Object) -> Option<(&str, &str, Children<'_>)> {
+    Some(match object {
+        Object::Array(elements) => ("[", "]", elements.iter().map(|x| (None, x)).collect()),
+        Object::Tuple(elements) => ("(", ")", elements.iter().map(|x| (None, x)).collect()),
+        Object::Hash(pairs) => (
+            "{",
+            "}",
+            pairs
+                .iter()
+                .map(|(key, value)| (Some(key), value))
+                .collect(),
+        ),
+        _ => return None,
+    })
+}
+
+/// The single-line form, with the depth cutoff applied.
+fn compact(object: &Object, options: &PrettyOptions, depth: usize) -> String {
+    let Some((open, close, children)) = children(object) else {
+        return object.to_string();
+    };
+    if depth >= options.depth && !children.is_empty() {
+        return format!("{}...{}", open, close);
+    }
+
+    let children = children
+        .into_iter()
+        .map(|(key, value)| match key {
+            Some(key) => format!("{}: {}", key, compact(value, options, depth + 1)),
+            None => compact(value, options, depth + 1),
+        })
+        .collect::<Vec<_>>();
+    format!("{}{}{}", open, children.join(", "), close)
+}
+
+fn write_pretty(
+    output: &mut String,
+    object: &Object,
+    options: &PrettyOptions,
+    indent: usize,
+    depth: usize,
+) {
+    let line = compact(object, options, depth);
+    let Some((open, close, children)) = children(object)
+        .filter(|_| indent + line.chars().count() > options.width && depth < options.depth)
+    else {
+        output.push_str(&line);
+        return;
+    };
+
+    output.push_str(open);
+    for (i, (key, value)) in children.iter().enumerate() {
+        output.push('\n');
+        output.extend(std::iter::repeat_n(' ', indent + 2));
+        if let Some(key) = key {
+            output.push_str(&format!("{}: ", key));
+        }
+        write_pretty(output, value, options, indent + 2, depth + 1);
+        if i + 1 < children.len() {
+            output.push(',');
+        }
+    }
+    output.push('\n');
+    output.extend(std::iter::repeat_n(' ', indent));
+    output.push_str(close);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::eval::object::Object;
+
+    use super::{Pretty, PrettyOptions};
+
+    fn ints(values: &[i64]) -> Object {
+        Object::Array(values.iter().copied().map(Object::Int).collect())
+    }
+
+    #[test]
+    fn splits_long_values() {
+        let options = PrettyOptions {
+            width: 12,
+            depth: 8,
+        };
+        let nested = Object::Array(vec![ints(&[1, 2]), ints(&[10, 20, 30, 40])].into());
+
+        assert_eq!(Pretty::new(&ints(&[1, 2]), &options).to_string(), "[1, 2]");
+        assert_eq!(
+            Pretty::new(&nested, &options).to_string(),
+            "[\n  [1, 2],\n  [\n    10,\n    20,\n    30,\n    40\n  ]\n]"
+        );
+        assert_eq!(format!("{:#}", nested), "[[1, 2], [10, 20, 30, 40]]");
+    }
+
+    #[test]
+    fn elides_deep_values() {
+        let options = PrettyOptions {
+            width: 80,
+            depth: 2,
+        };
+        let deep = Object::Tuple(
+            vec![
+                Object::Int(1),
+                Object::Array(vec![ints(&[2]), ints(&[])].into()),
+            ]
+            .into(),
+        );
+
+        assert_eq!(Pretty::new(&deep, &options).to_string(), "(1, [[...], []])");
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+/// Call counts and cumulative time per function, keyed by the name it was called through.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Default)]
+pub struct Entry {
+    pub calls: u64,
+    pub total: Duration,
+    active: usize,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, name: &str) -> Instant {
+        let entry = self.entries.entry(name.to_string()).or_default();
+        entry.calls += 1;
+        entry.active += 1;
+        Instant::now()
+    }
+
+    /// Only the outermost active call of a function adds to its total, so
+    /// recursion isn't counted twice.
+    pub fn exit(&mut self, name: &str, start: Instant) {
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.active -= 1;
+            if entry.active == 0 {
+                entry.total += start.elapsed();
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Display for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by(|(a_name, a), (b_name, b)| b.total.cmp(&a.total).then(a_name.cmp(b_name)));
+
+        writeln!(f, "{:<20} {:>10} {:>14}", "function", "calls", "total")?;
+        for (name, entry) in entries {
+            writeln!(
+                f,
+                "{:<20} {:>10} {:>14}",
+                name,
+                entry.calls,
+                format!("{:.3?}", entry.total)
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// SplitMix64 generator behind `random_int`. Not fit for cryptography, but fast, and the same
+/// seed always gives the same sequence, on every platform.
+#[derive(Debug)]
+pub struct Random {
+    state: Cell<u64>,
+}
+
+impl Default for Random {
+    /// Seeded from the clock, so runs differ until a script calls `seed`.
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self::new(nanos)
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Cell::new(seed),
+        }
+    }
+
+    /// Restarts the sequence from `seed`.
+    pub fn seed(&self, seed: u64) {
+        self.state.set(seed);
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number between `lo` and `hi`, both included, which must be in order.
+    pub fn int_between(&self, lo: i64, hi: i64) -> i64 {
+        let span = hi.wrapping_sub(lo) as u64;
+        if span == u64::MAX {
+            return self.next_u64() as i64;
+        }
+
+        // Widening multiplication maps the 64 random bits onto the range with negligible bias.
+        let offset = ((self.next_u64() as u128 * (span as u128 + 1)) >> 64) as u64;
+        lo.wrapping_add(offset as i64)
+    }
+}
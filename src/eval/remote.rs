@@ -0,0 +1,185 @@
+use std::{
+    io::Write,
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{lexer::Lexer, parser::Parser};
+
+use super::{Eval, InterruptHandle, Options};
+
+/// Stack for the worker thread, large enough to reach the default maximum call depth.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+enum Request {
+    Eval(String, Sender<Result<String>>),
+    SetOutput(Box<dyn Write + Send>),
+}
+
+/// Evaluator that can be shared between threads, e.g. by the handlers of a server, by talking
+/// to it in Monkey source.
+///
+/// Values are shared through `Rc`s and generators run on stacks of their own, so an `Eval`
+/// can't leave the thread that created it. A `RemoteEval` keeps one on a worker thread instead
+/// and evaluates the sources sent to it there, one at a time, in the same global environment.
+/// Objects never leave the worker: results come back as source, see `eval`, so values
+/// without a literal syntax, such as functions and externals, only come back as their display
+/// form. Bind them to a global name to keep using them.
+pub struct RemoteEval {
+    /// Dropped to stop the worker.
+    requests: Option<Sender<Request>>,
+    interrupt: InterruptHandle,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl RemoteEval {
+    pub fn new(options: Options) -> Result<Self> {
+        let (requests, received) = mpsc::channel();
+        let (handle, interrupt) = mpsc::channel();
+
+        let worker = thread::Builder::new()
+            .name(String::from("monkey-eval"))
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let mut eval = Eval::with_options(options);
+                _ = handle.send(eval.interrupt_handle());
+
+                for request in received {
+                    match request {
+                        Request::Eval(source, reply) => _ = reply.send(run(&mut eval, &source)),
+                        Request::SetOutput(output) => eval.set_output(Box::new(output)),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            requests: Some(requests),
+            interrupt: interrupt.recv()?,
+            worker: Some(worker),
+        })
+    }
+
+    /// Parses and evaluates `source`, returning the result as Monkey source, see
+    /// `Object::inspect`, which can't be parsed back for functions and the like. Waits for the
+    /// evaluations other threads requested first.
+    pub fn eval(&self, source: &str) -> Result<String> {
+        let (reply, result) = mpsc::channel();
+        self.send(Request::Eval(source.to_string(), reply))?;
+
+        result
+            .recv()
+            .map_err(|_| anyhow!("Evaluator thread stopped!"))?
+    }
+
+    /// Redirects what scripts print, see `Eval::set_output`.
+    pub fn set_output(&self, output: Box<dyn Write + Send>) -> Result<()> {
+        self.send(Request::SetOutput(output))
+    }
+
+    /// Aborts the running evaluation, see `InterruptHandle`.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    fn send(&self, request: Request) -> Result<()> {
+        self.requests
+            .as_ref()
+            .and_then(|requests| requests.send(request).ok())
+            .ok_or_else(|| anyhow!("Evaluator thread stopped!"))
+    }
+}
+
+fn run(eval: &mut Eval, source: &str) -> Result<String> {
+    let program = Parser::new(Lexer::new(source)).parse_program()?;
+    Ok(eval.eval(program)?.inspect())
+}
+
+impl Drop for RemoteEval {
+    fn drop(&mut self) {
+        self.requests.take();
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use crate::eval::Options;
+
+    use super::RemoteEval;
+
+    #[derive(Clone)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let eval = Arc::new(RemoteEval::new(Options::default()).unwrap());
+        eval.eval(r#"let greet = fn(name) { "Hello, " + name }"#)
+            .unwrap();
+
+        let greetings = (0..4)
+            .map(|i| {
+                let eval = eval.clone();
+                thread::spawn(move || eval.eval(&format!(r#"greet("{}")"#, i)).unwrap())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            greetings,
+            [
+                r#""Hello, 0""#,
+                r#""Hello, 1""#,
+                r#""Hello, 2""#,
+                r#""Hello, 3""#
+            ]
+        );
+        assert_eq!(
+            eval.eval("greet(1)").unwrap_err().to_string(),
+            "Infix operator + not found for the operands: string & int!"
+        );
+    }
+
+    #[test]
+    fn captures_output() {
+        let eval = RemoteEval::new(Options::default()).unwrap();
+        let output = Output(Arc::default());
+        eval.set_output(Box::new(output.clone())).unwrap();
+
+        assert_eq!(eval.eval(r#"puts("hi")"#).unwrap(), "null");
+        assert_eq!(*output.0.lock().unwrap(), b"hi\n");
+    }
+
+    #[test]
+    fn returns_source() {
+        let eval = RemoteEval::new(Options::default()).unwrap();
+
+        assert_eq!(
+            eval.eval(r#"{"a": [1, "b"]}"#).unwrap(),
+            r#"{"a": [1, "b"]}"#
+        );
+        assert_eq!(eval.eval("let f = fn(x) { x }; f").unwrap(), "fn(x)");
+        assert_eq!(eval.eval("f(2)").unwrap(), "2");
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    fmt::Display,
+    rc::Rc,
+};
+
+use super::{env::Env, object::Object};
+
+thread_local! {
+    static LIVE_ENVS: Cell<usize> = const { Cell::new(0) };
+    static LIVE_FUNCTIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Env,
+    Function,
+}
+
+impl Kind {
+    fn counter(self) -> &'static std::thread::LocalKey<Cell<usize>> {
+        match self {
+            Kind::Env => &LIVE_ENVS,
+            Kind::Function => &LIVE_FUNCTIONS,
+        }
+    }
+}
+
+/// Field counting the scopes or functions alive on this thread, by existing once per value.
+#[derive(Debug)]
+pub struct Live(Kind);
+
+impl Live {
+    pub fn env() -> Self {
+        Self::new(Kind::Env)
+    }
+
+    pub fn function() -> Self {
+        Self::new(Kind::Function)
+    }
+
+    fn new(kind: Kind) -> Self {
+        kind.counter().with(|count| count.set(count.get() + 1));
+        Self(kind)
+    }
+}
+
+impl Clone for Live {
+    fn clone(&self) -> Self {
+        Self::new(self.0)
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        self.0.counter().with(|count| count.set(count.get() - 1));
+    }
+}
+
+/// Not part of the value, so it never makes two values differ.
+impl PartialEq for Live {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// What `stats()` and the REPL's `:stats` report. Values are reference counted, so a closure
+/// stored in the scope it captures keeps that scope alive after nothing can reach it: live
+/// scopes and functions well above the reachable ones point to such cycles.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HeapStats {
+    /// Scopes alive on this thread, whether scripts can still reach them or not.
+    pub live_envs: usize,
+    /// Functions alive on this thread, whether scripts can still reach them or not.
+    pub live_functions: usize,
+    /// Scopes reachable from the running code and imported modules.
+    pub envs: usize,
+    /// Functions reachable from the running code and imported modules.
+    pub functions: usize,
+    /// Values reachable from the running code, counted once per place they are stored in, as
+    /// copies of the same array or string share their contents.
+    pub arrays: usize,
+    pub tuples: usize,
+    pub hashes: usize,
+    pub sets: usize,
+    pub strings: usize,
+}
+
+impl HeapStats {
+    /// Counts what is reachable from `roots`, along with the live counts.
+    pub fn collect<'a>(roots: impl IntoIterator<Item = &'a Rc<RefCell<Env>>>) -> Self {
+        let mut walk = Walk {
+            stats: HeapStats {
+                live_envs: LIVE_ENVS.with(Cell::get),
+                live_functions: LIVE_FUNCTIONS.with(Cell::get),
+                ..Default::default()
+            },
+            seen: HashSet::new(),
+        };
+        for root in roots {
+            walk.env(root);
+        }
+        walk.stats
+    }
+
+    /// The counts by name, as `stats()` returns them.
+    pub fn entries(&self) -> [(&'static str, usize); 9] {
+        [
+            ("live_envs", self.live_envs),
+            ("live_functions", self.live_functions),
+            ("envs", self.envs),
+            ("functions", self.functions),
+            ("arrays", self.arrays),
+            ("tuples", self.tuples),
+            ("hashes", self.hashes),
+            ("sets", self.sets),
+            ("strings", self.strings),
+        ]
+    }
+}
+
+impl Display for HeapStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<10} {:>10} {:>10}", "", "live", "reachable")?;
+        writeln!(f, "{:<10} {:>10} {:>10}", "envs", self.live_envs, self.envs)?;
+        write!(
+            f,
+            "{:<10} {:>10} {:>10}",
+            "functions", self.live_functions, self.functions
+        )?;
+        for (name, count) in [
+            ("arrays", self.arrays),
+            ("tuples", self.tuples),
+            ("hashes", self.hashes),
+            ("sets", self.sets),
+            ("strings", self.strings),
+        ] {
+            write!(f, "\n{:<10} {:>10} {:>10}", name, "", count)?;
+        }
+        Ok(())
+    }
+}
+
+struct Walk {
+    stats: HeapStats,
+    /// Scopes and functions already counted, by address, so cycles end.
+    seen: HashSet<usize>,
+}
+
+impl Walk {
+    fn env(&mut self, env: &Rc<RefCell<Env>>) {
+        if !self.seen.insert(Rc::as_ptr(env) as *const () as usize) {
+            return;
+        }
+        self.stats.envs += 1;
+
+        let env = env.borrow();
+        for value in env.values() {
+            self.object(value);
+        }
+        if let Some(outer) = &env.outer {
+            self.env(outer);
+        }
+    }
+
+    fn object(&mut self, object: &Object) {
+        match object {
+            Object::String(_) => self.stats.strings += 1,
+            Object::Function(function)
+                if self.seen.insert(Rc::as_ptr(function) as *const () as usize) =>
+            {
+                self.stats.functions += 1;
+                self.env(&function.env);
+            }
+            Object::Array(elements) => {
+                self.stats.arrays += 1;
+                elements.iter().for_each(|element| self.object(element));
+            }
+            Object::Tuple(elements) => {
+                self.stats.tuples += 1;
+                elements.iter().for_each(|element| self.object(element));
+            }
+            Object::Hash(pairs) => {
+                self.stats.hashes += 1;
+                for (key, value) in pairs {
+                    self.object(key);
+                    self.object(value);
+                }
+            }
+            Object::Set(values) => {
+                self.stats.sets += 1;
+                values.iter().for_each(|value| self.object(value));
+            }
+            Object::Module(module) => self.env(&module.scope),
+            Object::ReturnValue(value) => self.object(value),
+            _ => {}
+        }
+    }
+}
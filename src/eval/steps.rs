@@ -0,0 +1,41 @@
+use anyhow::Result;
+use corosensei::{stack::DefaultStack, Coroutine, CoroutineResult, Yielder};
+
+use super::object::{Object, GENERATOR_STACK_SIZE};
+
+/// Outcome of `Eval::run_steps`.
+#[derive(Debug, PartialEq)]
+pub enum StepResult {
+    /// The program finished with this value.
+    Done(Object),
+    /// The program used up its steps and continues on the next call.
+    Pending,
+}
+
+/// Program loaded with `Eval::load`, running on a stack of its own so it can be suspended after
+/// any expression. Like tasks, it runs with the generators' maximum call depth.
+pub(super) struct Steps {
+    coroutine: Coroutine<usize, (), Result<Object>>,
+}
+
+impl Steps {
+    /// `body` gets how many steps it may take before suspending, and the same again from each
+    /// `suspend`.
+    pub(super) fn new(
+        body: impl FnOnce(&Yielder<usize, ()>, usize) -> Result<Object> + 'static,
+    ) -> Result<Self> {
+        let stack = DefaultStack::new(GENERATOR_STACK_SIZE)?;
+
+        Ok(Self {
+            coroutine: Coroutine::with_stack(stack, body),
+        })
+    }
+
+    /// Runs up to `steps` more steps, returning the result once the program finishes.
+    pub(super) fn resume(&mut self, steps: usize) -> Option<Result<Object>> {
+        match self.coroutine.resume(steps) {
+            CoroutineResult::Yield(()) => None,
+            CoroutineResult::Return(result) => Some(result),
+        }
+    }
+}
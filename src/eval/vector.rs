@@ -0,0 +1,184 @@
+use std::{ops::Index, rc::Rc};
+
+use super::object::Object;
+
+const BITS: usize = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Branch(Vec<Rc<Node>>),
+    Leaf(Vec<Object>),
+}
+
+/// Persistent vector: a trie of 32-element leaves plus a tail holding the last, possibly
+/// partial, leaf. Cloning is O(1) and `push` and `set` copy only the O(log n) nodes on the path
+/// to the element, so copies that diverge keep sharing everything else. Nodes nobody else sees
+/// are updated in place.
+#[derive(Debug, Clone)]
+pub struct Vector {
+    len: usize,
+    /// Bits of the index consumed by the levels above the leaves.
+    shift: usize,
+    root: Rc<Node>,
+    tail: Rc<Vec<Object>>,
+}
+
+impl Default for Vector {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            shift: BITS,
+            root: Rc::new(Node::Branch(vec![])),
+            tail: Rc::new(vec![]),
+        }
+    }
+}
+
+impl Vector {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Index of the first element in the tail.
+    fn tail_offset(&self) -> usize {
+        self.len - self.tail.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Object> {
+        if index >= self.len {
+            return None;
+        }
+        if index >= self.tail_offset() {
+            return self.tail.get(index - self.tail_offset());
+        }
+
+        let mut node = &self.root;
+        let mut level = self.shift;
+        loop {
+            match node.as_ref() {
+                Node::Branch(children) => node = &children[(index >> level) & MASK],
+                Node::Leaf(elements) => return elements.get(index & MASK),
+            }
+            level = level.saturating_sub(BITS);
+        }
+    }
+
+    pub fn push(&mut self, value: Object) {
+        if self.tail.len() == WIDTH {
+            let index = self.tail_offset();
+            let leaf = Rc::new(Node::Leaf(std::mem::take(Rc::make_mut(&mut self.tail))));
+
+            if index == 1 << (self.shift + BITS) {
+                let path = new_path(self.shift, leaf);
+                self.root = Rc::new(Node::Branch(vec![self.root.clone(), path]));
+                self.shift += BITS;
+            } else {
+                push_leaf(Rc::make_mut(&mut self.root), self.shift, index, leaf);
+            }
+        }
+
+        Rc::make_mut(&mut self.tail).push(value);
+        self.len += 1;
+    }
+
+    /// Replaces the element at `index`, which must be less than the length.
+    pub fn set(&mut self, index: usize, value: Object) {
+        assert!(index < self.len, "index {} out of bounds", index);
+
+        let tail_offset = self.tail_offset();
+        if index >= tail_offset {
+            Rc::make_mut(&mut self.tail)[index - tail_offset] = value;
+            return;
+        }
+
+        let mut node = Rc::make_mut(&mut self.root);
+        let mut level = self.shift;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = Rc::make_mut(&mut children[(index >> level) & MASK])
+                }
+                Node::Leaf(elements) => {
+                    elements[index & MASK] = value;
+                    return;
+                }
+            }
+            level = level.saturating_sub(BITS);
+        }
+    }
+}
+
+/// A chain of branches down to `leaf`, for an index past everything under `level`.
+fn new_path(level: usize, leaf: Rc<Node>) -> Rc<Node> {
+    if level == 0 {
+        return leaf;
+    }
+    Rc::new(Node::Branch(vec![new_path(level - BITS, leaf)]))
+}
+
+fn push_leaf(node: &mut Node, level: usize, index: usize, leaf: Rc<Node>) {
+    let Node::Branch(children) = node else {
+        unreachable!("leaves are only pushed into branches");
+    };
+
+    let slot = (index >> level) & MASK;
+    if level == BITS {
+        children.push(leaf);
+    } else if slot < children.len() {
+        push_leaf(Rc::make_mut(&mut children[slot]), level - BITS, index, leaf);
+    } else {
+        children.push(new_path(level - BITS, leaf));
+    }
+}
+
+impl Index<usize> for Vector {
+    type Output = Object;
+
+    fn index(&self, index: usize) -> &Object {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl From<Vec<Object>> for Vector {
+    fn from(elements: Vec<Object>) -> Self {
+        let mut vector = Vector::default();
+        for element in elements {
+            vector.push(element);
+        }
+        vector
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::eval::object::Object;
+
+    use super::{Vector, WIDTH};
+
+    #[test]
+    fn copies_only_the_path() {
+        let count = WIDTH * WIDTH * 2 + 3;
+        let vector = Vector::from((0..count as i64).map(Object::Int).collect::<Vec<_>>());
+        assert_eq!(vector.len(), count);
+        assert!((0..count).all(|i| vector[i] == Object::Int(i as i64)));
+        assert_eq!(vector.get(count), None);
+
+        let mut changed = vector.clone();
+        changed.set(5, Object::Null);
+        changed.set(count - 1, Object::Null);
+        changed.push(Object::Bool(true));
+
+        assert_eq!(changed[5], Object::Null);
+        assert_eq!(changed[count - 1], Object::Null);
+        assert_eq!(changed[count], Object::Bool(true));
+        assert_eq!(vector[5], Object::Int(5));
+        assert_eq!(vector[count - 1], Object::Int(count as i64 - 1));
+        assert_eq!(vector.len(), count);
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    cell::RefCell,
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    eval::{object::Object, Eval, Options},
+    lexer::Lexer,
+    parser::{Parser, SyntaxError},
+    repl::{catch_silently, panic_message},
+};
+
+/// What scripts print while a request is handled, returned as its `stdout`.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A REPL for programs rather than people: reads one JSON request per line from stdin and
+/// writes one JSON response per line to stdout, see the README for the protocol.
+pub fn run(options: Options) -> Result<()> {
+    let mut eval = Eval::with_options(options);
+    let captured = Captured::default();
+    eval.set_output(Box::new(captured.clone()));
+
+    let mut buffer = String::new();
+    while std::io::stdin().lock().read_line(&mut buffer)? > 0 {
+        let line = std::mem::take(&mut buffer);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(&mut eval, &captured, &line);
+
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+/// The response to one request line.
+fn handle(eval: &mut Eval, captured: &Captured, line: &str) -> Value {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(request @ Value::Object(_)) => request,
+        Ok(_) => return failure(Value::Null, "Request must be a JSON object"),
+        Err(error) => return failure(Value::Null, &format!("Invalid JSON: {}", error)),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+
+    match request.get("op").and_then(Value::as_str) {
+        Some("eval") => match request.get("code").and_then(Value::as_str) {
+            Some(code) => eval_code(eval, captured, id, code),
+            None => failure(id, "Missing string field code"),
+        },
+        Some(op) => failure(id, &format!("Unknown op {}", op)),
+        None => failure(id, "Missing string field op"),
+    }
+}
+
+fn eval_code(eval: &mut Eval, captured: &Captured, id: Value, code: &str) -> Value {
+    captured.0.borrow_mut().clear();
+
+    let result = catch_silently(|| -> Result<Object, Vec<Value>> {
+        let program = Parser::new(Lexer::new(code))
+            .parse_program()
+            .map_err(|error| vec![syntax_error(&error)])?;
+        let errors = program
+            .iter()
+            .filter_map(|statement| statement.as_ref().err())
+            .map(syntax_error)
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        eval.eval(program).map_err(|error| {
+            vec![json!({
+                "severity": "error",
+                "message": error.to_string(),
+                "stack": eval.stack_trace(),
+            })]
+        })
+    });
+
+    let (result, mut diagnostics) = match result {
+        Ok(Ok(result)) => (result, vec![]),
+        Ok(Err(diagnostics)) => (Object::Empty, diagnostics),
+        Err(panic) => {
+            eval.reset_scope();
+            let message = format!("Internal error: {}", panic_message(&*panic));
+            (Object::Empty, vec![diagnostic("error", &message)])
+        }
+    };
+    diagnostics.extend(
+        eval.take_warnings()
+            .iter()
+            .map(|warning| diagnostic("warning", warning)),
+    );
+
+    let (value, kind) = match result {
+        Object::Empty => (Value::Null, Value::Null),
+        result => (json!(result.to_string()), json!(result.get_type())),
+    };
+    json!({
+        "id": id,
+        "result": value,
+        "type": kind,
+        "stdout": String::from_utf8_lossy(&captured.0.borrow()),
+        "diagnostics": diagnostics,
+    })
+}
+
+fn syntax_error(error: &anyhow::Error) -> Value {
+    let mut value = diagnostic("error", &error.to_string());
+    if let Some(error) = error.downcast_ref::<SyntaxError>() {
+        value["span"] = json!({ "start": error.span.start, "end": error.span.end });
+        if let Some(hint) = &error.hint {
+            value["hint"] = json!(hint);
+        }
+    }
+    value
+}
+
+fn diagnostic(severity: &str, message: &str) -> Value {
+    json!({ "severity": severity, "message": message })
+}
+
+/// The response to a request that couldn't be handled at all.
+fn failure(id: Value, message: &str) -> Value {
+    json!({
+        "id": id,
+        "result": null,
+        "type": null,
+        "stdout": "",
+        "diagnostics": [diagnostic("error", message)],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::eval::{object::Object, observer::EvalObserver, Eval};
+
+    use super::{handle, Captured};
+
+    fn session() -> impl FnMut(&str) -> Value {
+        session_with(Eval::new())
+    }
+
+    fn session_with(mut eval: Eval) -> impl FnMut(&str) -> Value {
+        let captured = Captured::default();
+        eval.set_output(Box::new(captured.clone()));
+        move |line| handle(&mut eval, &captured, line)
+    }
+
+    #[test]
+    fn evaluates_requests() {
+        let mut request = session();
+
+        assert_eq!(
+            request(r#"{"id": 1, "op": "eval", "code": "let x = 2; puts(x); x + 1"}"#),
+            json!({"id": 1, "result": "3", "type": "int", "stdout": "2\n", "diagnostics": []})
+        );
+        assert_eq!(
+            request(r#"{"op": "eval", "code": "let y = x"}"#),
+            json!({"id": null, "result": null, "type": null, "stdout": "", "diagnostics": []})
+        );
+        assert_eq!(
+            request(r#"{"id": "a", "op": "eval", "code": "let f = fn() { -true }; f()"}"#),
+            json!({
+                "id": "a",
+                "result": null,
+                "type": null,
+                "stdout": "",
+                "diagnostics": [{
+                    "severity": "error",
+                    "message": "Operator prefix - is not defined for bool!",
+                    "stack": ["f"],
+                }],
+            })
+        );
+        assert_eq!(
+            request(r#"{"op": "eval", "code": "let z = (1"}"#)["diagnostics"],
+            json!([{
+                "severity": "error",
+                "message": "Failed to parse grouped expression!",
+                "span": {"start": 9, "end": 10},
+                "hint": "did you forget a closing `)`?",
+            }])
+        );
+        assert_eq!(
+            request(r#"{"op": "eval", "code": "y"}"#)["result"],
+            json!("2")
+        );
+    }
+
+    struct PanicOnCall;
+
+    impl EvalObserver for PanicOnCall {
+        fn on_call(&mut self, _: &Object, _: &[Object]) {
+            panic!("observer exploded");
+        }
+    }
+
+    #[test]
+    fn survives_panics() {
+        let mut eval = Eval::new();
+        eval.add_observer(Box::new(PanicOnCall));
+        let mut request = session_with(eval);
+
+        request(r#"{"op": "eval", "code": "let x = 5;"}"#);
+        assert_eq!(
+            request(r#"{"id": 1, "op": "eval", "code": "let f = fn(y) { y }; f(1)"}"#),
+            json!({
+                "id": 1,
+                "result": null,
+                "type": null,
+                "stdout": "",
+                "diagnostics": [{
+                    "severity": "error",
+                    "message": "Internal error: observer exploded",
+                }],
+            })
+        );
+        assert_eq!(
+            request(r#"{"op": "eval", "code": "x + 1"}"#)["result"],
+            json!("6")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        let mut request = session();
+        let message = |response: Value| response["diagnostics"][0]["message"].clone();
+
+        assert_eq!(
+            message(request(r#"{"id": 7, "op": "run"}"#)),
+            json!("Unknown op run")
+        );
+        assert_eq!(
+            message(request(r#"{"op": "eval", "code": 5}"#)),
+            json!("Missing string field code")
+        );
+        assert_eq!(
+            message(request("[1]")),
+            json!("Request must be a JSON object")
+        );
+        assert!(message(request("{"))
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid JSON: "));
+    }
+}
@@ -1,3 +1,8 @@
+use std::{
+    io::{self, Read},
+    ops::Range,
+};
+
 use anyhow::{bail, Result};
 
 #[derive(Debug, PartialEq, Default)]
@@ -8,100 +13,499 @@ pub enum Token {
 
     Ident(String),
     Int(i64),
+    Float(f64),
     Bool(bool),
     String(String),
+    /// `b"..."`
+    Bytes(Vec<u8>),
+    Null,
 
     Assign,
     Plus,
     Minus,
     Asterisk,
     Slash,
+    /// `//`
+    DoubleSlash,
     Bang,
     Lt,
     Gt,
 
     Equal,
     NotEqual,
+    StrictEqual,
+    StrictNotEqual,
+    /// `in`, testing membership.
+    In,
+    /// `!in`
+    NotIn,
+    /// `|>`
+    Pipe,
+    /// `=>`, between the parameters and the body of an arrow function.
+    Arrow,
 
     Comma,
     Semicolon,
+    Colon,
+    /// `...`
+    Spread,
+    /// `..`
+    DotDot,
+    /// `..=`
+    DotDotEq,
+    /// End of a line that ends a statement, so it can terminate it just like a semicolon.
+    Newline,
 
     Lparen,
     Rparen,
     LSquirly,
     RSquirly,
+    LBracket,
+    RBracket,
 
     Function,
     Let,
     If,
     Else,
     Return,
+    Yield,
+    Await,
+    Import,
+    Dot,
+}
+
+/// Source between two tokens that makes no difference to the program. Monkey has no comments,
+/// so for now that is whitespace, line breaks included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+/// Broad class of a token, for colouring source without parsing it, see `tokenize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    Number,
+    /// String and bytes literals.
+    String,
+    /// `true`, `false` and `null`.
+    Constant,
+    Operator,
+    /// Brackets and separators.
+    Punctuation,
+    /// Input the lexer rejects, such as an unterminated string or a stray `@`.
+    Error,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::Illegal | Token::Eof => TokenKind::Error,
+            Token::Ident(_) => TokenKind::Identifier,
+            Token::Int(_) | Token::Float(_) => TokenKind::Number,
+            Token::String(_) | Token::Bytes(_) => TokenKind::String,
+            Token::Bool(_) | Token::Null => TokenKind::Constant,
+            Token::Assign
+            | Token::Plus
+            | Token::Minus
+            | Token::Asterisk
+            | Token::Slash
+            | Token::DoubleSlash
+            | Token::Bang
+            | Token::Lt
+            | Token::Gt
+            | Token::Equal
+            | Token::NotEqual
+            | Token::StrictEqual
+            | Token::StrictNotEqual
+            | Token::Pipe
+            | Token::Arrow
+            | Token::Spread
+            | Token::DotDot
+            | Token::DotDotEq
+            | Token::Dot => TokenKind::Operator,
+            Token::Comma
+            | Token::Semicolon
+            | Token::Newline
+            | Token::Colon
+            | Token::Lparen
+            | Token::Rparen
+            | Token::LSquirly
+            | Token::RSquirly
+            | Token::LBracket
+            | Token::RBracket => TokenKind::Punctuation,
+            Token::Function
+            | Token::Let
+            | Token::If
+            | Token::Else
+            | Token::Return
+            | Token::Yield
+            | Token::Await
+            | Token::Import
+            | Token::In
+            | Token::NotIn => TokenKind::Keyword,
+        }
+    }
+}
+
+/// The tokens of `source` with their byte offsets, for syntax highlighters. Never fails: input
+/// the lexer rejects becomes an `Error` token and lexing carries on after it. Line breaks that
+/// end statements are left out, like whitespace.
+pub fn tokenize(source: &str) -> Vec<(TokenKind, Range<usize>)> {
+    let mut lexer = Lexer::new(source);
+    let mut tokens = vec![];
+
+    loop {
+        match lexer.next_token() {
+            Ok(Token::Eof) => return tokens,
+            Ok(Token::Newline) => {}
+            Ok(token) => tokens.push((token.kind(), lexer.span())),
+            Err(_) => {
+                // The lexer stops at the byte it rejected, so skip at least the whole character.
+                let span = lexer.span();
+                let char_len = source[span.start..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+                let end = span.end.max(span.start + char_len);
+                while lexer.position < end {
+                    lexer.read_char();
+                }
+                tokens.push((TokenKind::Error, span.start..end));
+            }
+        }
+    }
 }
 
+/// Bytes read from a stream at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+/// Bytes past the current one the lexer may look at to decide on a token.
+const LOOKAHEAD: usize = 8;
+
 pub struct Lexer {
+    /// The source, or for a stream, the part of it from the start of the line being lexed on.
     input: Vec<u8>,
+    /// Set when lexing a stream, which is read into `input` as needed.
+    reader: Option<Box<dyn Read>>,
+    /// Error reading the stream, returned once the bytes read before it are lexed.
+    read_error: Option<io::Error>,
+    /// Offset in the source of the first byte in `input`. Offsets and spans always refer to the
+    /// whole source.
+    offset: usize,
+    /// Line breaks in the part of the source dropped from `input`, for error locations.
+    lines_dropped: usize,
     position: usize,
     read_position: usize,
     ch: u8,
+    /// Whether the last token can end a statement, making a following line break significant.
+    ends_statement: bool,
+    /// Currently open brackets. Line breaks inside parentheses and square brackets are ignored.
+    brackets: Vec<u8>,
+    /// Byte offset where the last token starts.
+    token_start: usize,
+    /// Trivia before the last token, when kept at all.
+    trivia: Option<Vec<Trivia>>,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        Self::with_input(input.into(), None)
+    }
+
+    /// Lexes a stream, reading it in chunks as tokens are requested instead of all at once.
+    /// Lines already lexed are dropped, so memory stays proportional to the longest line.
+    pub fn from_reader(reader: impl Read + 'static) -> Self {
+        Self::with_input(vec![], Some(Box::new(reader)))
+    }
+
+    /// Keeps the trivia before each token instead of skipping it, for tools that rewrite source
+    /// and must not lose anything, see `trivia`.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = Some(vec![]);
+        self
+    }
+
+    fn with_input(input: Vec<u8>, reader: Option<Box<dyn Read>>) -> Self {
         let mut lexer = Self {
-            input: input.into(),
+            input,
+            reader,
+            read_error: None,
+            offset: 0,
+            lines_dropped: 0,
             position: 0,
             read_position: 0,
             ch: 0,
+            ends_statement: false,
+            brackets: vec![],
+            token_start: 0,
+            trivia: None,
         };
         lexer.read_char();
         lexer
     }
 
     fn read_char(&mut self) {
-        self.ch = if self.read_position >= self.input.len() {
-            0
-        } else {
-            self.input[self.read_position]
-        };
+        self.fill(self.read_position + LOOKAHEAD);
+        self.ch = self.byte(self.read_position);
 
         self.position = self.read_position;
         self.read_position += 1;
     }
 
+    /// The byte at offset `pos` of the source, or 0 past its end.
+    fn byte(&self, pos: usize) -> u8 {
+        self.input.get(pos - self.offset).copied().unwrap_or(0)
+    }
+
+    /// The source from offset `start` up to `end`, which must not have been dropped.
+    fn slice(&self, start: usize, end: usize) -> &[u8] {
+        let end = end.min(self.end());
+        &self.input[start.min(end) - self.offset..end - self.offset]
+    }
+
+    /// The source from the current byte on, as far as it was read.
+    fn rest(&self) -> &[u8] {
+        self.input
+            .get(self.position - self.offset..)
+            .unwrap_or_default()
+    }
+
+    /// Offset just past the last byte read so far.
+    fn end(&self) -> usize {
+        self.offset + self.input.len()
+    }
+
+    /// Reads from the stream, if any, until `input` reaches offset `until` or the stream ends.
+    fn fill(&mut self, until: usize) {
+        let Some(reader) = &mut self.reader else {
+            return;
+        };
+
+        while self.offset + self.input.len() < until {
+            let len = self.input.len();
+            self.input.resize(len + CHUNK_SIZE, 0);
+            match reader.read(&mut self.input[len..]) {
+                Ok(0) => {
+                    self.input.truncate(len);
+                    self.reader = None;
+                    return;
+                }
+                Ok(read) => self.input.truncate(len + read),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {
+                    self.input.truncate(len)
+                }
+                Err(error) => {
+                    self.input.truncate(len);
+                    self.read_error = Some(error);
+                    self.reader = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Forgets the lines of a stream before the current one, once they add up to a chunk.
+    fn drop_lexed_lines(&mut self) {
+        if self.reader.is_none() || self.position - self.offset < CHUNK_SIZE {
+            return;
+        }
+        let Some(line_start) = self
+            .slice(self.offset, self.position)
+            .iter()
+            .rposition(|&ch| ch == b'\n')
+        else {
+            return;
+        };
+
+        self.lines_dropped += self.input[..line_start]
+            .iter()
+            .filter(|&&ch| ch == b'\n')
+            .count()
+            + 1;
+        self.input.drain(..line_start + 1);
+        self.offset += line_start + 1;
+    }
+
+    /// Byte offsets of the last token returned, or of the invalid input after an error.
+    pub fn span(&self) -> Range<usize> {
+        self.token_start.min(self.end())..self.position.min(self.end())
+    }
+
+    /// Trivia between the previous token and the last one returned, always empty unless the
+    /// lexer was built `with_trivia`. Trivia at the end of the source comes before `Eof`, and a
+    /// `Newline` gets the whitespace around the line break it stands for.
+    pub fn trivia(&self) -> &[Trivia] {
+        self.trivia.as_deref().unwrap_or_default()
+    }
+
+    /// The innermost bracket opened and not closed yet.
+    pub fn open_bracket(&self) -> Option<char> {
+        self.brackets.last().map(|&bracket| bracket as char)
+    }
+
     pub fn next_token(&mut self) -> Result<Token> {
-        self.skip_whitespace();
+        let start = self.position;
+        let line_break = self.skip_whitespace();
+        if let Some(trivia) = &mut self.trivia {
+            trivia.clear();
+            if self.position > start {
+                trivia.push(Trivia {
+                    text: String::from_utf8_lossy(
+                        &self.input[start - self.offset..self.position - self.offset],
+                    )
+                    .to_string(),
+                    span: start..self.position,
+                });
+            }
+        }
+        self.drop_lexed_lines();
+        self.token_start = self.position;
+
+        if self.ch == 0 && self.position >= self.end() {
+            if let Some(error) = self.read_error.take() {
+                bail!("Cannot read the program: {}!", error);
+            }
+        }
+
+        if line_break
+            && self.ends_statement
+            && !matches!(self.brackets.last(), Some(b'(' | b'['))
+            && !self.continues_statement()
+        {
+            self.ends_statement = false;
+            return Ok(Token::Newline);
+        }
+
+        let token = self.read_token()?;
+
+        self.ends_statement = matches!(
+            token,
+            Token::Ident(_)
+                | Token::Int(_)
+                | Token::Float(_)
+                | Token::Bool(_)
+                | Token::String(_)
+                | Token::Bytes(_)
+                | Token::Null
+                | Token::Rparen
+                | Token::RBracket
+                | Token::RSquirly
+        );
+        match token {
+            Token::Lparen => self.brackets.push(b'('),
+            Token::LBracket => self.brackets.push(b'['),
+            Token::LSquirly => self.brackets.push(b'{'),
+            Token::Rparen | Token::RBracket | Token::RSquirly => {
+                self.brackets.pop();
+            }
+            _ => {}
+        }
+
+        Ok(token)
+    }
+
+    /// Whether the line starting at the current character carries on the previous statement
+    /// instead of starting a new one: it begins with `else`, a closing bracket or an operator
+    /// that cannot start an expression, such as `|>` in a pipeline split across lines. `+` and
+    /// `-` can also be prefixes, so they only carry on the statement when a space follows them:
+    /// `+ 1` does, `-1` starts a new one.
+    fn continues_statement(&self) -> bool {
+        let rest = self.rest();
+
+        match rest {
+            [b'e', b'l', b's', b'e', next, ..] => !next.is_ascii_alphabetic() && *next != b'_',
+            [b'e', b'l', b's', b'e'] => true,
+            [b'!', b'=', ..] | [b'|', b'>', ..] => true,
+            [b'+' | b'-', next, ..] => next.is_ascii_whitespace(),
+            [ch, ..] => b"*/=<>,:.)]}".contains(ch),
+            [] => false,
+        }
+    }
 
+    fn read_token(&mut self) -> Result<Token> {
         let token = match self.ch {
             b'=' => {
-                if self.peek() == b'=' {
+                if self.rest().starts_with(b"===") {
+                    self.read_char();
+                    self.read_char();
+                    Token::StrictEqual
+                } else if self.peek() == b'=' {
                     self.read_char();
                     Token::Equal
+                } else if self.peek() == b'>' {
+                    self.read_char();
+                    Token::Arrow
                 } else {
                     Token::Assign
                 }
             }
             b';' => Token::Semicolon,
+            b':' => Token::Colon,
             b'(' => Token::Lparen,
             b')' => Token::Rparen,
             b',' => Token::Comma,
             b'+' => Token::Plus,
             b'-' => Token::Minus,
             b'*' => Token::Asterisk,
+            b'/' if self.peek() == b'/' => {
+                self.read_char();
+                Token::DoubleSlash
+            }
             b'/' => Token::Slash,
             b'!' => {
-                if self.peek() == b'=' {
+                if self.rest().starts_with(b"!==") {
+                    self.read_char();
+                    self.read_char();
+                    Token::StrictNotEqual
+                } else if self.peek() == b'=' {
                     self.read_char();
                     Token::NotEqual
+                } else if let [b'!', b'i', b'n', next @ ..] = self.rest() {
+                    match next.first() {
+                        Some(ch) if ch.is_ascii_alphabetic() || *ch == b'_' => Token::Bang,
+                        _ => {
+                            self.read_char();
+                            self.read_char();
+                            Token::NotIn
+                        }
+                    }
                 } else {
                     Token::Bang
                 }
             }
+            b'.' if self.rest().starts_with(b"...") => {
+                self.read_char();
+                self.read_char();
+                Token::Spread
+            }
+            b'.' if self.rest().starts_with(b"..=") => {
+                self.read_char();
+                self.read_char();
+                Token::DotDotEq
+            }
+            b'.' if self.peek() != b'.' => Token::Dot,
+            b'.' if self.peek() == b'.' => {
+                self.read_char();
+                Token::DotDot
+            }
+            b'|' if self.peek() == b'>' => {
+                self.read_char();
+                Token::Pipe
+            }
             b'<' => Token::Lt,
             b'>' => Token::Gt,
             b'{' => Token::LSquirly,
             b'}' => Token::RSquirly,
+            b'[' => Token::LBracket,
+            b']' => Token::RBracket,
             0 => Token::Eof,
 
+            b'b' if self.peek() == b'"' => {
+                self.read_char();
+                return Ok(Token::Bytes(self.read_string_bytes()?));
+            }
             b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
                 return Ok({
                     let ident = self.read_identifier();
@@ -112,15 +516,24 @@ impl Lexer {
                         "else" => Token::Else,
                         "true" => Token::Bool(true),
                         "false" => Token::Bool(false),
+                        "null" => Token::Null,
                         "return" => Token::Return,
+                        "yield" => Token::Yield,
+                        "await" => Token::Await,
+                        "import" => Token::Import,
+                        "in" => Token::In,
                         _ => Token::Ident(ident),
                     }
                 })
             }
 
-            b'0'..=b'9' => return Ok(Token::Int(self.read_int())),
+            b'0'..=b'9' => return self.read_number(),
             b'"' => return Ok(Token::String(self.read_string()?)),
-            _ => bail!("No program should contain this token: {}", self.ch as char),
+            _ => bail!(
+                "No program should contain this token: {} at {}",
+                self.ch as char,
+                self.location(self.position)
+            ),
         };
 
         self.read_char();
@@ -128,18 +541,25 @@ impl Lexer {
     }
 
     fn read_string(&mut self) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.read_string_bytes()?).to_string())
+    }
+
+    fn read_string_bytes(&mut self) -> Result<Vec<u8>> {
         self.read_char();
 
         let pos = self.position;
         while self.ch != b'"' {
             self.read_char();
             if self.ch == 0 {
-                bail!("String is not properly closed!")
+                bail!(
+                    "String is not properly closed at {}!",
+                    self.location(pos - 1)
+                )
             }
         }
         self.read_char();
 
-        Ok(String::from_utf8_lossy(&self.input[pos..self.position - 1]).to_string())
+        Ok(self.slice(pos, self.position - 1).to_vec())
     }
 
     fn read_identifier(&mut self) -> String {
@@ -147,45 +567,81 @@ impl Lexer {
         while self.ch.is_ascii_alphabetic() || self.ch == b'_' {
             self.read_char();
         }
-        String::from_utf8_lossy(&self.input[pos..self.position]).to_string()
+        String::from_utf8_lossy(self.slice(pos, self.position)).to_string()
     }
 
-    fn skip_whitespace(&mut self) {
+    /// Returns whether a line break was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let mut line_break = false;
         while self.ch.is_ascii_whitespace() {
+            line_break |= self.ch == b'\n';
             self.read_char();
         }
+        line_break
     }
 
-    fn read_int(&mut self) -> i64 {
+    /// An int, or a float when the digits go on after a `.`, so `1..2` is still a range.
+    fn read_number(&mut self) -> Result<Token> {
         let pos = self.position;
         while self.ch.is_ascii_digit() {
             self.read_char();
         }
-        String::from_utf8_lossy(&self.input[pos..self.position])
-            .to_string()
-            .parse()
-            .unwrap()
+
+        if self.ch == b'.' && self.peek().is_ascii_digit() {
+            self.read_char();
+            while self.ch.is_ascii_digit() {
+                self.read_char();
+            }
+            let literal = String::from_utf8_lossy(self.slice(pos, self.position)).to_string();
+            return Ok(Token::Float(literal.parse()?));
+        }
+
+        let literal = String::from_utf8_lossy(self.slice(pos, self.position)).to_string();
+        match literal.parse() {
+            Ok(num) => Ok(Token::Int(num)),
+            Err(_) => bail!(
+                "Integer literal {} is too large at {}!",
+                literal,
+                self.location(pos)
+            ),
+        }
+    }
+
+    /// Human readable line and column (both starting at 1) of a byte offset, for error messages.
+    fn location(&self, pos: usize) -> String {
+        let before = self.slice(self.offset, pos);
+        let line_start = before
+            .iter()
+            .rposition(|&ch| ch == b'\n')
+            .map_or(0, |newline| newline + 1);
+
+        format!(
+            "line {}, column {}",
+            self.lines_dropped + before.iter().filter(|&&ch| ch == b'\n').count() + 1,
+            String::from_utf8_lossy(&before[line_start..])
+                .chars()
+                .count()
+                + 1
+        )
     }
 
     fn peek(&self) -> u8 {
-        if self.read_position >= self.input.len() {
-            0
-        } else {
-            self.input[self.read_position]
-        }
+        self.byte(self.read_position)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::io::{self, Read};
+
     use anyhow::{Ok, Result};
 
-    use super::{Lexer, Token};
+    use super::{tokenize, Lexer, Token, TokenKind, Trivia, CHUNK_SIZE};
 
     #[test]
     fn get_next_token() -> Result<()> {
-        let input = "=+(){},;";
-        let mut lexer = Lexer::new(input.into());
+        let input = r#"=+(){},;[]:|>.....=.. === !== // => 1.25 1..2 b"hi" in !in !inner"#;
+        let mut lexer = Lexer::new(input);
 
         let tokens = vec![
             Token::Assign,
@@ -196,6 +652,26 @@ mod test {
             Token::RSquirly,
             Token::Comma,
             Token::Semicolon,
+            Token::LBracket,
+            Token::RBracket,
+            Token::Colon,
+            Token::Pipe,
+            Token::Spread,
+            Token::DotDotEq,
+            Token::DotDot,
+            Token::StrictEqual,
+            Token::StrictNotEqual,
+            Token::DoubleSlash,
+            Token::Arrow,
+            Token::Float(1.25),
+            Token::Int(1),
+            Token::DotDot,
+            Token::Int(2),
+            Token::Bytes(b"hi".to_vec()),
+            Token::In,
+            Token::NotIn,
+            Token::Bang,
+            Token::Ident(String::from("inner")),
         ];
 
         for token in tokens {
@@ -205,6 +681,38 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn tokenizes_invalid_source() {
+        let source = "let s = \"ñ\" @ 1;\nif x !in [2] { ñ } \"open";
+        let tokens = tokenize(source)
+            .into_iter()
+            .map(|(kind, span)| (kind, &source[span]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Keyword, "let"),
+                (TokenKind::Identifier, "s"),
+                (TokenKind::Operator, "="),
+                (TokenKind::String, "\"ñ\""),
+                (TokenKind::Error, "@"),
+                (TokenKind::Number, "1"),
+                (TokenKind::Punctuation, ";"),
+                (TokenKind::Keyword, "if"),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Keyword, "!in"),
+                (TokenKind::Punctuation, "["),
+                (TokenKind::Number, "2"),
+                (TokenKind::Punctuation, "]"),
+                (TokenKind::Punctuation, "{"),
+                (TokenKind::Error, "ñ"),
+                (TokenKind::Punctuation, "}"),
+                (TokenKind::Error, "\"open"),
+            ]
+        );
+    }
+
     #[test]
     fn get_next_complete() -> Result<()> {
         let input = r#"let five = 5;
@@ -230,7 +738,7 @@ mod test {
         "foobar"
         "foo bar""#;
 
-        let mut lexer = Lexer::new(input.into());
+        let mut lexer = Lexer::new(input);
         let tokens = vec![
             Token::Let,
             Token::Ident(String::from("five")),
@@ -297,6 +805,7 @@ mod test {
             Token::Bool(false),
             Token::Semicolon,
             Token::RSquirly,
+            Token::Newline,
             Token::Int(10),
             Token::Equal,
             Token::Int(10),
@@ -306,6 +815,7 @@ mod test {
             Token::Int(9),
             Token::Semicolon,
             Token::String("foobar".into()),
+            Token::Newline,
             Token::String("foo bar".into()),
             Token::Eof,
         ];
@@ -318,4 +828,220 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn newlines() -> Result<()> {
+        let input = "let x = 1
+        let y = x +
+            2
+        add(x,
+            y)
+        if (x) { x
+        }
+        else { y }
+        [1]
+        x
+            + 1
+            - 2
+        -x
+        ";
+        let mut lexer = Lexer::new(input);
+
+        let tokens = vec![
+            Token::Let,
+            Token::Ident(String::from("x")),
+            Token::Assign,
+            Token::Int(1),
+            Token::Newline,
+            Token::Let,
+            Token::Ident(String::from("y")),
+            Token::Assign,
+            Token::Ident(String::from("x")),
+            Token::Plus,
+            Token::Int(2),
+            Token::Newline,
+            Token::Ident(String::from("add")),
+            Token::Lparen,
+            Token::Ident(String::from("x")),
+            Token::Comma,
+            Token::Ident(String::from("y")),
+            Token::Rparen,
+            Token::Newline,
+            Token::If,
+            Token::Lparen,
+            Token::Ident(String::from("x")),
+            Token::Rparen,
+            Token::LSquirly,
+            Token::Ident(String::from("x")),
+            Token::RSquirly,
+            Token::Else,
+            Token::LSquirly,
+            Token::Ident(String::from("y")),
+            Token::RSquirly,
+            Token::Newline,
+            Token::LBracket,
+            Token::Int(1),
+            Token::RBracket,
+            Token::Newline,
+            Token::Ident(String::from("x")),
+            Token::Plus,
+            Token::Int(1),
+            Token::Minus,
+            Token::Int(2),
+            Token::Newline,
+            Token::Minus,
+            Token::Ident(String::from("x")),
+            Token::Newline,
+            Token::Eof,
+        ];
+
+        for token in tokens {
+            assert_eq!(token, lexer.next_token()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_report_location() -> Result<()> {
+        let errors = [
+            (
+                "let x = 99999999999999999999;",
+                "Integer literal 99999999999999999999 is too large at line 1, column 9!",
+            ),
+            (
+                "let x = 1;\n  let y = 18446744073709551616;",
+                "Integer literal 18446744073709551616 is too large at line 2, column 11!",
+            ),
+            (
+                "puts(1);\n\"ñandú\" + \"oops",
+                "String is not properly closed at line 2, column 11!",
+            ),
+            (
+                "1 +\n  @",
+                "No program should contain this token: @ at line 2, column 3",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let mut lexer = Lexer::new(input);
+            let error = loop {
+                match lexer.next_token() {
+                    Err(error) => break error,
+                    token => assert_ne!(token?, Token::Eof, "expected an error for {:?}", input),
+                }
+            };
+            assert_eq!(error.to_string(), expected);
+        }
+
+        let mut lexer = Lexer::new("9223372036854775807");
+        assert_eq!(lexer.next_token()?, Token::Int(i64::MAX));
+
+        Ok(())
+    }
+
+    /// Hands out its bytes a few at a time, like a slow pipe, then optionally fails.
+    struct Trickle {
+        bytes: Vec<u8>,
+        position: usize,
+        fail: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.position == self.bytes.len() && self.fail {
+                return Err(io::Error::other("connection reset"));
+            }
+            let len = buf.len().min(3).min(self.bytes.len() - self.position);
+            buf[..len].copy_from_slice(&self.bytes[self.position..self.position + len]);
+            self.position += len;
+            io::Result::Ok(len)
+        }
+    }
+
+    fn trickle(source: &str, fail: bool) -> Lexer {
+        Lexer::from_reader(Trickle {
+            bytes: source.as_bytes().to_vec(),
+            position: 0,
+            fail,
+        })
+    }
+
+    #[test]
+    fn reads_streams() -> Result<()> {
+        let line = "let s = \"ñandú\" + b\"x\"; if (a === 1..=2) { x } else { y !== 1.5 } |> f\n";
+        let source = format!("{}let big = 99999999999999999999", line.repeat(500));
+        assert!(source.len() > 4 * CHUNK_SIZE);
+
+        let mut streamed = trickle(&source, false);
+        let mut lexer = Lexer::new(&source);
+        let error = loop {
+            match (lexer.next_token(), streamed.next_token()) {
+                (Err(error), Err(streamed)) => break (error.to_string(), streamed.to_string()),
+                (token, streamed_token) => {
+                    let token = token?;
+                    assert_eq!(token, streamed_token?);
+                    assert_eq!(lexer.span(), streamed.span());
+                    assert_ne!(token, Token::Eof);
+                }
+            }
+        };
+        assert_eq!(error.0, error.1);
+        assert_eq!(
+            error.1,
+            "Integer literal 99999999999999999999 is too large at line 501, column 11!"
+        );
+        assert!(streamed.input.len() < 2 * CHUNK_SIZE);
+
+        let mut streamed = trickle("let x = 1", true);
+        for token in [
+            Token::Let,
+            Token::Ident(String::from("x")),
+            Token::Assign,
+            Token::Int(1),
+        ] {
+            assert_eq!(streamed.next_token()?, token);
+        }
+        assert_eq!(
+            streamed.next_token().unwrap_err().to_string(),
+            "Cannot read the program: connection reset!"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_trivia() -> Result<()> {
+        let input = "  let x = [1,\n\t2]\n\n  x |> f  \n";
+        let mut lexer = Lexer::new(input).with_trivia();
+        let mut rebuilt = String::new();
+        let mut trivia = vec![];
+        loop {
+            let token = lexer.next_token()?;
+            trivia.extend(lexer.trivia().iter().cloned());
+            for piece in lexer.trivia() {
+                rebuilt += &piece.text;
+                assert_eq!(&input[piece.span.clone()], piece.text);
+            }
+            rebuilt += &input[lexer.span()];
+            if token == Token::Eof {
+                break;
+            }
+        }
+        assert_eq!(rebuilt, input);
+        assert_eq!(
+            trivia.first(),
+            Some(&Trivia {
+                text: String::from("  "),
+                span: 0..2,
+            })
+        );
+        assert_eq!(trivia.len(), 9);
+
+        let mut lexer = Lexer::new(" x");
+        lexer.next_token()?;
+        assert!(lexer.trivia().is_empty());
+
+        Ok(())
+    }
 }
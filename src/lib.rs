@@ -0,0 +1,16 @@
+pub mod arena;
+pub mod ast;
+pub mod config;
+pub mod eval;
+pub mod json_repl;
+pub mod lexer;
+pub mod node_id;
+pub mod parser;
+pub mod repl;
+pub mod script;
+pub mod share;
+pub mod symbol;
+pub mod visit;
+
+/// Version of the interpreter, as in Cargo.toml.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,15 +1,239 @@
-pub mod ast;
-pub mod eval;
-pub mod lexer;
-pub mod parser;
-pub mod repl;
+use anyhow::{bail, Context, Result};
 
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use interpreter::{
+    config::Config,
+    eval::{modules, Division, Eval, Options},
+    json_repl,
+    repl::{self, ReplOptions},
+    script,
+    share::{self, Snippet},
+    VERSION,
+};
+
+/// Stack for the thread running the REPL or script, large enough to reach the default maximum call depth.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() -> Result<()> {
-    println!("Hello world! This is the Monkey programming language!");
-    println!("Type in commands:");
-    repl::run()?;
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("share") {
+        let [_, path] = args.as_slice() else {
+            bail!("Usage: share <FILE>");
+        };
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path))?;
+        println!("{}", share::pack(&source));
+        return Ok(());
+    }
+
+    let watch = args.first().map(String::as_str) == Some("watch");
+    if watch {
+        args.remove(0);
+    }
+
+    // Which monkey.toml applies depends on the script, so find that first, then apply the
+    // flags over the file's settings.
+    let script = parse_args(&args, &mut Options::default(), &mut ReplOptions::default())?.script;
+    let dir = script
+        .as_deref()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new("."));
+    let config = match args.iter().any(|arg| arg == "--no-config") {
+        true => None,
+        false => Config::find(dir)?.map(|(_, config)| config),
+    };
+
+    let mut options = Options::default();
+    let mut repl_options = ReplOptions::default();
+    if let Some(config) = &config {
+        config.apply(&mut options, &mut repl_options);
+    }
+    let Args {
+        script,
+        snippet,
+        json,
+        keep_env,
+        interactive,
+    } = parse_args(&args, &mut options, &mut repl_options)?;
+
+    options.module_paths = modules::search_paths(dir);
+    if let Some(config) = config {
+        options.module_paths.splice(1..1, config.module_paths);
+    }
+
+    let thread = std::thread::Builder::new().stack_size(STACK_SIZE);
+
+    if watch {
+        let script = script.context("Usage: watch [OPTIONS] <FILE>")?;
+        return run_on(thread, move || script::watch(options, &script, keep_env))?;
+    }
+    if keep_env {
+        bail!("--keep-env only applies to watch");
+    }
+    if interactive && (script.is_none() || snippet.is_some()) {
+        bail!("--interactive only applies to scripts");
+    }
+
+    if let Some(snippet) = snippet {
+        if snippet.version != VERSION {
+            eprintln!(
+                "WARNING: snippet made with interpreter {}, running it with {}",
+                snippet.version, VERSION
+            );
+        }
+        let succeeded = run_on(thread, move || script::run_code(options, &snippet.source))?;
+        if !succeeded {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(script) = script {
+        if interactive {
+            // Like `python -i`, the REPL starts even if the script failed, and without the rc
+            // file, which could shadow the script's variables.
+            repl_options.rc_file = None;
+            return run_on(thread, move || {
+                let mut eval = Eval::with_options(options);
+                script::run_in(&mut eval, &script)?;
+                repl::run_with(eval, repl_options)
+            })?;
+        }
+
+        let succeeded = run_on(thread, move || script::run(options, &script))??;
+        if !succeeded {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if json {
+        return run_on(thread, move || json_repl::run(options))?;
+    }
+
+    run_on(thread, move || repl::run(options, repl_options))?
+}
+
+/// Runs `f` on the interpreter's thread and waits for it. A panic there is a bug, reported like
+/// the REPL reports one, rather than as a second panic here.
+fn run_on<T: Send + 'static>(
+    thread: std::thread::Builder,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T> {
+    match thread.spawn(f)?.join() {
+        Ok(value) => Ok(value),
+        Err(panic) => {
+            eprintln!("INTERNAL ERROR: {}", repl::panic_message(&*panic));
+            std::process::exit(1);
+        }
+    }
+}
+
+struct Args {
+    script: Option<PathBuf>,
+    snippet: Option<Snippet>,
+    json: bool,
+    keep_env: bool,
+    interactive: bool,
+}
+
+/// Applies the command line flags to `options` and `repl_options`, returning the other
+/// arguments.
+fn parse_args(
+    args: &[String],
+    options: &mut Options,
+    repl_options: &mut ReplOptions,
+) -> Result<Args> {
+    let mut script = None;
+    let mut snippet = None;
+    let mut json = false;
+    let mut keep_env = false;
+    let mut interactive = false;
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => options.strict = true,
+            "--strict-index" => options.strict_index = true,
+            "--profile" => options.profile = true,
+            "--time" => options.time = true,
+            "--no-rc" => repl_options.rc_file = None,
+            "--no-config" => {}
+            "--json-repl" => json = true,
+            "--keep-env" => keep_env = true,
+            "--interactive" => interactive = true,
+            "--allow-fs" => options.capabilities.fs.push(PathBuf::from("/")),
+            _ if arg.starts_with("--allow-fs=") => options
+                .capabilities
+                .fs
+                .push(PathBuf::from(&arg["--allow-fs=".len()..])),
+            "--allow-net" => options.capabilities.net = true,
+            "--allow-exec" => options.capabilities.exec = true,
+            "--allow-env" => options.capabilities.env = true,
+            "--deny-time" => options.capabilities.time = false,
+            "--max-call-depth" => {
+                options.max_call_depth = args
+                    .next()
+                    .context("Missing value for --max-call-depth")?
+                    .parse()
+                    .context("Invalid value for --max-call-depth")?
+            }
+            "--max-array-len" => {
+                options.limits.max_array_len = args
+                    .next()
+                    .context("Missing value for --max-array-len")?
+                    .parse()
+                    .context("Invalid value for --max-array-len")?
+            }
+            "--max-hash-len" => {
+                options.limits.max_hash_len = args
+                    .next()
+                    .context("Missing value for --max-hash-len")?
+                    .parse()
+                    .context("Invalid value for --max-hash-len")?
+            }
+            "--max-string-len" => {
+                options.limits.max_string_len = args
+                    .next()
+                    .context("Missing value for --max-string-len")?
+                    .parse()
+                    .context("Invalid value for --max-string-len")?
+            }
+            "--max-env-entries" => {
+                options.limits.max_env_entries = args
+                    .next()
+                    .context("Missing value for --max-env-entries")?
+                    .parse()
+                    .context("Invalid value for --max-env-entries")?
+            }
+            "--division" => {
+                options.division = match args.next().as_deref() {
+                    Some("truncate") => Division::Truncate,
+                    Some("floor") => Division::Floor,
+                    Some("float") => Division::Float,
+                    Some(other) => bail!("Invalid value for --division: {}", other),
+                    None => bail!("Missing value for --division"),
+                }
+            }
+            "--from-share" => {
+                snippet = Some(share::unpack(
+                    &args.next().context("Missing value for --from-share")?,
+                )?)
+            }
+            "--prompt" => {
+                repl_options.prompt = args.next().context("Missing value for --prompt")?
+            }
+            _ if !arg.starts_with('-') && script.is_none() => script = Some(PathBuf::from(arg)),
+            _ => bail!("Unknown argument: {}", arg),
+        }
+    }
 
-    Ok(())
+    Ok(Args {
+        script,
+        snippet,
+        json,
+        keep_env,
+        interactive,
+    })
 }
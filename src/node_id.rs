@@ -0,0 +1,166 @@
+//! Identifiers for statements and expressions, so passes can keep what they learn about nodes
+//! in side tables instead of adding fields to the AST.
+//!
+//! Ids are positions rather than fields stored in the nodes: within a block, statements and
+//! expressions are numbered from 0 in the order `Visit` reaches them, parents before their
+//! children. Parsing the same source always gives the same ids, and a pass implementing `Visit`
+//! itself gets them by taking the next number at the start of `visit_statement` and
+//! `visit_expression`, before walking into the node.
+
+use std::collections::{btree_map, BTreeMap};
+
+use crate::{
+    ast::{BlockStatement, Expression, Statement},
+    visit::{self, Visit},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub u32);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Statement(&'a Statement),
+    Expression(&'a Expression),
+}
+
+/// Calls `f` with every statement and expression in `block`, along with its id.
+pub fn number(block: &BlockStatement, f: impl FnMut(NodeId, Node)) {
+    Numbering { next: 0, f }.visit_block(block);
+}
+
+struct Numbering<F> {
+    next: u32,
+    f: F,
+}
+
+impl<F: FnMut(NodeId, Node)> Visit for Numbering<F> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        (self.f)(NodeId(self.next), Node::Statement(statement));
+        self.next += 1;
+        visit::visit_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        (self.f)(NodeId(self.next), Node::Expression(expression));
+        self.next += 1;
+        visit::visit_expression(self, expression);
+    }
+}
+
+/// What a pass found out about nodes, by id.
+#[derive(Debug, Clone)]
+pub struct SideTable<T> {
+    entries: BTreeMap<NodeId, T>,
+}
+
+impl<T> Default for SideTable<T> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> SideTable<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `value` for `id`, returning what was stored for it before.
+    pub fn insert(&mut self, id: NodeId, value: T) -> Option<T> {
+        self.entries.insert(id, value)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.entries.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.entries.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        self.entries.remove(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries, in order of id.
+    pub fn iter(&self) -> btree_map::Iter<'_, NodeId, T> {
+        self.entries.iter()
+    }
+}
+
+impl<T> FromIterator<(NodeId, T)> for SideTable<T> {
+    fn from_iter<I: IntoIterator<Item = (NodeId, T)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use crate::{
+        ast::{Expression, Literal, Statement},
+        lexer::Lexer,
+        parser::Parser,
+    };
+
+    use super::{number, Node, NodeId, SideTable};
+
+    #[test]
+    fn numbers_nodes() -> Result<()> {
+        let program = Parser::new(Lexer::new("let x = 1 + 2; f(x)"))
+            .parse_program()?
+            .into_iter()
+            .collect::<Result<Vec<Statement>>>()?;
+
+        let mut nodes = vec![];
+        number(&program, |id, node| {
+            nodes.push(match node {
+                Node::Statement(statement) => format!("{} {}", id.0, statement),
+                Node::Expression(expression) => format!("{} {}", id.0, expression),
+            })
+        });
+        assert_eq!(
+            nodes,
+            [
+                "0 let x = (1 + 2)",
+                "1 (1 + 2)",
+                "2 1",
+                "3 2",
+                "4 f(x)",
+                "5 f(x)",
+                "6 f",
+                "7 x",
+            ]
+        );
+
+        let mut ints = SideTable::new();
+        number(&program, |id, node| {
+            if let Node::Expression(Expression::Literal(Literal::Int(int))) = node {
+                ints.insert(id, *int);
+            }
+        });
+        assert_eq!(ints.len(), 2);
+        assert_eq!(ints.get(NodeId(3)), Some(&2));
+        assert_eq!(ints.get(NodeId(4)), None);
+        assert_eq!(
+            ints.iter()
+                .map(|(id, int)| (id.0, *int))
+                .collect::<Vec<_>>(),
+            [(2, 1), (3, 2)]
+        );
+
+        Ok(())
+    }
+}
@@ -1,19 +1,92 @@
-use std::mem::take;
+use std::{collections::VecDeque, fmt::Display, ops::Range};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
 use crate::{
+    arena::{self, Arena},
     ast::{
-        BlockStatement, Expression, Identifier, IfExpression, Infix, Literal, Precedence, Prefix,
-        Program, Statement,
+        BlockStatement, Expression, Identifier, IfExpression, Imports, Infix, Literal, Pattern,
+        Precedence, Prefix, Program, Statement,
     },
     lexer::{Lexer, Token},
+    symbol::Symbol,
 };
 
+/// Deepest nesting of expressions and patterns accepted before giving up, so
+/// hostile input errors out instead of overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+/// A parse error along with where in the source it was found.
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub message: String,
+    /// Byte offsets of the tokens being looked at when parsing failed.
+    pub span: Range<usize>,
+    /// A likely fix, such as closing a bracket.
+    pub hint: Option<String>,
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+impl SyntaxError {
+    /// The source line containing the error with `^` under the span, followed by the hint.
+    pub fn excerpt(&self, source: &str) -> String {
+        let mut start = self.span.start.min(source.len());
+        if start == source.len() && source.ends_with('\n') {
+            start -= 1;
+        }
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |end| start + end);
+        let end = self.span.end.clamp(start, line_end);
+
+        let number = (source[..start].matches('\n').count() + 1).to_string();
+        let margin = " ".repeat(number.len());
+        let mut excerpt = format!(
+            "{} | {}\n{} | {}{}",
+            number,
+            &source[line_start..line_end],
+            margin,
+            " ".repeat(source[line_start..start].chars().count()),
+            "^".repeat(source[start..end].chars().count().max(1))
+        );
+        if let Some(hint) = &self.hint {
+            excerpt += &format!("\n{} = hint: {}", margin, hint);
+        }
+
+        excerpt
+    }
+}
+
+/// A token read ahead of the current one.
+struct Lexed {
+    token: Token,
+    span: Range<usize>,
+    /// What `Lexer::open_bracket` returned right after lexing the token.
+    open_bracket: Option<char>,
+}
+
 pub struct Parser {
     lexer: Lexer,
     current_token: Token,
-    peek_token: Token,
+    current_span: Range<usize>,
+    /// Tokens after the current one, nearest first. Holds at least the peek token once parsing
+    /// started, and more when `peek_nth` looked further ahead.
+    lookahead: VecDeque<Lexed>,
+    depth: usize,
+    /// Whether a `yield` was found in the function body being parsed, `None` outside functions.
+    yields: Option<bool>,
+    /// Whether an `await` was found outside of any function.
+    awaits: bool,
+    /// Byte offsets of each statement `parse_program` returned.
+    statement_spans: Vec<Range<usize>>,
 }
 
 impl Parser {
@@ -21,19 +94,129 @@ impl Parser {
         Self {
             lexer,
             current_token: Token::default(),
-            peek_token: Token::default(),
+            current_span: 0..0,
+            lookahead: VecDeque::new(),
+            depth: 0,
+            yields: None,
+            awaits: false,
+            statement_spans: vec![],
         }
     }
 
+    /// Whether the statements parsed so far await outside of any function, so running them can
+    /// suspend at the top level.
+    pub fn awaits(&self) -> bool {
+        self.awaits
+    }
+
+    /// Where in the source each statement parsed so far is, in the order they were returned,
+    /// including any `;` ending them.
+    pub fn statement_spans(&self) -> &[Range<usize>] {
+        &self.statement_spans
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            bail!("Program is nested too deeply!");
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
     fn next_token(&mut self) -> Result<()> {
-        self.current_token = take(&mut self.peek_token);
-        self.peek_token = self.lexer.next_token()?;
+        let next = match self.lookahead.pop_front() {
+            Some(next) => next,
+            None => self.lex()?,
+        };
+        self.current_token = next.token;
+        self.current_span = next.span;
+        self.peek_nth(1)?;
         Ok(())
     }
 
+    fn lex(&mut self) -> Result<Lexed> {
+        let token = self.lexer.next_token().map_err(|error| SyntaxError {
+            message: error.to_string(),
+            span: self.lexer.span(),
+            hint: None,
+        })?;
+        Ok(Lexed {
+            token,
+            span: self.lexer.span(),
+            open_bracket: self.lexer.open_bracket(),
+        })
+    }
+
+    /// The `n`th token after the current one, lexing ahead as far as needed: `peek_nth(0)` is
+    /// the current token and `peek_nth(1)` the peek token. Past the end of the source, it's `Eof`.
+    fn peek_nth(&mut self, n: usize) -> Result<&Token> {
+        if n == 0 {
+            return Ok(&self.current_token);
+        }
+        while self.lookahead.len() < n {
+            if self
+                .lookahead
+                .back()
+                .is_some_and(|last| last.token == Token::Eof)
+            {
+                return Ok(&Token::Eof);
+            }
+            let lexed = self.lex()?;
+            self.lookahead.push_back(lexed);
+        }
+        Ok(&self.lookahead[n - 1].token)
+    }
+
+    /// The token after the current one, `Illegal` if lexing it failed.
+    fn peek_token(&self) -> &Token {
+        self.lookahead
+            .front()
+            .map_or(&Token::Illegal, |next| &next.token)
+    }
+
+    fn peek_span(&self) -> Range<usize> {
+        self.lookahead
+            .front()
+            .map_or(0..0, |next| next.span.clone())
+    }
+
+    /// Locates an error from parsing a statement at the tokens the parser stopped at.
+    fn locate(&self, error: anyhow::Error) -> anyhow::Error {
+        if error.is::<SyntaxError>() {
+            return error;
+        }
+
+        let open_bracket = self
+            .lookahead
+            .front()
+            .map_or(self.lexer.open_bracket(), |next| next.open_bracket);
+        let hint = match (self.peek_token(), open_bracket) {
+            (Token::Eof, Some(bracket)) | (Token::Semicolon, Some(bracket @ ('(' | '['))) => {
+                let closing = match bracket {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                Some(format!("did you forget a closing `{}`?", closing))
+            }
+            _ => None,
+        };
+
+        SyntaxError {
+            message: error.to_string(),
+            span: self.current_span.start..self.peek_span().end,
+            hint,
+        }
+        .into()
+    }
+
     fn parse_ident(&mut self) -> Result<Identifier> {
         match &self.current_token {
-            Token::Ident(name) => Ok(Identifier(name.clone())),
+            Token::Ident(name) => Ok(Identifier(Symbol::intern(name))),
             _ => bail!("Failed to parse identifier!"),
         }
     }
@@ -49,6 +232,13 @@ impl Parser {
         }
     }
 
+    fn parse_bytes_expr(&mut self) -> Result<Expression> {
+        match &self.current_token {
+            Token::Bytes(bytes) => Ok(Expression::Literal(Literal::Bytes(bytes.clone()))),
+            _ => bail!("Failed to parse bytes!"),
+        }
+    }
+
     fn parse_int_expr(&mut self) -> Result<Expression> {
         match self.current_token {
             Token::Int(num) => Ok(Expression::Literal(Literal::Int(num))),
@@ -56,13 +246,17 @@ impl Parser {
         }
     }
 
+    fn parse_float_expr(&mut self) -> Result<Expression> {
+        match self.current_token {
+            Token::Float(num) => Ok(Expression::Literal(Literal::Float(num))),
+            _ => bail!("Failed to parse float"),
+        }
+    }
+
     fn parse_let_statement(&mut self) -> Result<Statement> {
         self.next_token()?;
 
-        let name = match self.current_token {
-            Token::Ident(_) => self.parse_ident(),
-            _ => bail!("Missing indentifier in let statement"),
-        };
+        let pattern = self.parse_pattern();
 
         self.next_token()?;
         if self.current_token != Token::Assign {
@@ -71,11 +265,55 @@ impl Parser {
 
         self.next_token()?;
         Ok(Statement::Let(
-            name?,
+            pattern?,
             self.parse_expression(Precedence::Lowest)?,
         ))
     }
 
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        self.nested(Self::parse_nested_pattern)
+    }
+
+    fn parse_nested_pattern(&mut self) -> Result<Pattern> {
+        match self.current_token {
+            Token::Ident(_) => Ok(Pattern::Identifier(self.parse_ident()?)),
+            Token::LBracket => Ok(Pattern::Array(self.parse_pattern_list(Token::RBracket)?)),
+            Token::Lparen => Ok(Pattern::Tuple(self.parse_pattern_list(Token::Rparen)?)),
+            Token::LSquirly => {
+                let mut names = vec![];
+
+                self.next_token()?;
+                while self.current_token != Token::RSquirly {
+                    names.push(self.parse_ident()?);
+
+                    self.next_token()?;
+                    if self.current_token == Token::Comma {
+                        self.next_token()?;
+                    }
+                }
+
+                Ok(Pattern::Hash(names))
+            }
+            _ => bail!("Missing indentifier in let statement"),
+        }
+    }
+
+    fn parse_pattern_list(&mut self, end: Token) -> Result<Vec<Pattern>> {
+        let mut patterns = vec![];
+
+        self.next_token()?;
+        while self.current_token != end {
+            patterns.push(self.parse_pattern()?);
+
+            self.next_token()?;
+            if self.current_token == Token::Comma {
+                self.next_token()?;
+            }
+        }
+
+        Ok(patterns)
+    }
+
     fn parse_return_statement(&mut self) -> Result<Statement> {
         self.next_token()?;
 
@@ -84,6 +322,65 @@ impl Parser {
         ))
     }
 
+    fn parse_yield_statement(&mut self) -> Result<Statement> {
+        match &mut self.yields {
+            Some(yields) => *yields = true,
+            None => bail!("Cannot yield outside of a function!"),
+        }
+
+        self.next_token()?;
+
+        Ok(Statement::Yield(self.parse_expression(Precedence::Lowest)?))
+    }
+
+    fn parse_import_statement(&mut self) -> Result<Statement> {
+        let Token::String(name) = self.peek_token() else {
+            bail!("Expected a module name in quotes after import!");
+        };
+        let name = name.clone();
+        self.next_token()?;
+
+        if !matches!(self.peek_token(), Token::Ident(word) if word == "as") {
+            return Ok(Statement::Import(name, Imports::All));
+        }
+        self.next_token()?;
+        self.next_token()?;
+        let alias = self
+            .parse_ident()
+            .map_err(|_| anyhow!("Expected a name for the module after as!"))?;
+
+        Ok(Statement::Import(name, Imports::Module(alias)))
+    }
+
+    /// `from "name" import a, b`, where `from` is only a keyword before a module name.
+    fn parse_from_import_statement(&mut self) -> Result<Statement> {
+        self.next_token()?;
+        let Token::String(name) = &self.current_token else {
+            unreachable!("from is only parsed as an import before a string");
+        };
+        let name = name.clone();
+
+        if *self.peek_token() != Token::Import {
+            bail!("Expected import after the module name!");
+        }
+        self.next_token()?;
+
+        let mut names = vec![];
+        loop {
+            self.next_token()?;
+            names.push(
+                self.parse_ident()
+                    .map_err(|_| anyhow!("Expected a name to import!"))?,
+            );
+            if *self.peek_token() != Token::Comma {
+                break;
+            }
+            self.next_token()?;
+        }
+
+        Ok(Statement::Import(name, Imports::Names(names)))
+    }
+
     fn parse_block_statement(&mut self) -> Result<BlockStatement> {
         if self.current_token != Token::LSquirly {
             bail!("Failed to parse block statement!");
@@ -92,29 +389,49 @@ impl Parser {
         self.next_token()?;
 
         let mut block = BlockStatement::new();
-
-        while self.current_token != Token::RSquirly && self.current_token != Token::Semicolon {
-            block.push(self.parse_statement()?);
+        let mut semicolon = false;
+
+        while self.current_token != Token::RSquirly {
+            if self.current_token == Token::Semicolon {
+                semicolon = true;
+            } else {
+                block.push(self.parse_statement()?);
+                semicolon = self.current_token == Token::Semicolon;
+            }
             self.next_token()?;
         }
+        if semicolon && !block.is_empty() {
+            block.push(Statement::Empty);
+        }
 
         Ok(block)
     }
 
+    /// `if (condition) { ... } else { ... }`. The parentheses are optional: they are just a
+    /// grouped expression, so `if x < y { x }` works too.
     fn parse_if_expr(&mut self) -> Result<Expression> {
         self.next_token()?;
+        if self.current_token == Token::LSquirly {
+            bail!("Expected a condition after if!");
+        }
 
-        let condition = self.parse_expression(Precedence::Lowest);
-
-        if self.current_token == Token::Rparen {
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.next_token()?;
+        while self.current_token == Token::Newline {
             self.next_token()?;
         }
+        if self.current_token != Token::LSquirly {
+            bail!(
+                "Expected {{ after the if condition, found {:?}!",
+                self.current_token
+            );
+        }
 
         let consequence = self.parse_block_statement();
-        self.next_token()?;
 
-        let alternative = match self.current_token {
+        let alternative = match *self.peek_token() {
             Token::Else => {
+                self.next_token()?;
                 self.next_token()?;
                 self.parse_block_statement()
             }
@@ -122,7 +439,7 @@ impl Parser {
         };
 
         Ok(Expression::If(IfExpression {
-            condition: Box::new(condition?),
+            condition: Box::new(condition),
             consequence: consequence?,
             alternative: alternative?,
         }))
@@ -144,6 +461,16 @@ impl Parser {
         Ok(params)
     }
 
+    /// Checked once the whole function is parsed, so parsing resumes after it.
+    fn check_parameters(params: &[Identifier]) -> Result<()> {
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].contains(param) {
+                bail!("Duplicate parameter {}!", param.0);
+            }
+        }
+        Ok(())
+    }
+
     fn parse_function_expr(&mut self) -> Result<Expression> {
         self.next_token()?;
 
@@ -158,16 +485,80 @@ impl Parser {
             bail!("Failed to parse function body!");
         }
 
-        let body = self.parse_block_statement()?;
+        let outer = self.yields.replace(false);
+        let body = self.parse_block_statement();
+        let generator = std::mem::replace(&mut self.yields, outer) == Some(true);
+        let body = body?;
+        Self::check_parameters(&params)?;
 
-        Ok(Expression::Function { params, body })
+        Ok(Expression::Function {
+            params,
+            body,
+            generator,
+        })
     }
 
-    fn parse_call_args(&mut self) -> Result<Vec<Expression>> {
-        let mut args = vec![];
+    /// Whether the `(` at the current token opens the parameters of an arrow function, as in
+    /// `(x, y) => x + y`, rather than a grouped expression or a tuple.
+    fn at_arrow_params(&mut self) -> Result<bool> {
+        let mut n = 1;
+        loop {
+            match self.peek_nth(n)? {
+                Token::Ident(_) => {}
+                Token::Rparen => return Ok(*self.peek_nth(n + 1)? == Token::Arrow),
+                _ => return Ok(false),
+            }
+            match self.peek_nth(n + 1)? {
+                Token::Comma => n += 2,
+                Token::Rparen => return Ok(*self.peek_nth(n + 2)? == Token::Arrow),
+                _ => return Ok(false),
+            }
+        }
+    }
 
-        while self.current_token != Token::Rparen {
-            args.push(self.parse_expression(Precedence::Lowest)?);
+    /// `x => body` or `(x, y) => body`, sugar for `fn(x, y) { body }`. The body is a single
+    /// expression, so `x => { "key": x }` returns a hash.
+    fn parse_arrow_expr(&mut self) -> Result<Expression> {
+        let params = if self.current_token == Token::Lparen {
+            self.next_token()?;
+            self.parse_function_parameters()?
+        } else {
+            let param = self.parse_ident()?;
+            self.next_token()?;
+            vec![param]
+        };
+        self.next_token()?;
+
+        let outer = self.yields.replace(false);
+        let body = self.parse_expression(Precedence::Lowest);
+        let generator = std::mem::replace(&mut self.yields, outer) == Some(true);
+        let body = body?;
+        Self::check_parameters(&params)?;
+
+        Ok(Expression::Function {
+            params,
+            body: vec![Statement::Expression(body)],
+            generator,
+        })
+    }
+
+    /// An array element or positional argument, which can be spread.
+    fn parse_element(&mut self) -> Result<Expression> {
+        if self.current_token == Token::Spread {
+            self.next_token()?;
+            return Ok(Expression::Spread(Box::new(
+                self.parse_expression(Precedence::Lowest)?,
+            )));
+        }
+
+        self.parse_expression(Precedence::Lowest)
+    }
+
+    fn parse_expression_list(&mut self, end: Token) -> Result<Vec<Expression>> {
+        let mut list = vec![];
+
+        while self.current_token != end {
+            list.push(self.parse_element()?);
 
             self.next_token()?;
             if self.current_token == Token::Comma {
@@ -175,45 +566,199 @@ impl Parser {
             }
         }
 
-        Ok(args)
+        Ok(list)
     }
 
     fn parse_call_expr(&mut self, function: Expression) -> Result<Expression> {
         self.next_token()?;
 
-        let args = self.parse_call_args()?;
+        let mut args = vec![];
+        let mut named = vec![];
+        // Reported once the whole list is parsed, so parsing resumes after the call.
+        let mut error = None;
+
+        while self.current_token != Token::Rparen {
+            if let (Token::Ident(name), Token::Colon) = (&self.current_token, self.peek_token()) {
+                let name = Identifier(Symbol::intern(name));
+                if named.iter().any(|(other, _)| *other == name) {
+                    error.get_or_insert_with(|| format!("Duplicate named argument {}!", name.0));
+                }
+                self.next_token()?;
+                self.next_token()?;
+                named.push((name, self.parse_expression(Precedence::Lowest)?));
+            } else if named.is_empty() {
+                args.push(self.parse_element()?);
+            } else {
+                self.parse_element()?;
+                error.get_or_insert_with(|| {
+                    String::from("Positional argument after named arguments!")
+                });
+            }
+
+            self.next_token()?;
+            if self.current_token == Token::Comma {
+                self.next_token()?;
+            }
+        }
+
+        if let Some(error) = error {
+            bail!(error);
+        }
 
         Ok(Expression::Call {
             function: Box::new(function),
             args,
+            named,
+        })
+    }
+
+    fn parse_array_expr(&mut self) -> Result<Expression> {
+        self.next_token()?;
+
+        Ok(Expression::Array(
+            self.parse_expression_list(Token::RBracket)?,
+        ))
+    }
+
+    fn parse_member_expr(&mut self, object: Expression) -> Result<Expression> {
+        self.next_token()?;
+
+        let attribute = self
+            .parse_ident()
+            .map_err(|_| anyhow!("Expected a name after .!"))?;
+
+        Ok(Expression::Member {
+            object: Box::new(object),
+            attribute,
+        })
+    }
+
+    fn parse_index_expr(&mut self, left: Expression) -> Result<Expression> {
+        self.next_token()?;
+
+        let index = self.parse_expression(Precedence::Lowest)?;
+
+        if *self.peek_token() != Token::RBracket {
+            bail!("Failed to parse index expression!");
+        }
+        self.next_token()?;
+
+        Ok(Expression::Index {
+            left: Box::new(left),
+            index: Box::new(index),
         })
     }
 
+    fn parse_assign_expr(&mut self, left: Expression) -> Result<Expression> {
+        let target = match left {
+            Expression::Identifier(id) => id,
+            _ => bail!("Invalid assignment target!"),
+        };
+
+        self.next_token()?;
+
+        Ok(Expression::Assign {
+            target,
+            value: Box::new(self.parse_expression(Precedence::Lowest)?),
+        })
+    }
+
+    /// `x |> f(y)` is sugar for `f(x, y)`, and `x |> f` for `f(x)`.
+    fn parse_pipe_expr(&mut self, left: Expression) -> Result<Expression> {
+        self.next_token()?;
+
+        Ok(match self.parse_expression(Precedence::Pipe)? {
+            Expression::Call {
+                function,
+                mut args,
+                named,
+            } => {
+                args.insert(0, left);
+                Expression::Call {
+                    function,
+                    args,
+                    named,
+                }
+            }
+            function @ (Expression::Identifier(_)
+            | Expression::Member { .. }
+            | Expression::Function { .. }) => Expression::Call {
+                function: Box::new(function),
+                args: vec![left],
+                named: vec![],
+            },
+            _ => bail!("Right side of |> must be a function call!"),
+        })
+    }
+
+    fn parse_hash_expr(&mut self) -> Result<Expression> {
+        let mut pairs = vec![];
+
+        while *self.peek_token() != Token::RSquirly {
+            self.next_token()?;
+            let key = self.parse_expression(Precedence::Lowest)?;
+
+            if *self.peek_token() != Token::Colon {
+                bail!("Missing colon after key in hash literal!");
+            }
+            self.next_token()?;
+            self.next_token()?;
+            let value = self.parse_expression(Precedence::Lowest)?;
+
+            pairs.push((key, value));
+
+            match *self.peek_token() {
+                Token::Comma => self.next_token()?,
+                Token::RSquirly => {}
+                _ => bail!("Failed to parse hash literal!"),
+            }
+        }
+        self.next_token()?;
+
+        Ok(Expression::Hash(pairs))
+    }
+
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression> {
+        self.nested(|parser| parser.parse_nested_expression(precedence))
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Result<Expression> {
         let mut expr = match self.current_token {
+            Token::Ident(_) if *self.peek_token() == Token::Arrow => self.parse_arrow_expr(),
             Token::Ident(_) => self.parse_ident_expr(),
             Token::Int(_) => self.parse_int_expr(),
+            Token::Float(_) => self.parse_float_expr(),
             Token::Bool(_) => self.parse_bool_expr(),
+            Token::Null => Ok(Expression::Literal(Literal::Null)),
             Token::Lparen => self.parse_grouped_expr(),
             Token::Plus | Token::Bang | Token::Minus => self.parse_prefix_expr(),
+            Token::Await => self.parse_await_expr(),
             Token::If => self.parse_if_expr(),
             Token::Function => self.parse_function_expr(),
             Token::String(_) => self.parse_string_expr(),
+            Token::Bytes(_) => self.parse_bytes_expr(),
+            Token::LSquirly => self.parse_hash_expr(),
+            Token::LBracket => self.parse_array_expr(),
             _ => bail!("Expression type {:?} is unhandled yet!", self.current_token),
         };
 
-        while self.peek_token != Token::Semicolon
-            && precedence < Self::get_precedence(&self.peek_token)
+        while *self.peek_token() != Token::Semicolon
+            && precedence < Self::get_precedence(self.peek_token())
         {
-            match self.peek_token {
+            match *self.peek_token() {
                 Token::Plus
                 | Token::Minus
                 | Token::Slash
+                | Token::DoubleSlash
                 | Token::Asterisk
                 | Token::Equal
                 | Token::NotEqual
+                | Token::StrictEqual
+                | Token::StrictNotEqual
                 | Token::Lt
-                | Token::Gt => {
+                | Token::Gt
+                | Token::In
+                | Token::NotIn => {
                     self.next_token()?;
                     expr = self.parse_infix_expr(expr?);
                 }
@@ -221,10 +766,34 @@ impl Parser {
                     self.next_token()?;
                     expr = self.parse_call_expr(expr?);
                 }
+                Token::LBracket => {
+                    self.next_token()?;
+                    expr = self.parse_index_expr(expr?);
+                }
+                Token::Dot => {
+                    self.next_token()?;
+                    expr = self.parse_member_expr(expr?);
+                }
+                Token::Assign => {
+                    self.next_token()?;
+                    expr = self.parse_assign_expr(expr?);
+                }
+                Token::Pipe => {
+                    self.next_token()?;
+                    expr = self.parse_pipe_expr(expr?);
+                }
+                Token::DotDot | Token::DotDotEq => {
+                    self.next_token()?;
+                    expr = self.parse_range_expr(expr?);
+                }
                 _ => bail!("Invalid expression!"),
             }
         }
 
+        if *self.peek_token() == Token::Arrow {
+            bail!("Arrow function parameters must be names!");
+        }
+
         expr
     }
 
@@ -238,10 +807,21 @@ impl Parser {
         let statement = match self.current_token {
             Token::Let => self.parse_let_statement(),
             Token::Return => self.parse_return_statement(),
+            Token::Yield => self.parse_yield_statement(),
+            Token::Import => self.parse_import_statement(),
+            Token::Ident(ref name)
+                if name == "from" && matches!(self.peek_token(), Token::String(_)) =>
+            {
+                self.parse_from_import_statement()
+            }
             _ => self.parse_expression_statement(),
-        };
+        }
+        .map_err(|error| self.locate(error));
 
-        if self.peek_token == Token::Semicolon || self.peek_token == Token::Eof {
+        if matches!(
+            self.peek_token(),
+            Token::Semicolon | Token::Newline | Token::Eof
+        ) {
             self.next_token()?;
         }
 
@@ -251,17 +831,65 @@ impl Parser {
     pub fn parse_program(&mut self) -> Result<Program> {
         let mut program = Program::new();
 
-        self.next_token()?;
         self.next_token()?;
 
         while self.current_token != Token::Eof {
+            let start = self.current_span.start;
             program.push(self.parse_statement());
+            self.statement_spans.push(start..self.current_span.end);
             self.next_token()?;
         }
 
         Ok(program)
     }
 
+    /// Like `parse_program`, laid out in an `Arena`. Fails on the first statement that doesn't
+    /// parse.
+    pub fn parse_arena(&mut self) -> Result<(Arena, arena::Block)> {
+        let program = self
+            .parse_program()?
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arena::from_block(&program))
+    }
+
+    /// Parses `source` as a lone expression, such as `x > 3`, for embedders evaluating
+    /// expressions outside of any program. Nothing but a `;` or line break may follow it.
+    pub fn parse_expression_str(source: &str) -> Result<Expression> {
+        let mut parser = Self::new(Lexer::new(source));
+        parser.next_token()?;
+
+        let expression = parser
+            .parse_expression(Precedence::Lowest)
+            .map_err(|error| parser.locate(error))?;
+        parser.next_token()?;
+        while matches!(parser.current_token, Token::Semicolon | Token::Newline) {
+            parser.next_token()?;
+        }
+        if parser.current_token != Token::Eof {
+            return Err(SyntaxError {
+                message: format!(
+                    "Expected the end of the expression, found {:?}!",
+                    parser.current_token
+                ),
+                span: parser.current_span.clone(),
+                hint: None,
+            }
+            .into());
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_await_expr(&mut self) -> Result<Expression> {
+        self.awaits |= self.yields.is_none();
+        self.next_token()?;
+
+        Ok(Expression::Await(Box::new(
+            self.parse_expression(Precedence::Prefix)?,
+        )))
+    }
+
     fn parse_prefix_expr(&mut self) -> Result<Expression> {
         let prefix = match self.current_token {
             Token::Bang => Prefix::Not,
@@ -280,25 +908,48 @@ impl Parser {
 
     fn get_precedence(token: &Token) -> Precedence {
         match token {
-            Token::Equal | Token::NotEqual => Precedence::Equals,
+            Token::Equal | Token::NotEqual | Token::StrictEqual | Token::StrictNotEqual => {
+                Precedence::Equals
+            }
             Token::Lt | Token::Gt => Precedence::LessGreater,
+            Token::In | Token::NotIn => Precedence::Membership,
+            Token::Pipe => Precedence::Pipe,
+            Token::DotDot | Token::DotDotEq => Precedence::Range,
             Token::Plus | Token::Minus => Precedence::Sum,
-            Token::Slash | Token::Asterisk => Precedence::Product,
+            Token::Slash | Token::DoubleSlash | Token::Asterisk => Precedence::Product,
             Token::Lparen => Precedence::Call,
+            Token::LBracket | Token::Dot => Precedence::Index,
+            Token::Assign => Precedence::Assign,
             _ => Precedence::Lowest,
         }
     }
 
+    fn parse_range_expr(&mut self, start: Expression) -> Result<Expression> {
+        let inclusive = self.current_token == Token::DotDotEq;
+        self.next_token()?;
+
+        Ok(Expression::Range {
+            start: Box::new(start),
+            end: Box::new(self.parse_expression(Precedence::Range)?),
+            inclusive,
+        })
+    }
+
     fn parse_infix_expr(&mut self, left: Expression) -> Result<Expression> {
         let infix = match self.current_token {
             Token::Plus => Infix::Plus,
             Token::Minus => Infix::Minus,
             Token::Slash => Infix::Divide,
+            Token::DoubleSlash => Infix::FloorDivide,
             Token::Asterisk => Infix::Product,
             Token::Equal => Infix::Equal,
             Token::NotEqual => Infix::NotEqual,
+            Token::StrictEqual => Infix::StrictEqual,
+            Token::StrictNotEqual => Infix::StrictNotEqual,
             Token::Lt => Infix::LessThan,
             Token::Gt => Infix::GreaterThan,
+            Token::In => Infix::In,
+            Token::NotIn => Infix::NotIn,
             _ => bail!("No valid infix operator"),
         };
 
@@ -320,11 +971,18 @@ impl Parser {
     }
 
     fn parse_grouped_expr(&mut self) -> Result<Expression> {
+        if self.at_arrow_params()? {
+            return self.parse_arrow_expr();
+        }
         self.next_token()?;
 
         let expr = self.parse_expression(Precedence::Lowest);
 
-        if self.peek_token != Token::Rparen {
+        if *self.peek_token() == Token::Comma {
+            return self.parse_tuple_expr(expr?);
+        }
+
+        if *self.peek_token() != Token::Rparen {
             bail!("Failed to parse grouped expression!");
         }
 
@@ -332,11 +990,36 @@ impl Parser {
 
         expr
     }
+
+    fn parse_tuple_expr(&mut self, first: Expression) -> Result<Expression> {
+        let mut elements = vec![first];
+
+        while *self.peek_token() == Token::Comma {
+            self.next_token()?;
+            if *self.peek_token() == Token::Rparen {
+                break;
+            }
+
+            self.next_token()?;
+            elements.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if *self.peek_token() != Token::Rparen {
+            bail!("Failed to parse tuple expression!");
+        }
+
+        self.next_token()?;
+
+        Ok(Expression::Tuple(elements))
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::lexer::Lexer;
+    use crate::{
+        ast::{block_source, Expression, Identifier, Imports, Infix, Literal, Statement},
+        lexer::{Lexer, Token},
+    };
 
     use super::Parser;
 
@@ -474,6 +1157,21 @@ mod test {
         assert!(program.iter().all(|x| x.is_ok()));
     }
 
+    #[test]
+    fn membership() {
+        for (input, expected) in [
+            ("x in xs", "(x in xs)"),
+            ("x + 1 !in xs", "((x + 1) !in xs)"),
+            ("x in xs == ys", "(x in (xs == ys))"),
+            ("a in b in c", "((a in b) in c)"),
+            ("!inside", "(!inside)"),
+        ] {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            let statement = program.into_iter().next().unwrap().unwrap();
+            assert_eq!(statement.to_string(), expected, "{}", input);
+        }
+    }
+
     #[test]
     fn simple_ast() {
         let input = "
@@ -504,6 +1202,338 @@ mod test {
         assert!(program.iter().all(|x| x.is_ok()));
     }
 
+    #[test]
+    fn if_parentheses() {
+        let parse = |source: &str| {
+            Parser::new(Lexer::new(source))
+                .parse_program()
+                .unwrap()
+                .into_iter()
+                .map(|statement| statement.map_err(|error| error.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        for (without, with) in [
+            ("if x < y { x } else { y }", "if (x < y) { x } else { y }"),
+            ("if x { 1 }", "if (x) { 1 }"),
+            ("if f(x) { 1 }", "if (f(x)) { 1 }"),
+            ("if (a) == (b) { 1 }", "if ((a) == (b)) { 1 }"),
+            ("if x\n{ 1 }\nelse\n{ 2 }", "if (x) { 1 } else { 2 }"),
+        ] {
+            assert_eq!(parse(without), parse(with), "{}", without);
+        }
+
+        for (input, error) in [
+            ("if { 1 }", "Expected a condition after if!"),
+            (
+                "if (x) 1",
+                "Expected { after the if condition, found Int(1)!",
+            ),
+            (
+                "if x y { 1 }",
+                "Expected { after the if condition, found Ident(\"y\")!",
+            ),
+            ("if (x { 1 }", "Failed to parse grouped expression!"),
+        ] {
+            assert_eq!(parse(input)[0], Err(String::from(error)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn if_expression_followed_by_statement() {
+        let input = "if (x) { let y = 1; } y";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 2);
+        assert!(program.iter().all(|x| x.is_ok()));
+    }
+
+    #[test]
+    fn multi_statement_blocks() {
+        for (input, statements) in [
+            ("fn(x) { let a = 1; a + x }", 2),
+            ("fn(x) { let a = 1;; a + x }", 2),
+            ("fn(x) { ; let a = 1; ; let b = 2; a + b + x }", 3),
+            ("fn(x) { let a = 1; a + x; }", 3),
+            ("fn(x) {\n let a = 1\n let b = a; b\n}", 3),
+            (
+                "fn(x) { let f = fn(y) { y; }; let g = fn() { 1 }; f(g()) }",
+                3,
+            ),
+            ("fn(x) { if (x) { 1; 2 } else { 3 }; x }", 2),
+        ] {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            assert_eq!(program.len(), 1, "{}", input);
+            let Ok(Statement::Expression(Expression::Function { body, .. })) = &program[0] else {
+                panic!("expected a function: {}", input);
+            };
+            assert_eq!(body.len(), statements, "{}", input);
+        }
+    }
+
+    #[test]
+    fn assign_expression() {
+        let input = "x = 5;
+        x = y = 1 + 2;
+        ";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 2);
+        assert!(program.iter().all(|x| x.is_ok()));
+
+        let lexer = Lexer::new("1 + x = 5");
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+        assert!(program[0].is_err());
+    }
+
+    #[test]
+    fn newline_terminated_statements() {
+        let input = "let x = 1
+        let y = x
+        (1, 2)
+        -y
+        let add = fn(a, b) {
+            a +
+                b
+        }
+        if (x) { x }
+        else { y }
+        add(
+            x,
+            y
+        )
+        ";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 7);
+        assert!(program.iter().all(|x| x.is_ok()));
+        assert!(matches!(
+            program[2],
+            Ok(Statement::Expression(Expression::Tuple(_)))
+        ));
+    }
+
+    #[test]
+    fn yield_statement() {
+        let input = "let gen = fn() { yield 1; fn() { 2 } }";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        let Ok(Statement::Let(
+            _,
+            Expression::Function {
+                generator, body, ..
+            },
+        )) = &program[0]
+        else {
+            panic!("expected a function");
+        };
+        assert!(generator);
+        assert!(matches!(
+            body[1],
+            Statement::Expression(Expression::Function {
+                generator: false,
+                ..
+            })
+        ));
+
+        let lexer = Lexer::new("yield 1");
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+        assert!(program[0].is_err());
+    }
+
+    #[test]
+    fn pipe_expression() {
+        let tests = [
+            ("x |> f(y)", "f(x, y)"),
+            ("x |> f", "f(x)"),
+            ("data |> filter(f) |> map(g)", "map(filter(data, f), g)"),
+            ("a + 1 |> f() == b", "f(a + 1) == b"),
+            ("a |> f() < a |> g", "f(a) < g(a)"),
+            ("[1] |> fn(x) { x }", "fn(x) { x }([1])"),
+            ("x\n  |> f\n  |> g(1)", "g(f(x), 1)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            let desugared = Parser::new(Lexer::new(expected)).parse_program().unwrap();
+
+            assert_eq!(program.len(), 1, "{}", input);
+            assert_eq!(
+                program[0].as_ref().unwrap(),
+                desugared[0].as_ref().unwrap(),
+                "{}",
+                input
+            );
+        }
+
+        let program = Parser::new(Lexer::new("x |> 1")).parse_program().unwrap();
+        assert!(program[0].is_err());
+    }
+
+    #[test]
+    fn range_expression() {
+        let program = Parser::new(Lexer::new("1..n + 1; 0..=9 |> f"))
+            .parse_program()
+            .unwrap();
+
+        assert_eq!(
+            program[0].as_ref().unwrap(),
+            &Statement::Expression(Expression::Range {
+                start: Box::new(Expression::Literal(Literal::Int(1))),
+                end: Box::new(Expression::Infix(
+                    Infix::Plus,
+                    Box::new(Expression::Identifier(Identifier("n".into()))),
+                    Box::new(Expression::Literal(Literal::Int(1))),
+                )),
+                inclusive: false,
+            })
+        );
+        assert_eq!(
+            program[1].as_ref().unwrap(),
+            &Statement::Expression(Expression::Call {
+                function: Box::new(Expression::Identifier(Identifier("f".into()))),
+                args: vec![Expression::Range {
+                    start: Box::new(Expression::Literal(Literal::Int(0))),
+                    end: Box::new(Expression::Literal(Literal::Int(9))),
+                    inclusive: true,
+                }],
+                named: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn named_arguments() {
+        let input = r#"greet("Hi", name: "Ana", title: "Dr")"#;
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        let Ok(Statement::Expression(Expression::Call { args, named, .. })) = &program[0] else {
+            panic!("expected a call");
+        };
+        assert_eq!(args.len(), 1);
+        assert_eq!(named.len(), 2);
+        assert_eq!(named[1].0 .0.as_str(), "title");
+
+        for (input, error) in [
+            ("f(x: 1, 2)", "Positional argument after named arguments!"),
+            ("f(1, x: 2, y: 3, x: 4)", "Duplicate named argument x!"),
+            ("f(a: 1, a: 2, b: 3, b: 4)", "Duplicate named argument a!"),
+        ] {
+            assert_eq!(errors(input), [error], "{}", input);
+        }
+    }
+
+    /// The messages of every statement of `input` that failed to parse.
+    fn errors(input: &str) -> Vec<String> {
+        Parser::new(Lexer::new(input))
+            .parse_program()
+            .unwrap()
+            .into_iter()
+            .filter_map(|statement| statement.err().map(|error| error.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_parameters() {
+        for (input, error) in [
+            ("let f = fn(x, x) { x }; f(1)", "Duplicate parameter x!"),
+            ("fn(a, b, a, b) { a }", "Duplicate parameter a!"),
+            ("(x, y, x) => x + y", "Duplicate parameter x!"),
+        ] {
+            assert_eq!(errors(input), [error], "{}", input);
+        }
+
+        let program = Parser::new(Lexer::new("fn(x) { fn(x) { x } }"))
+            .parse_program()
+            .unwrap();
+        assert!(program[0].is_ok());
+    }
+
+    #[test]
+    fn arrow_functions() {
+        let parse = |source: &str| {
+            Parser::new(Lexer::new(source))
+                .parse_program()
+                .unwrap()
+                .into_iter()
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap()
+        };
+
+        for (arrow, function) in [
+            ("x => x * 2", "fn(x) { x * 2 }"),
+            ("(x, y) => x + y", "fn(x, y) { x + y }"),
+            ("() => 1", "fn() { 1 }"),
+            ("(x) => (x, 1)", "fn(x) { (x, 1) }"),
+            ("x => y => x + y", "fn(x) { fn(y) { x + y } }"),
+            ("map(xs, x => x + 1)", "map(xs, fn(x) { x + 1 })"),
+            ("let f = (x,) =>\n  x;", "let f = fn(x) { x };"),
+        ] {
+            assert_eq!(parse(arrow), parse(function), "{}", arrow);
+        }
+
+        assert_eq!(parse("(x, y)"), parse("(x, y)"));
+        assert!(matches!(
+            &parse("(x, y)")[0],
+            Statement::Expression(Expression::Tuple(_))
+        ));
+        for input in ["(x, 1) => x", "(x + y) => x", "1 => 1"] {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            assert!(program[0].is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn spread() {
+        let input = "f(1, ...args); [1, ...rest, 9]";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 2);
+        assert!(program.iter().all(|x| x.is_ok()));
+
+        for input in ["...x", "(1, ...x)", "let ...x = 1", "{1: ...x}"] {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+
+            let program = parser.parse_program().unwrap();
+            assert!(program[0].is_err(), "{}", input);
+        }
+    }
+
     #[test]
     fn call_expression() {
         let input = "add(1, 2 * 3,((alice)), 4 + 5);";
@@ -535,6 +1565,117 @@ mod test {
         assert!(program.iter().all(|x| x.is_ok()));
     }
 
+    #[test]
+    fn array_literal() {
+        let input = "[1, 2 * 2, fn(x) { x }];
+        [];
+        [1, 2][0];
+        ";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 3);
+        assert!(program.iter().all(|x| x.is_ok()));
+    }
+
+    #[test]
+    fn tuple_expression() {
+        let input = "(1, 2);
+        (a, b * 2, (c, d),);
+        (1);
+        ";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 3);
+        assert!(program.iter().all(|x| x.is_ok()));
+        assert!(matches!(
+            program[2],
+            Ok(Statement::Expression(Expression::Literal(Literal::Int(1))))
+        ));
+    }
+
+    #[test]
+    fn destructuring_let() {
+        let input = "let [a, b] = [1, 2];
+        let [first, [second, third]] = arr;
+        let {x, y} = point;
+        let [] = [];
+        let (q, r) = divmod(7, 2);
+        ";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 5);
+        assert!(program.iter().all(|x| x.is_ok()));
+    }
+
+    #[test]
+    fn hash_literal() {
+        let input = r#"{"one": 1, "two": 1 + 1, true: fn(x) { x }};
+        {};
+        {"a": 1}["a"];
+        "#;
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+
+        println!("{:?}", program);
+        assert_eq!(program.len(), 3);
+        assert!(program.iter().all(|x| x.is_ok()));
+    }
+
+    #[test]
+    fn hostile_input_does_not_panic() {
+        let deep = |open: &str, close: &str| open.repeat(10_000) + &close.repeat(10_000);
+        let inputs = [
+            "99999999999999999999".to_string(),
+            "\"unterminated".to_string(),
+            "let".to_string(),
+            "let [".to_string(),
+            "let {a".to_string(),
+            "fn(".to_string(),
+            "fn(x".to_string(),
+            "if".to_string(),
+            "{".to_string(),
+            "{1:".to_string(),
+            "[1,".to_string(),
+            "a[".to_string(),
+            "(1,".to_string(),
+            "x = ".to_string(),
+            "\u{0}".to_string(),
+            "ñ".to_string(),
+            deep("(", ")"),
+            deep("[", "]"),
+            deep("{1: ", "}"),
+            deep("if (x) { ", "}"),
+            deep("fn() { ", "}"),
+            deep("-", "1"),
+            deep("let [", "] = 1"),
+        ];
+
+        for input in inputs {
+            let mut parser = Parser::new(Lexer::new(&input));
+            if let Ok(program) = parser.parse_program() {
+                assert!(program.iter().any(|x| x.is_err()) || program.is_empty());
+            }
+        }
+    }
+
     #[test]
     fn string_literal() {
         let input = r#"
@@ -550,4 +1691,165 @@ mod test {
         assert_eq!(program.len(), 1);
         assert!(program.iter().all(|x| x.is_ok()));
     }
+
+    #[test]
+    fn source_round_trip() {
+        let input = r#"import "utils/strings"
+import "math" as m; from "list" import sum, filter
+let f = fn(x, y) {
+    let [a, (b, c)] = x;
+    if (a < -b * c) {
+        return {"key": y[0]};
+    } else {
+        yield (1,);
+    }
+    g(...a, 1..=2, b"hi", m.sqrt(x).y, named: !true);
+    x = null
+}"#;
+        let parse = |source: &str| {
+            Parser::new(Lexer::new(source))
+                .parse_program()
+                .unwrap()
+                .into_iter()
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap()
+        };
+
+        let program = parse(input);
+        let source = block_source(&program);
+        assert_eq!(
+            source,
+            r#"import "utils/strings";
+import "math" as m;
+from "list" import sum, filter;
+let f = fn(x, y) {
+    let [a, (b, c)] = x;
+    if ((a < ((-b) * c))) {
+        return {"key": (y[0])};
+    } else {
+        yield (1,);
+    };
+    g(...a, (1..=2), b"hi", ((m.sqrt)(x).y), named: (!true));
+    x = null
+}"#
+        );
+        assert_eq!(parse(&source), program);
+    }
+
+    #[test]
+    fn import_statement() {
+        let parse = |source: &str| {
+            Parser::new(Lexer::new(source))
+                .parse_program()
+                .unwrap()
+                .remove(0)
+                .map_err(|error| error.to_string())
+        };
+        let name = |name: &str| Identifier(name.into());
+
+        assert_eq!(
+            parse(r#"import "math""#),
+            Ok(Statement::Import(String::from("math"), Imports::All))
+        );
+        assert_eq!(
+            parse(r#"import "math" as m"#),
+            Ok(Statement::Import(
+                String::from("math"),
+                Imports::Module(name("m"))
+            ))
+        );
+        assert_eq!(
+            parse(r#"from "list" import map, filter"#),
+            Ok(Statement::Import(
+                String::from("list"),
+                Imports::Names(vec![name("map"), name("filter")])
+            ))
+        );
+        assert_eq!(
+            parse("from + 1"),
+            Ok(Statement::Expression(Expression::Infix(
+                Infix::Plus,
+                Box::new(Expression::Identifier(name("from"))),
+                Box::new(Expression::Literal(Literal::Int(1)))
+            )))
+        );
+
+        for (source, error) in [
+            ("import 5", "Expected a module name in quotes after import!"),
+            (
+                r#"import "math" as"#,
+                "Expected a name for the module after as!",
+            ),
+            (
+                r#"from "list" map"#,
+                "Expected import after the module name!",
+            ),
+            (r#"from "list" import"#, "Expected a name to import!"),
+            ("m.5", "Expected a name after .!"),
+        ] {
+            assert_eq!(parse(source), Err(String::from(error)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn peeks_ahead() -> anyhow::Result<()> {
+        let mut parser = Parser::new(Lexer::new("(x, y) + z"));
+        parser.next_token()?;
+
+        assert_eq!(parser.peek_nth(2)?, &Token::Comma);
+        assert_eq!(parser.peek_nth(0)?, &Token::Lparen);
+        assert_eq!(parser.peek_nth(4)?, &Token::Rparen);
+        assert_eq!(parser.peek_token(), &Token::Ident(String::from("x")));
+
+        parser.next_token()?;
+        parser.next_token()?;
+        assert_eq!(parser.current_token, Token::Comma);
+        assert_eq!(parser.peek_nth(3)?, &Token::Plus);
+        assert_eq!(parser.peek_nth(5)?, &Token::Eof);
+        assert_eq!(parser.peek_nth(9)?, &Token::Eof);
+
+        Ok(())
+    }
+
+    #[test]
+    fn single_expression() {
+        let parse = |source: &str| {
+            Parser::parse_expression_str(source)
+                .map(|expression| expression.to_string())
+                .map_err(|error| error.to_string())
+        };
+
+        assert_eq!(parse("x > 3 + 1"), Ok(String::from("(x > (3 + 1))")));
+        assert_eq!(
+            parse("status == \"ok\";\n"),
+            Ok(String::from("(status == \"ok\")"))
+        );
+        assert_eq!(
+            parse("x > 3; y"),
+            Err(String::from(
+                "Expected the end of the expression, found Ident(\"y\")!"
+            ))
+        );
+        assert_eq!(
+            parse("let x = 1"),
+            Err(String::from("Expression type Let is unhandled yet!"))
+        );
+        assert_eq!(
+            parse(""),
+            Err(String::from("Expression type Eof is unhandled yet!"))
+        );
+    }
+
+    #[test]
+    fn top_level_await() {
+        let awaits = |source: &str| {
+            let mut parser = Parser::new(Lexer::new(source));
+            parser.parse_program().unwrap();
+            parser.awaits()
+        };
+
+        assert!(awaits("let x = 1; f(await p)"));
+        assert!(!awaits("let f = fn() { await p }"));
+        assert!(awaits("let f = fn() { await p }; await f()"));
+    }
 }
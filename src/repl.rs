@@ -1,36 +1,657 @@
-use std::io::Write;
+use std::{
+    any::Any,
+    cell::Cell,
+    fs,
+    io::Write,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Once,
+    },
+    thread,
+    time::Instant,
+};
 
 use anyhow::Result;
 
 use crate::{
-    eval::{object::Object, Eval},
+    eval::{
+        builtins::Runtime,
+        capabilities::Capabilities,
+        event_loop::EventLoop,
+        object::Object,
+        pretty::{Pretty, PrettyOptions},
+        Eval, Options,
+    },
     lexer::Lexer,
     parser::Parser,
+    VERSION,
 };
 
-pub fn run() -> Result<()> {
-    print!(">> ");
+/// Cargo features the interpreter can be built with, and whether this build has them.
+const FEATURES: &[(&str, bool)] = &[
+    ("persistent", cfg!(feature = "persistent")),
+    ("remote", cfg!(feature = "remote")),
+];
+
+pub struct ReplOptions {
+    /// Printed before every input line.
+    pub prompt: String,
+    /// Monkey file evaluated into the global environment before the first
+    /// prompt, `~/.monkeyrc` by default.
+    pub rc_file: Option<PathBuf>,
+}
+
+impl Default for ReplOptions {
+    fn default() -> Self {
+        Self {
+            prompt: String::from(">> "),
+            rc_file: std::env::var_os("HOME").map(|home| Path::new(&home).join(".monkeyrc")),
+        }
+    }
+}
+
+/// State kept between input lines, changed by `:set` and read by `:show`.
+struct Session {
+    eval: Eval,
+    pretty: PrettyOptions,
+    /// Echoed results longer than this many chars are cut short.
+    maxlen: usize,
+    /// The previous result, which `:show _` prints in full.
+    last: Object,
+    /// Lines suspended at a top-level `await`, running in the background.
+    tasks: EventLoop,
+}
+
+impl Session {
+    fn new(eval: Eval) -> Self {
+        Self {
+            eval,
+            pretty: PrettyOptions::default(),
+            maxlen: 2000,
+            last: Object::Null,
+            tasks: EventLoop::default(),
+        }
+    }
+}
+
+pub fn run(options: Options, repl_options: ReplOptions) -> Result<()> {
+    run_with(Eval::with_options(options), repl_options)
+}
+
+/// Like `run`, carrying on in the environment of `eval`, e.g. after running a script in it.
+pub fn run_with(eval: Eval, repl_options: ReplOptions) -> Result<()> {
+    println!("{}", version(eval.capabilities()));
+    println!("Type in commands, :version prints the above again.");
+
+    let mut session = Session::new(eval);
+    let eval = &mut session.eval;
+
+    if let Some(rc_file) = &repl_options.rc_file {
+        if let Err(error) = load_rc(eval, rc_file) {
+            println!("ERROR in {}: {}", rc_file.display(), error);
+        }
+    }
+
+    print!("{}", repl_options.prompt);
     std::io::stdout().flush()?;
 
-    let mut eval = Eval::new();
-    std::io::stdin().lines().for_each(|line| {
-        if let Ok(line) = line {
-            let lexer = Lexer::new(line.as_str());
-            let mut parser = Parser::new(lexer);
+    // Ctrl-C aborts the running evaluation, or leaves the REPL when idle at the prompt.
+    let interrupt = eval.interrupt_handle();
+    let evaluating = Arc::new(AtomicBool::new(false));
+    ctrlc::set_handler({
+        let interrupt = interrupt.clone();
+        let evaluating = evaluating.clone();
+        move || {
+            if evaluating.load(Ordering::SeqCst) {
+                interrupt.interrupt();
+            } else {
+                println!();
+                std::process::exit(0);
+            }
+        }
+    })?;
+
+    // Locks stdin only while reading each line, so `input` can read from it during evaluation.
+    let mut buffer = String::new();
+    while matches!(std::io::stdin().read_line(&mut buffer), Ok(read) if read > 0) {
+        let line = std::mem::take(&mut buffer);
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        interrupt.reset();
+        evaluating.store(true, Ordering::SeqCst);
+        let output = eval_line(&mut session, line);
+        evaluating.store(false, Ordering::SeqCst);
+
+        let eval = &mut session.eval;
+        for warning in eval.take_warnings() {
+            println!("WARNING: {}", warning);
+        }
+        if let Some(output) = output {
+            println!("{}", output);
+        }
+        if let Some(profiler) = eval.profiler().filter(|profiler| !profiler.is_empty()) {
+            print!("{}", profiler);
+            profiler.clear();
+        }
+        for output in finished_tasks(&mut session) {
+            println!("{}", output);
+        }
+        print!("{}", repl_options.prompt);
+        _ = std::io::stdout().flush();
+    }
+
+    Ok(())
+}
+
+/// Evaluates the startup file into `eval`'s global environment. A missing file is not an error.
+fn load_rc(eval: &mut Eval, path: &Path) -> Result<()> {
+    let Ok(source) = fs::read_to_string(path) else {
+        return Ok(());
+    };
 
-            let result = match parser.parse_program() {
-                Ok(program) => eval.eval(program),
-                Err(error) => Err(error),
+    let program = Parser::new(Lexer::new(&source)).parse_program()?;
+    eval.eval(program)?;
+
+    Ok(())
+}
+
+/// Runs a `:`-prefixed REPL command instead of Monkey code.
+fn run_command(session: &mut Session, command: &str) -> Option<String> {
+    let words = command.split_whitespace().collect::<Vec<_>>();
+
+    match words.as_slice() {
+        ["scopes"] => {
+            let scopes = session.eval.scopes();
+            let mut output = vec![];
+            for (depth, bindings) in scopes.iter().rev().enumerate() {
+                output.push(match depth {
+                    0 => String::from("global:"),
+                    _ => format!("scope {}:", depth),
+                });
+                output.extend(
+                    bindings
+                        .iter()
+                        .map(|(id, value)| format!("  {}: {} = {}", id, value.get_type(), value)),
+                );
+            }
+            Some(output.join("\n"))
+        }
+        ["set"] => Some(format!(
+            "maxlen {}\ndepth {}\nwidth {}",
+            session.maxlen, session.pretty.depth, session.pretty.width
+        )),
+        ["set", setting, value] => {
+            let Ok(value) = value.parse() else {
+                return Some(format!("ERROR: Invalid value {} for {}", value, setting));
             };
-            match result {
-                Ok(Object::Empty) => {}
-                Ok(result) => println!("{}", result),
-                Err(result) => println!("ERROR: {}", result),
+            match *setting {
+                "maxlen" => session.maxlen = value,
+                "depth" => session.pretty.depth = value,
+                "width" => session.pretty.width = value,
+                _ => return Some(format!("ERROR: Unknown setting {}", setting)),
             }
-            print!(">> ");
-            _ = std::io::stdout().flush();
+            None
+        }
+        ["doc", name] => Some(match session.eval.lookup(name) {
+            Ok(value) if value.is_callable() => match value.doc() {
+                Some(doc) => doc,
+                None => format!("{} has no documentation", name),
+            },
+            Ok(value) => format!("ERROR: {} is {}, not a function", name, value.get_type()),
+            Err(error) => format!("ERROR: {}", error),
+        }),
+        ["version"] => Some(version(session.eval.capabilities())),
+        ["stats"] => Some(session.eval.heap_stats().to_string()),
+        ["time", ..] => {
+            let code = command["time".len()..].trim();
+            let start = Instant::now();
+            let output = eval_line(session, code);
+            let time = format!("time: {:.3?}", start.elapsed());
+            Some(match output {
+                Some(output) => format!("{}\n{}", output, time),
+                None => time,
+            })
+        }
+        ["show", "_"] => {
+            let options = PrettyOptions {
+                depth: usize::MAX,
+                ..session.pretty.clone()
+            };
+            Some(Pretty::new(&session.last, &options).to_string())
         }
+        _ => Some(format!("ERROR: Unknown command :{}", command)),
+    }
+}
+
+/// The interpreter version, the features it was built with and what scripts are allowed to
+/// do, to paste into bug reports.
+fn version(capabilities: &Capabilities) -> String {
+    let features = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| *feature)
+        .collect::<Vec<_>>();
+
+    let mut allowed = vec![];
+    if !capabilities.fs.is_empty() {
+        let dirs = capabilities
+            .fs
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>();
+        allowed.push(format!("fs ({})", dirs.join(", ")));
+    }
+    for (capability, enabled) in [
+        ("net", capabilities.net),
+        ("exec", capabilities.exec),
+        ("env", capabilities.env),
+        ("time", capabilities.time),
+    ] {
+        if enabled {
+            allowed.push(String::from(capability));
+        }
+    }
+
+    let list = |items: Vec<String>| match items.is_empty() {
+        true => String::from("none"),
+        false => items.join(", "),
+    };
+    format!(
+        "Monkey interpreter {}\nfeatures: {}\ncapabilities: {}",
+        VERSION,
+        list(features.into_iter().map(String::from).collect()),
+        list(allowed)
+    )
+}
+
+/// `output` cut down to `maxlen` chars, noting how much is missing.
+fn truncate(output: String, maxlen: usize) -> String {
+    let length = output.chars().count();
+    if length <= maxlen {
+        return output;
+    }
+
+    let kept = output.chars().take(maxlen).collect::<String>();
+    format!(
+        "{}... ({} more chars, :show _ prints everything)",
+        kept,
+        length - maxlen
+    )
+}
+
+/// The error message followed by the functions it happened in, innermost first.
+pub(crate) fn describe_error(eval: &Eval, error: &anyhow::Error) -> String {
+    let mut output = format!("ERROR: {}", error);
+    for calls in eval.stack_trace().chunk_by(|a, b| a == b) {
+        output += &match calls.len() {
+            1 => format!("\n  in {}", calls[0]),
+            n => format!("\n  in {} ({} times)", calls[0], n),
+        };
+    }
+    output
+}
+
+fn eval_line(session: &mut Session, line: &str) -> Option<String> {
+    if let Some(command) = line.trim().strip_prefix(':') {
+        return run_command(session, command.trim());
+    }
+
+    let eval = &mut session.eval;
+    let tasks = &mut session.tasks;
+    let result = catch_silently(|| {
+        let lexer = Lexer::new(line);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program()?;
+
+        // Lines awaiting at the top level run as tasks, so the prompt comes back while they wait.
+        if !parser.awaits() {
+            return eval.eval(program).map(Some);
+        }
+        tasks.spawn(eval, program)?.transpose()
     });
 
-    Ok(())
+    match result {
+        Ok(Ok(None)) => Some(String::from("(awaiting in the background)")),
+        Ok(Ok(Some(Object::Empty))) => None,
+        Ok(Ok(Some(result))) => {
+            let output = Pretty::new(&result, &session.pretty).to_string();
+            session.last = result;
+            Some(truncate(output, session.maxlen))
+        }
+        Ok(Err(error)) => Some(describe_error(eval, &error)),
+        Err(panic) => {
+            eval.reset_scope();
+            Some(format!(
+                "INTERNAL ERROR: {} (while evaluating `{}`)",
+                panic_message(&*panic),
+                line
+            ))
+        }
+    }
+}
+
+/// Resumes the lines whose awaited promise has settled, describing the results of those that
+/// finish.
+fn finished_tasks(session: &mut Session) -> Vec<String> {
+    let results = match catch_silently(|| session.tasks.run_ready()) {
+        Ok(results) => results,
+        Err(panic) => {
+            session.tasks = EventLoop::default();
+            let message = panic_message(&*panic);
+            return vec![format!("INTERNAL ERROR: {} (in the background)", message)];
+        }
+    };
+
+    results
+        .into_iter()
+        .map(|result| match result {
+            Ok(Object::Empty) => String::from("[background] done"),
+            Ok(result) => {
+                let output = Pretty::new(&result, &session.pretty).to_string();
+                session.last = result;
+                format!("[background] {}", truncate(output, session.maxlen))
+            }
+            Err(error) => format!("[background] ERROR: {}", error),
+        })
+        .collect()
+}
+
+thread_local! {
+    /// Whether this thread is in `catch_silently`, whose caller reports its panics.
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Catches a panic of `f`, which the caller reports, without the panic hook printing it as
+/// well. The hook is process-wide, so it is wrapped once rather than swapped on every call:
+/// panics elsewhere, e.g. on other threads, still reach the original hook.
+pub fn catch_silently<T>(f: impl FnOnce() -> T) -> thread::Result<T> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(Cell::get) {
+                previous(info);
+            }
+        }));
+    });
+
+    let catching = CATCHING.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.set(catching);
+    result
+}
+
+pub fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        eval::{capabilities::Capabilities, object::Object, observer::EvalObserver, Eval},
+        VERSION,
+    };
+
+    use super::{catch_silently, eval_line, finished_tasks, load_rc, Session, CATCHING};
+
+    struct PanicOnCall;
+
+    impl EvalObserver for PanicOnCall {
+        fn on_call(&mut self, _: &Object, _: &[Object]) {
+            panic!("observer exploded");
+        }
+    }
+
+    #[test]
+    fn survives_panics() {
+        let mut session = Session::new(Eval::new());
+        session.eval.add_observer(Box::new(PanicOnCall));
+
+        assert_eq!(eval_line(&mut session, "let x = 5;"), None);
+        assert_eq!(
+            eval_line(&mut session, "let f = fn(y) { let z = 1; y }; f(1)"),
+            Some(String::from(
+                "INTERNAL ERROR: observer exploded (while evaluating `let f = fn(y) { let z = 1; y }; f(1)`)"
+            ))
+        );
+        assert_eq!(eval_line(&mut session, "x"), Some(String::from("5")));
+        assert_eq!(
+            eval_line(&mut session, "z"),
+            Some(String::from("ERROR: Identifier z not found!"))
+        );
+    }
+
+    #[test]
+    fn catches_only_while_catching() {
+        let nested = catch_silently(|| {
+            assert!(catch_silently(|| panic!("inner")).is_err());
+            assert!(CATCHING.get());
+            panic!("outer")
+        });
+
+        assert!(nested.is_err());
+        assert!(!CATCHING.get());
+        assert_eq!(catch_silently(|| 1).unwrap(), 1);
+        assert!(!CATCHING.get());
+    }
+
+    #[test]
+    fn scopes_command() {
+        let mut session = Session::new(Eval::new());
+
+        assert_eq!(
+            eval_line(&mut session, ":scopes"),
+            Some(String::from("global:"))
+        );
+        eval_line(&mut session, "let b = [1, 2]; let a = fn(x) { x };");
+        assert_eq!(
+            eval_line(&mut session, ":scopes"),
+            Some(String::from(
+                "global:\n  a: function = fn(x)\n  b: array = [1, 2]"
+            ))
+        );
+        assert_eq!(
+            eval_line(&mut session, "let f = fn() { a(true) + 1 }; f()"),
+            Some(String::from(
+                "ERROR: Infix operator + not found for the operands: bool & int!\n  in f"
+            ))
+        );
+        assert_eq!(
+            eval_line(
+                &mut session,
+                "let g = fn(n) { if (n == 0) { f() } else { g(n - 1) } }; g(2)"
+            ),
+            Some(String::from(
+                "ERROR: Infix operator + not found for the operands: bool & int!\n  in f\n  in g (3 times)"
+            ))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":nope"),
+            Some(String::from("ERROR: Unknown command :nope"))
+        );
+    }
+
+    #[test]
+    fn redefinitions_are_late_bound() {
+        let mut session = Session::new(Eval::new());
+
+        eval_line(&mut session, "let f = fn() { 1 };");
+        eval_line(&mut session, "let g = fn() { f() };");
+        eval_line(&mut session, "let make = fn() { fn() { f() + 10 } };");
+        eval_line(&mut session, "let h = make(); let old = f;");
+        assert_eq!(
+            eval_line(&mut session, "[g(), h()]"),
+            Some(String::from("[1, 11]"))
+        );
+
+        eval_line(&mut session, "let f = fn() { 2 };");
+        assert_eq!(
+            eval_line(&mut session, "[g(), h(), old()]"),
+            Some(String::from("[2, 12, 1]"))
+        );
+    }
+
+    #[test]
+    fn version_command() {
+        let mut session = Session::new(Eval::with_capabilities(Capabilities {
+            exec: true,
+            ..Capabilities::none()
+        }));
+
+        let output = eval_line(&mut session, ":version").unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], format!("Monkey interpreter {}", VERSION));
+        assert_eq!(
+            lines[1] == "features: none",
+            !cfg!(any(feature = "persistent", feature = "remote"))
+        );
+        assert_eq!(lines[2], "capabilities: exec, time");
+    }
+
+    #[test]
+    fn time_command() {
+        let mut session = Session::new(Eval::new());
+
+        let output = eval_line(&mut session, ":time 1 + 2").unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "3");
+        assert!(lines[1].starts_with("time: "), "{}", output);
+        assert!(eval_line(&mut session, ":time let x = 5")
+            .unwrap()
+            .starts_with("time: "));
+        assert_eq!(eval_line(&mut session, "x"), Some(String::from("5")));
+    }
+
+    #[test]
+    fn doc_command() {
+        let mut session = Session::new(Eval::new());
+
+        eval_line(
+            &mut session,
+            r#"let square = fn(x) { "Multiplies x by itself."; x * x }; let id = fn(x) { x }; let n = 1;"#,
+        );
+        assert_eq!(
+            eval_line(&mut session, ":doc square"),
+            Some(String::from("Multiplies x by itself."))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":doc id"),
+            Some(String::from("id has no documentation"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":doc push"),
+            Some(String::from("push(array: array, value)"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":doc format"),
+            Some(String::from("format(template: string, ...args)"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":doc n"),
+            Some(String::from("ERROR: n is int, not a function"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":doc squar"),
+            Some(String::from(
+                "ERROR: Identifier squar not found! Did you mean `square`?"
+            ))
+        );
+    }
+
+    #[test]
+    fn top_level_await() {
+        let mut session = Session::new(Eval::new());
+
+        assert_eq!(
+            eval_line(&mut session, r#"let a = await sleep_async(200); "a""#),
+            Some(String::from("(awaiting in the background)"))
+        );
+        assert_eq!(
+            eval_line(&mut session, "let b = await sleep_async(0); 2"),
+            Some(String::from("2"))
+        );
+        assert_eq!(finished_tasks(&mut session), Vec::<String>::new());
+
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        assert_eq!(finished_tasks(&mut session), ["[background] a"]);
+        assert_eq!(
+            eval_line(&mut session, "[a, b]"),
+            Some(String::from("[NULL, NULL]"))
+        );
+        assert!(session.tasks.is_empty());
+
+        eval_line(&mut session, "await sleep_async(1); x");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(
+            finished_tasks(&mut session),
+            ["[background] ERROR: Identifier x not found!"]
+        );
+    }
+
+    #[test]
+    fn display_settings() {
+        let mut session = Session::new(Eval::new());
+
+        eval_line(&mut session, "let xs = [[1, [2, [3]]], 12345678]");
+        assert_eq!(eval_line(&mut session, ":set depth 2"), None);
+        assert_eq!(
+            eval_line(&mut session, "xs"),
+            Some(String::from("[[1, [...]], 12345678]"))
+        );
+        assert_eq!(eval_line(&mut session, ":set maxlen 10"), None);
+        assert_eq!(
+            eval_line(&mut session, "xs"),
+            Some(String::from(
+                "[[1, [...]... (12 more chars, :show _ prints everything)"
+            ))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":show _"),
+            Some(String::from("[[1, [2, [3]]], 12345678]"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":set"),
+            Some(String::from("maxlen 10\ndepth 2\nwidth 80"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":set depth x"),
+            Some(String::from("ERROR: Invalid value x for depth"))
+        );
+        assert_eq!(
+            eval_line(&mut session, ":set colour 1"),
+            Some(String::from("ERROR: Unknown setting colour"))
+        );
+    }
+
+    #[test]
+    fn rc_file() {
+        let path = std::env::temp_dir().join(format!("monkeyrc-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "let double = fn(x) { x * 2 };\nlet answer = double(21);\n",
+        )
+        .unwrap();
+
+        let mut session = Session::new(Eval::new());
+        load_rc(&mut session.eval, &path).unwrap();
+        assert_eq!(
+            eval_line(&mut session, "double(answer)"),
+            Some(String::from("84"))
+        );
+
+        std::fs::write(&path, "let broken = 1 + true;").unwrap();
+        assert_eq!(
+            load_rc(&mut Eval::new(), &path).unwrap_err().to_string(),
+            "Infix operator + not found for the operands: int & bool!"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_rc(&mut Eval::new(), &path).is_ok());
+    }
 }
@@ -0,0 +1,317 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    eval::{Eval, Options},
+    lexer::Lexer,
+    parser::{Parser, SyntaxError},
+    repl::describe_error,
+};
+
+/// Runs a Monkey file, printing errors to stderr. Returns whether it ran without errors.
+pub fn run(options: Options, path: &Path) -> Result<bool> {
+    run_in(&mut Eval::with_options(options), path)
+}
+
+/// Like `run`, in the environment of `eval`, which keeps the script's variables afterwards.
+pub fn run_in(eval: &mut Eval, path: &Path) -> Result<bool> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    Ok(report(run_source(eval, &source)))
+}
+
+/// Like `run`, for a program that isn't in a file.
+pub fn run_code(options: Options, source: &str) -> bool {
+    report(run_source(&mut Eval::with_options(options), source))
+}
+
+/// Prints the errors of a run, if any, to stderr. Returns whether there were none.
+fn report(result: Result<(), Vec<String>>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            false
+        }
+    }
+}
+
+/// How often `watch` checks whether files changed.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs a Monkey file, then again every time it or a module it imports changes, until the
+/// process is killed. With `keep_env`, each run starts with the variables the previous one left
+/// behind instead of a fresh environment.
+pub fn watch(options: Options, path: &Path, keep_env: bool) -> Result<()> {
+    let mut eval = Eval::with_options(options.clone());
+    let mut watched = vec![];
+
+    loop {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                if let Err(errors) = run_source(&mut eval, &source) {
+                    for error in errors {
+                        eprintln!("{}", error);
+                    }
+                }
+            }
+            Err(error) => eprintln!("ERROR: Cannot read {}: {}", path.display(), error),
+        }
+
+        // Modules that failed to load are not in `module_files`, so keep watching the files
+        // of earlier runs too, and a fix to a module that broke is noticed.
+        watched = watched_files(&eval, path, watched);
+        eprintln!(
+            "[watch] waiting for changes to {} and {} imported files",
+            path.display(),
+            watched.len() - 1
+        );
+        let stamps = modified(&watched);
+        while modified(&watched) == stamps {
+            thread::sleep(POLL_INTERVAL);
+        }
+        eprintln!("[watch] change detected, running {} again", path.display());
+
+        if keep_env {
+            eval.forget_modules();
+        } else {
+            eval = Eval::with_options(options.clone());
+        }
+    }
+}
+
+/// `path`, the files `eval` imported and `previous`, without duplicates.
+fn watched_files(eval: &Eval, path: &Path, previous: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    for file in previous.into_iter().chain(eval.module_files()) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+/// When each of `paths` was last changed, `None` for those that can't be read.
+fn modified(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+/// Parses the whole program before running any of it, so a syntax error anywhere means
+/// nothing runs. Errors come formatted for people, syntax errors with a source excerpt.
+fn run_source(eval: &mut Eval, source: &str) -> Result<(), Vec<String>> {
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser
+        .parse_program()
+        .map_err(|error| vec![describe_syntax_error(&error, source)])?;
+
+    let errors = program
+        .iter()
+        .filter_map(|statement| statement.as_ref().err())
+        .map(|error| describe_syntax_error(error, source))
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let result = eval
+        .eval(program)
+        .map(|_| ())
+        .map_err(|error| vec![describe_error(eval, &error)]);
+
+    let timings = eval.take_timings();
+    if !timings.is_empty() {
+        eprint!(
+            "{}",
+            timing_report(source, parser.statement_spans(), &timings)
+        );
+    }
+
+    result
+}
+
+/// A table of how long each top-level statement took, labelled with its line and source.
+fn timing_report(source: &str, spans: &[Range<usize>], timings: &[Duration]) -> String {
+    const WIDTH: usize = 50;
+
+    let mut report = format!("{:<WIDTH$} {:>14}\n", "statement", "time");
+    for (span, time) in spans.iter().zip(timings) {
+        let line = source[..span.start].matches('\n').count() + 1;
+        let text = source[span.clone()]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut label = format!("{}: {}", line, text);
+        if label.chars().count() > WIDTH {
+            label = label.chars().take(WIDTH - 3).collect::<String>() + "...";
+        }
+        report += &format!("{:<WIDTH$} {:>14}\n", label, format!("{:.3?}", time));
+    }
+    report
+}
+
+fn describe_syntax_error(error: &anyhow::Error, source: &str) -> String {
+    match error.downcast_ref::<SyntaxError>() {
+        Some(error) => format!("ERROR: {}\n{}", error, error.excerpt(source)),
+        None => format!("ERROR: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::PathBuf, time::Duration};
+
+    use crate::{
+        eval::{Eval, Options},
+        lexer::Lexer,
+        parser::Parser,
+    };
+
+    use super::{modified, run_in, run_source, timing_report, watched_files};
+
+    fn errors(source: &str) -> Vec<String> {
+        run_source(&mut Eval::new(), source).unwrap_err()
+    }
+
+    #[test]
+    fn syntax_errors_show_source() {
+        assert_eq!(
+            errors("let a = 1;\nlet x = (1 + 2;\nputs(x);\n"),
+            ["ERROR: Failed to parse grouped expression!\n\
+              2 | let x = (1 + 2;\n  \
+                |              ^^\n  \
+                = hint: did you forget a closing `)`?"]
+        );
+        assert_eq!(
+            errors("let f = fn(x) {\n  x + 1\n"),
+            ["ERROR: Expression type Eof is unhandled yet!\n\
+              2 |   x + 1\n  \
+                |        ^\n  \
+                = hint: did you forget a closing `}`?"]
+        );
+        assert_eq!(
+            errors("let = 1;\nputs(1)\nlet = 2;"),
+            [
+                "ERROR: Missing assign token after identifier in let statement\n1 | let = 1;\n  |       ^^",
+                "ERROR: Missing assign token after identifier in let statement\n3 | let = 2;\n  |       ^^",
+            ]
+        );
+        assert_eq!(
+            errors("let x = 1;\nlet y = \"ñ\" @ 2;"),
+            [
+                "ERROR: No program should contain this token: @ at line 2, column 13\n\
+              2 | let y = \"ñ\" @ 2;\n  \
+                |             ^"
+            ]
+        );
+    }
+
+    #[test]
+    fn runtime_errors_show_stack_trace() {
+        let source = "let f = fn(x) { x + true };\nlet g = fn() { f(1) };\ng();";
+
+        assert_eq!(
+            errors(source),
+            ["ERROR: Infix operator + not found for the operands: int & bool!\n  in f\n  in g"]
+        );
+        assert!(run_source(&mut Eval::new(), "let x = 1;\nx + 1\n").is_ok());
+    }
+
+    #[test]
+    fn runs_in_environment() {
+        let script =
+            std::env::temp_dir().join(format!("monkey-run-in-{}.monkey", std::process::id()));
+        std::fs::write(
+            &script,
+            "let x = 41;\nlet f = fn() { x + 1 };\nx + true;\nlet y = 1;",
+        )
+        .unwrap();
+
+        let mut eval = Eval::new();
+        assert!(!run_in(&mut eval, &script).unwrap());
+        assert_eq!(eval.lookup("x").unwrap().to_string(), "41");
+        assert!(eval.lookup("y").is_err());
+        assert!(run_source(&mut eval, "f() == 42").is_ok());
+
+        std::fs::remove_file(&script).unwrap();
+    }
+
+    #[test]
+    fn watches_imported_files() {
+        let dir = std::env::temp_dir().join(format!("monkey-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("main.monkey");
+        let module = dir.join("helpers.monkey");
+        std::fs::write(&module, "let one = 1;").unwrap();
+
+        let mut eval = Eval::with_options(Options {
+            module_paths: vec![dir.clone()],
+            ..Default::default()
+        });
+        run_source(&mut eval, r#"import "helpers"; import "math"; one"#).unwrap();
+        let gone = PathBuf::from("gone.monkey");
+        assert_eq!(
+            watched_files(&eval, &script, vec![module.clone(), gone.clone()]),
+            [script.clone(), module.clone(), gone.clone()]
+        );
+
+        let stamps = modified(&[module.clone(), gone]);
+        assert!(stamps[0].is_some() && stamps[1].is_none());
+
+        eval.forget_modules();
+        assert!(eval.module_files().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn times_statements() {
+        let mut eval = Eval::with_options(Options {
+            time: true,
+            ..Default::default()
+        });
+        run_source(&mut eval, "let x = 1;\nx + 1").unwrap();
+        assert!(eval.take_timings().is_empty());
+
+        let source = "let f = fn(n) {\n  n * 2\n};\n\nputs(f(1)); let long_name_for_a_list = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]\n";
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program().unwrap();
+        eval.eval(program).unwrap();
+        let timings = eval.take_timings();
+        assert_eq!(timings.len(), 3);
+
+        let report = timing_report(
+            source,
+            parser.statement_spans(),
+            &[
+                Duration::from_micros(1500),
+                Duration::ZERO,
+                Duration::from_secs(2),
+            ],
+        );
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            [
+                "statement                                                    time",
+                "1: let f = fn(n) { n * 2 };                               1.500ms",
+                "5: puts(f(1));                                            0.000ns",
+                "5: let long_name_for_a_list = [1, 2, 3, 4, 5, 6...         2.000s",
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Snippets packed into a single line of text, for pasting programs into chats and issues.
+//!
+//! A snippet is `monkey:` followed by the base64 of a header line naming the interpreter
+//! version that made it and then the source. Line breaks and spaces are ignored when
+//! unpacking, so a snippet survives being wrapped by whatever it was pasted into.
+
+use anyhow::{bail, Context, Result};
+
+use crate::VERSION;
+
+const PREFIX: &str = "monkey:";
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, PartialEq)]
+pub struct Snippet {
+    /// Version of the interpreter that made the snippet.
+    pub version: String,
+    pub source: String,
+}
+
+pub fn pack(source: &str) -> String {
+    let payload = format!("interpreter {}\n{}", VERSION, source);
+    format!("{}{}", PREFIX, encode(payload.as_bytes()))
+}
+
+pub fn unpack(snippet: &str) -> Result<Snippet> {
+    let snippet = snippet
+        .split_whitespace()
+        .collect::<String>()
+        .strip_prefix(PREFIX)
+        .map(String::from)
+        .with_context(|| format!("Snippets start with {}", PREFIX))?;
+
+    let payload = String::from_utf8(decode(&snippet)?).context("Snippet is not UTF-8")?;
+    let Some((header, source)) = payload.split_once('\n') else {
+        bail!("Snippet has no header");
+    };
+    let Some(version) = header.strip_prefix("interpreter ") else {
+        bail!("Invalid snippet header: {}", header);
+    };
+
+    Ok(Snippet {
+        version: version.to_string(),
+        source: source.to_string(),
+    })
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            output.push(if i <= chunk.len() {
+                ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char
+            } else {
+                '='
+            });
+        }
+    }
+    output
+}
+
+fn decode(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut output = vec![];
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            bail!("Snippet is truncated");
+        }
+        let mut group = 0u32;
+        for (i, &ch) in chunk.iter().enumerate() {
+            let Some(value) = ALPHABET.iter().position(|&letter| letter == ch) else {
+                bail!("Invalid character {:?} in snippet", ch as char);
+            };
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        output.extend_from_slice(&group.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::VERSION;
+
+    use super::{decode, encode, pack, unpack, Snippet};
+
+    #[test]
+    fn base64() {
+        for (bytes, text) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("ñandú", "w7FhbmTDug=="),
+        ] {
+            assert_eq!(encode(bytes.as_bytes()), text);
+            assert_eq!(decode(text).unwrap(), bytes.as_bytes());
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = "let x = [1, 2];\nputs(x)\n";
+        let snippet = pack(source);
+        assert!(snippet.starts_with("monkey:"));
+
+        let wrapped = snippet
+            .as_bytes()
+            .chunks(10)
+            .map(|line| String::from_utf8_lossy(line).to_string())
+            .collect::<Vec<_>>()
+            .join("\n  ");
+        assert_eq!(
+            unpack(&wrapped).unwrap(),
+            Snippet {
+                version: String::from(VERSION),
+                source: String::from(source),
+            }
+        );
+
+        for (snippet, error) in [
+            ("Zm9v", "Snippets start with monkey:"),
+            ("monkey:Zm9v", "Snippet has no header"),
+            ("monkey:Zm9*", "Invalid character '*' in snippet"),
+            ("monkey:Zm9vY", "Snippet is truncated"),
+        ] {
+            assert_eq!(unpack(snippet).unwrap_err().to_string(), error);
+        }
+    }
+}
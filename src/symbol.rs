@@ -0,0 +1,81 @@
+//! Interned names. The parser interns every identifier once, so the AST and scopes pass names
+//! around as `Symbol`s, which are copied, compared and hashed as integers.
+//!
+//! Interned names are never freed. There is one table for the whole process, so symbols can be
+//! sent between threads and a name always gets the same symbol.
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    names: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>,
+}
+
+fn interner() -> MutexGuard<'static, Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Mutex::default).lock().unwrap()
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let mut interner = interner();
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+
+        let symbol = Symbol(interner.names.len() as u32);
+        let name: &'static str = Box::leak(name.into());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::Symbol;
+
+    #[test]
+    fn interns_names() {
+        let x = Symbol::intern("x");
+        assert_eq!(Symbol::intern("x"), x);
+        assert_ne!(Symbol::intern("y"), x);
+        assert_eq!(x.as_str(), "x");
+        assert_eq!(format!("{} {:?}", x, x), r#"x "x""#);
+
+        let elsewhere = thread::spawn(|| Symbol::intern("x")).join().unwrap();
+        assert_eq!(elsewhere, x);
+    }
+}
@@ -0,0 +1,385 @@
+//! Traversals of the AST. `Visit` walks a tree by reference and `Fold` rebuilds it by value;
+//! both go through every node by default, so a pass only overrides the methods for the nodes
+//! it is interested in. An override can carry on into the children by calling the free
+//! function of the same name, such as `visit_expression`, or leave them out by not calling it.
+
+use crate::ast::{
+    BlockStatement, Expression, Identifier, IfExpression, Imports, Literal, Pattern, Statement,
+};
+
+pub trait Visit {
+    fn visit_block(&mut self, block: &BlockStatement) {
+        visit_block(self, block)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        visit_statement(self, statement)
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        visit_expression(self, expression)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        visit_pattern(self, pattern)
+    }
+
+    /// Every name in the tree, whether it refers to a binding, introduces one, such as a
+    /// parameter, or names an argument or module attribute.
+    fn visit_identifier(&mut self, _identifier: &Identifier) {}
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+}
+
+pub fn visit_block<V: Visit + ?Sized>(visitor: &mut V, block: &BlockStatement) {
+    for statement in block {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn visit_statement<V: Visit + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Let(pattern, value) => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expression(value);
+        }
+        Statement::Return(value) | Statement::Yield(value) | Statement::Expression(value) => {
+            visitor.visit_expression(value)
+        }
+        Statement::Import(_, Imports::All) | Statement::Empty => {}
+        Statement::Import(_, Imports::Module(name)) => visitor.visit_identifier(name),
+        Statement::Import(_, Imports::Names(names)) => {
+            for name in names {
+                visitor.visit_identifier(name);
+            }
+        }
+    }
+}
+
+pub fn visit_expression<V: Visit + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Identifier(name) => visitor.visit_identifier(name),
+        Expression::Literal(literal) => visitor.visit_literal(literal),
+        Expression::Prefix(_, right) => visitor.visit_expression(right),
+        Expression::Infix(_, left, right) => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::If(IfExpression {
+            condition,
+            consequence,
+            alternative,
+        }) => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(consequence);
+            visitor.visit_block(alternative);
+        }
+        Expression::Function { params, body, .. } => {
+            for param in params {
+                visitor.visit_identifier(param);
+            }
+            visitor.visit_block(body);
+        }
+        Expression::Call {
+            function,
+            args,
+            named,
+        } => {
+            visitor.visit_expression(function);
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+            for (name, value) in named {
+                visitor.visit_identifier(name);
+                visitor.visit_expression(value);
+            }
+        }
+        Expression::Array(elements) | Expression::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_expression(element);
+            }
+        }
+        Expression::Hash(pairs) => {
+            for (key, value) in pairs {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        }
+        Expression::Index { left, index } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(index);
+        }
+        Expression::Assign { target, value } => {
+            visitor.visit_identifier(target);
+            visitor.visit_expression(value);
+        }
+        Expression::Range { start, end, .. } => {
+            visitor.visit_expression(start);
+            visitor.visit_expression(end);
+        }
+        Expression::Spread(inner) | Expression::Await(inner) => visitor.visit_expression(inner),
+        Expression::Member { object, attribute } => {
+            visitor.visit_expression(object);
+            visitor.visit_identifier(attribute);
+        }
+    }
+}
+
+pub fn visit_pattern<V: Visit + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Identifier(name) => visitor.visit_identifier(name),
+        Pattern::Array(patterns) | Pattern::Tuple(patterns) => {
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        Pattern::Hash(names) => {
+            for name in names {
+                visitor.visit_identifier(name);
+            }
+        }
+    }
+}
+
+pub trait Fold {
+    fn fold_block(&mut self, block: BlockStatement) -> BlockStatement {
+        fold_block(self, block)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        fold_statement(self, statement)
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        fold_expression(self, expression)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        fold_pattern(self, pattern)
+    }
+
+    /// Every name in the tree, as for `Visit::visit_identifier`.
+    fn fold_identifier(&mut self, identifier: Identifier) -> Identifier {
+        identifier
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+}
+
+pub fn fold_block<F: Fold + ?Sized>(folder: &mut F, block: BlockStatement) -> BlockStatement {
+    block
+        .into_iter()
+        .map(|statement| folder.fold_statement(statement))
+        .collect()
+}
+
+pub fn fold_statement<F: Fold + ?Sized>(folder: &mut F, statement: Statement) -> Statement {
+    match statement {
+        Statement::Let(pattern, value) => {
+            Statement::Let(folder.fold_pattern(pattern), folder.fold_expression(value))
+        }
+        Statement::Return(value) => Statement::Return(folder.fold_expression(value)),
+        Statement::Yield(value) => Statement::Yield(folder.fold_expression(value)),
+        Statement::Import(name, imports) => Statement::Import(
+            name,
+            match imports {
+                Imports::All => Imports::All,
+                Imports::Module(alias) => Imports::Module(folder.fold_identifier(alias)),
+                Imports::Names(names) => Imports::Names(fold_identifiers(folder, names)),
+            },
+        ),
+        Statement::Expression(value) => Statement::Expression(folder.fold_expression(value)),
+        Statement::Empty => Statement::Empty,
+    }
+}
+
+pub fn fold_expression<F: Fold + ?Sized>(folder: &mut F, expression: Expression) -> Expression {
+    let mut fold_box = |expression: Box<Expression>| Box::new(folder.fold_expression(*expression));
+
+    match expression {
+        Expression::Identifier(name) => Expression::Identifier(folder.fold_identifier(name)),
+        Expression::Literal(literal) => Expression::Literal(folder.fold_literal(literal)),
+        Expression::Prefix(prefix, right) => Expression::Prefix(prefix, fold_box(right)),
+        Expression::Infix(infix, left, right) => {
+            let left = fold_box(left);
+            Expression::Infix(infix, left, fold_box(right))
+        }
+        Expression::If(IfExpression {
+            condition,
+            consequence,
+            alternative,
+        }) => Expression::If(IfExpression {
+            condition: fold_box(condition),
+            consequence: folder.fold_block(consequence),
+            alternative: folder.fold_block(alternative),
+        }),
+        Expression::Function {
+            params,
+            body,
+            generator,
+        } => Expression::Function {
+            params: fold_identifiers(folder, params),
+            body: folder.fold_block(body),
+            generator,
+        },
+        Expression::Call {
+            function,
+            args,
+            named,
+        } => Expression::Call {
+            function: fold_box(function),
+            args: fold_expressions(folder, args),
+            named: named
+                .into_iter()
+                .map(|(name, value)| (folder.fold_identifier(name), folder.fold_expression(value)))
+                .collect(),
+        },
+        Expression::Array(elements) => Expression::Array(fold_expressions(folder, elements)),
+        Expression::Tuple(elements) => Expression::Tuple(fold_expressions(folder, elements)),
+        Expression::Hash(pairs) => Expression::Hash(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (folder.fold_expression(key), folder.fold_expression(value)))
+                .collect(),
+        ),
+        Expression::Index { left, index } => {
+            let left = fold_box(left);
+            Expression::Index {
+                left,
+                index: fold_box(index),
+            }
+        }
+        Expression::Assign { target, value } => Expression::Assign {
+            target: folder.fold_identifier(target),
+            value: Box::new(folder.fold_expression(*value)),
+        },
+        Expression::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let start = fold_box(start);
+            Expression::Range {
+                start,
+                end: fold_box(end),
+                inclusive,
+            }
+        }
+        Expression::Spread(array) => Expression::Spread(fold_box(array)),
+        Expression::Await(promise) => Expression::Await(fold_box(promise)),
+        Expression::Member { object, attribute } => Expression::Member {
+            object: Box::new(folder.fold_expression(*object)),
+            attribute: folder.fold_identifier(attribute),
+        },
+    }
+}
+
+pub fn fold_pattern<F: Fold + ?Sized>(folder: &mut F, pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Identifier(name) => Pattern::Identifier(folder.fold_identifier(name)),
+        Pattern::Array(patterns) => Pattern::Array(fold_patterns(folder, patterns)),
+        Pattern::Tuple(patterns) => Pattern::Tuple(fold_patterns(folder, patterns)),
+        Pattern::Hash(names) => Pattern::Hash(fold_identifiers(folder, names)),
+    }
+}
+
+fn fold_expressions<F: Fold + ?Sized>(
+    folder: &mut F,
+    expressions: Vec<Expression>,
+) -> Vec<Expression> {
+    expressions
+        .into_iter()
+        .map(|expression| folder.fold_expression(expression))
+        .collect()
+}
+
+fn fold_patterns<F: Fold + ?Sized>(folder: &mut F, patterns: Vec<Pattern>) -> Vec<Pattern> {
+    patterns
+        .into_iter()
+        .map(|pattern| folder.fold_pattern(pattern))
+        .collect()
+}
+
+fn fold_identifiers<F: Fold + ?Sized>(folder: &mut F, names: Vec<Identifier>) -> Vec<Identifier> {
+    names
+        .into_iter()
+        .map(|name| folder.fold_identifier(name))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use crate::{
+        ast::{Expression, Identifier, Infix, Literal, Statement},
+        lexer::Lexer,
+        parser::Parser,
+    };
+
+    use super::{fold_expression, Fold, Visit};
+
+    fn parse(source: &str) -> Result<Vec<Statement>> {
+        Parser::new(Lexer::new(source))
+            .parse_program()?
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn visits_every_name() -> Result<()> {
+        struct Names(Vec<String>);
+
+        impl Visit for Names {
+            fn visit_identifier(&mut self, identifier: &Identifier) {
+                self.0.push(identifier.0.to_string());
+            }
+        }
+
+        let mut names = Names(vec![]);
+        for statement in parse(
+            r#"let [a, {b}] = f(x, by: y); let g = fn(p) { if (p) { m.q } else { [...r] } }; import "list" as l"#,
+        )? {
+            names.visit_statement(&statement);
+        }
+        assert_eq!(
+            names.0,
+            ["a", "b", "f", "x", "by", "y", "g", "p", "p", "m", "q", "r", "l"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn folds_constants() -> Result<()> {
+        /// Adds up sums of int literals, innermost first.
+        struct Sums;
+
+        impl Fold for Sums {
+            fn fold_expression(&mut self, expression: Expression) -> Expression {
+                match fold_expression(self, expression) {
+                    Expression::Infix(Infix::Plus, left, right) => match (*left, *right) {
+                        (
+                            Expression::Literal(Literal::Int(left)),
+                            Expression::Literal(Literal::Int(right)),
+                        ) => Expression::Literal(Literal::Int(left + right)),
+                        (left, right) => {
+                            Expression::Infix(Infix::Plus, Box::new(left), Box::new(right))
+                        }
+                    },
+                    expression => expression,
+                }
+            }
+        }
+
+        let folded = parse("let f = fn(x) { [1 + 2 + 3, x + 1 + 1] }")?
+            .into_iter()
+            .map(|statement| Sums.fold_statement(statement).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(folded, ["let f = fn(x) {\n    [6, ((x + 1) + 1)]\n}"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+//! Helpers shared by the integration tests.
+
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use interpreter::{eval::Eval, lexer::Lexer, parser::Parser};
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `source` through `Lexer → Parser → Eval` and returns what it printed,
+/// followed by "=> value" or "ERROR: message".
+pub fn run(source: &str) -> String {
+    let output = Output::default();
+
+    let mut eval = Eval::new();
+    eval.set_output(Box::new(output.clone()));
+
+    let result = Parser::new(Lexer::new(source))
+        .parse_program()
+        .and_then(|program| eval.eval(program));
+
+    let mut transcript = String::from_utf8_lossy(&output.0.borrow()).to_string();
+    match result {
+        Ok(value) => transcript += &format!("=> {}\n", value),
+        Err(error) => transcript += &format!("ERROR: {}\n", error),
+    }
+    transcript
+}
@@ -0,0 +1,37 @@
+//! Runs the complete programs in `examples/` end to end.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::run;
+
+fn run_example(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join(name)
+        .with_extension("monkey");
+
+    run(&fs::read_to_string(&path).unwrap())
+}
+
+#[test]
+fn fibonacci() {
+    assert_eq!(
+        run_example("fibonacci"),
+        "0, 1, 1, 2, 3, 5, 8, 13, 21, 34\n=> 6765\n"
+    );
+}
+
+#[test]
+fn map_reduce() {
+    assert_eq!(run_example("map_reduce"), "[2, 4, 6, 8, 10]\n30\n=> Bob\n");
+}
+
+#[test]
+fn strings() {
+    assert_eq!(
+        run_example("strings"),
+        "monkey business is serious\nserious is business monkey\nhahaha!\nHello, business\n15\n=> true\n"
+    );
+}
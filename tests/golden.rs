@@ -0,0 +1,68 @@
+//! Runs every `tests/golden/*.monkey` program and compares what it printed,
+//! plus its final value or error, against the matching `.out` file.
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the `.out` files from the current output.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::run;
+
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    let mut report = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => {}
+            (e, a) => {
+                report += &format!("  line {}:\n", i + 1);
+                report += &format!("    - {}\n", e.unwrap_or(&"<missing>"));
+                report += &format!("    + {}\n", a.unwrap_or(&"<missing>"));
+            }
+        }
+    }
+    report
+}
+
+#[test]
+fn golden() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    let mut inputs = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "monkey"))
+        .collect::<Vec<_>>();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no golden inputs in {}", dir.display());
+
+    let mut failures = vec![];
+    for input in inputs {
+        let actual = run(&fs::read_to_string(&input).unwrap());
+        let golden = input.with_extension("out");
+
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        if expected != actual {
+            failures.push(format!(
+                "{}:\n{}",
+                input.file_name().unwrap().to_string_lossy(),
+                diff(&expected, &actual)
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden output mismatch (rerun with UPDATE_GOLDEN=1 to accept):\n{}",
+        failures.join("\n")
+    );
+}